@@ -0,0 +1,29 @@
+// esp-hal glue: its attenuation, and the factory calibration from the eFuse.
+// Enable it with the "esp-hal" feature:
+//   adc-cal = { path = "../../libs/adc-cal", features = ["esp-hal"] }
+
+use esp_hal::{analog::adc, efuse::{AdcCalibUnit, Efuse}};
+
+use crate::{Attenuation, Line};
+
+// esp-hal's attenuation -> ours: the same thing
+impl From<adc::Attenuation> for Attenuation {
+    fn from(atten: adc::Attenuation) -> Self {
+        match atten {
+            adc::Attenuation::_0dB => Self::_0dB,
+            adc::Attenuation::_2p5dB => Self::_2p5dB,
+            adc::Attenuation::_6dB => Self::_6dB,
+            adc::Attenuation::_11dB => Self::_11dB,
+        }
+    }
+}
+
+// Factory calibration: the reference point from the eFuse.
+// Old chips don't have it: fall back to nominal values.
+pub fn efuse_line(atten: adc::Attenuation) -> Line {
+    use AdcCalibUnit::ADC1 as UNIT;
+    match Efuse::rtc_calib_cal_code(UNIT, atten) {
+        Some(code) => Line::efuse(atten.into(), code, Efuse::rtc_calib_cal_mv(UNIT, atten)),
+        None => Line::nominal(atten.into()),
+    }
+}
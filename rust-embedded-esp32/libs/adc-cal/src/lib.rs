@@ -0,0 +1,201 @@
+// ADC: raw counts -> millivolts, and how far off they might be.
+//
+// The ESP32 ADC is 12 bit: 0..4095. What voltage that is depends on the attenuation,
+// and on the chip: every chip is a bit different. So:
+// * `Attenuation`: picks the input range. Stay within `recommended_max_mv()`: above that, it's non-linear.
+// * `Line`: raw counts -> mV. Nominal (no calibration), eFuse (factory calibration), or your own points.
+// * `Calibration`: a `Line` per attenuation.
+// * `Samples`: oversampling. Read N times, average: less noise.
+// * `Reading`: millivolts ± uncertainty.
+//
+// Usage:
+//   let cal = Calibration::nominal()
+//       .with(Attenuation::_11dB, Line::efuse(Attenuation::_11dB, cal_code, cal_mv));
+//   // or, with the "esp-hal" feature: the eFuse is read for you. See esp.rs
+//   let cal = Calibration::nominal()
+//       .with(adc::Attenuation::_11dB.into(), adc_cal::esp::efuse_line(adc::Attenuation::_11dB));
+//   let samples = adc_cal::oversample(16, || nb::block!(adc1.read_oneshot(&mut pin)))?;
+//   let reading = cal.read(Attenuation::_11dB, &samples);
+//   info!("{} ± {} mV", reading.mv, reading.uncertainty_mv);
+#![cfg_attr(not(test), no_std)]
+
+pub mod line;
+pub mod samples;
+#[cfg(feature = "esp-hal")]
+pub mod esp;
+
+pub use line::Line;
+pub use samples::{oversample, Samples};
+
+// 12 bit ADC
+pub const ADC_MAX: u16 = (1 << 12) - 1;
+
+// The same attenuations as `esp_hal::analog::adc::Attenuation`. We can't use that one here: no ESP crates on the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attenuation {
+    _0dB,
+    _2p5dB,
+    _6dB,
+    _11dB,
+}
+
+impl Attenuation {
+    pub const ALL: [Self; 4] = [Self::_0dB, Self::_2p5dB, Self::_6dB, Self::_11dB];
+
+    pub const fn index(self) -> usize {
+        self as usize
+    }
+
+    // Datasheet (ESP32-C3): recommended input range. Above it, readings are non-linear.
+    pub const fn recommended_max_mv(self) -> u16 {
+        match self {
+            Self::_0dB => 750,
+            Self::_2p5dB => 1050,
+            Self::_6dB => 1300,
+            Self::_11dB => 2500,
+        }
+    }
+
+    // The eFuse reference point voltage: the chip was calibrated at this voltage in the factory.
+    // See `esp_hal::efuse::rtc_calib_cal_mv()`
+    pub const fn reference_mv(self) -> u16 {
+        match self {
+            Self::_0dB => 400,
+            Self::_2p5dB => 550,
+            Self::_6dB => 750,
+            Self::_11dB => 1370,
+        }
+    }
+
+    // Datasheet (ESP32-C3): error after calibration, within the recommended range
+    pub const fn calibrated_error_mv(self) -> f32 {
+        match self {
+            Self::_11dB => 35.0,
+            _ => 10.0,
+        }
+    }
+}
+
+// Millivolts ± uncertainty
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Reading {
+    pub mv: f32,
+    // Calibration error + noise. Roughly one standard deviation.
+    pub uncertainty_mv: f32,
+    // Outside of the linear range: the ADC clipped, or above the recommended range. Don't trust it.
+    pub out_of_range: bool,
+}
+
+impl Reading {
+    pub fn volts(&self) -> f32 {
+        self.mv / 1000.0
+    }
+}
+
+// A `Line` for every attenuation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Calibration {
+    lines: [Line; 4],
+}
+
+impl Calibration {
+    // No calibration data: typical values
+    pub fn nominal() -> Self {
+        Self { lines: Attenuation::ALL.map(Line::nominal) }
+    }
+
+    // Replace one line: e.g. with eFuse data, or with your own measurements
+    pub fn with(mut self, attenuation: Attenuation, line: Line) -> Self {
+        self.lines[attenuation.index()] = line;
+        self
+    }
+
+    pub fn line(&self, attenuation: Attenuation) -> &Line {
+        &self.lines[attenuation.index()]
+    }
+
+    // A single raw reading
+    pub fn millivolts(&self, attenuation: Attenuation, raw: u16) -> Reading {
+        let mut samples = Samples::new();
+        samples.push(raw);
+        self.read(attenuation, &samples)
+    }
+
+    // Oversampled reading: the average, and the uncertainty
+    pub fn read(&self, attenuation: Attenuation, samples: &Samples) -> Reading {
+        let line = self.line(attenuation);
+        let mv = line.millivolts(samples.mean());
+
+        // Noise: the standard error of the mean, in counts.
+        // It's never better than the quantization error: a 1-count step is ±1/√12 counts.
+        let noise = (samples.std_dev() / libm::sqrtf(samples.len().max(1) as f32))
+            .max(1.0 / libm::sqrtf(12.0));
+        let noise_mv = noise * line.mv_per_count.abs();
+
+        Reading {
+            mv,
+            uncertainty_mv: libm::sqrtf(line.uncertainty_mv * line.uncertainty_mv + noise_mv * noise_mv),
+            out_of_range: samples.clipped() || mv > attenuation.recommended_max_mv() as f32,
+        }
+    }
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self::nominal()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calibration() {
+        // A chip whose eFuse says: 1370 mV reads as 1950
+        let cal = Calibration::nominal()
+            .with(Attenuation::_11dB, Line::efuse(Attenuation::_11dB, 1950, 1370));
+
+        let r = cal.millivolts(Attenuation::_11dB, 1950);
+        assert!((r.mv - 1370.0).abs() < 1e-3);
+        assert!(!r.out_of_range);
+        // Calibration error dominates a single reading
+        assert!(r.uncertainty_mv >= 35.0 && r.uncertainty_mv < 36.0, "{r:?}");
+
+        // Other attenuations: untouched, and way less certain
+        let r = cal.millivolts(Attenuation::_0dB, 2000);
+        assert!((r.mv - 400.0).abs() < 1e-3);
+        assert!(r.uncertainty_mv > 50.0);
+
+        // Above the recommended range: flagged
+        assert!(cal.millivolts(Attenuation::_11dB, 3700).out_of_range);
+        assert!(cal.millivolts(Attenuation::_11dB, ADC_MAX).out_of_range);
+        assert!(cal.millivolts(Attenuation::_0dB, 0).out_of_range);
+    }
+
+    #[test]
+    fn test_oversampling() {
+        // Our own calibration: exact
+        let line = Line::two_point((0, 0.0), (4000, 2000.0), 0.0).unwrap();
+        let cal = Calibration::nominal().with(Attenuation::_11dB, line);
+
+        // Noisy: 1000 ± 10 counts
+        let mut samples = Samples::new();
+        for i in 0..100 {
+            samples.push(if i % 2 == 0 { 990 } else { 1010 });
+        }
+        let r = cal.read(Attenuation::_11dB, &samples);
+        assert!((r.mv - 500.0).abs() < 1e-3);
+        // σ=10 counts = 5mV; 100 samples: 5/√100 = 0.5 mV
+        assert!((r.uncertainty_mv - 0.5).abs() < 0.01, "{r:?}");
+
+        // No noise at all: quantization error remains. 0.5mV/count / √12
+        let mut samples = Samples::new();
+        for _ in 0..100 {
+            samples.push(1000);
+        }
+        let r = cal.read(Attenuation::_11dB, &samples);
+        assert!((r.uncertainty_mv - 0.5 / libm::sqrtf(12.0)).abs() < 1e-3, "{r:?}");
+    }
+}
@@ -0,0 +1,123 @@
+// Raw counts -> millivolts: a straight line. $mV = raw * mv\_per\_count + offset\_mv$
+//
+// Where to get the line from:
+// * `Line::nominal()`: no calibration at all. Typical chip. Can be off by 10%.
+// * `Line::efuse()`: factory calibration. Every ESP32-C3 has a reference point burnt into the eFuse:
+//   "at `reference_mv()`, this chip reads `cal_code`". Read it with `esp_hal::efuse::rtc_calib_cal_code()`.
+//   The point is offset-corrected: read the ADC with the `AdcCalBasic` scheme that removes the offset.
+// * `Line::two_point()`: your own two measurements: e.g. GND and a known reference voltage.
+// * `Line::fit()`: your own N measurements: least squares. The residuals tell how good the line is.
+
+use crate::Attenuation;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Line {
+    pub mv_per_count: f32,
+    pub offset_mv: f32,
+    // How far off the line can be, mV
+    pub uncertainty_mv: f32,
+}
+
+impl Line {
+    // Uncalibrated: the eFuse reference voltage reads as ~2000 counts on a typical chip
+    pub fn nominal(attenuation: Attenuation) -> Self {
+        let mv = attenuation.reference_mv() as f32;
+        Self {
+            mv_per_count: mv / 2000.0,
+            offset_mv: 0.0,
+            // A guess: 10% of the range
+            uncertainty_mv: attenuation.recommended_max_mv() as f32 / 10.0,
+        }
+    }
+
+    // Factory calibration: the eFuse reference point.
+    // `cal_mv`: `esp_hal::efuse::rtc_calib_cal_mv()`, `cal_code`: `esp_hal::efuse::rtc_calib_cal_code()`
+    pub fn efuse(attenuation: Attenuation, cal_code: u16, cal_mv: u16) -> Self {
+        Self {
+            mv_per_count: cal_mv as f32 / cal_code.max(1) as f32,
+            offset_mv: 0.0,
+            uncertainty_mv: attenuation.calibrated_error_mv(),
+        }
+    }
+
+    // A line through two (raw, mV) points. You tell how accurate your reference voltages are.
+    // Returns `None` if the points have the same raw value.
+    pub fn two_point(p1: (u16, f32), p2: (u16, f32), uncertainty_mv: f32) -> Option<Self> {
+        let ((r1, mv1), (r2, mv2)) = ((p1.0 as f32, p1.1), (p2.0 as f32, p2.1));
+        if r1 == r2 {
+            return None;
+        }
+        let mv_per_count = (mv2 - mv1) / (r2 - r1);
+        Some(Self { mv_per_count, offset_mv: mv1 - r1 * mv_per_count, uncertainty_mv })
+    }
+
+    // Least squares fit through N (raw, mV) points.
+    // The uncertainty is the worst residual: how far the farthest point is from the line.
+    // Needs at least 3 points, spread out: with 2, there are no residuals to judge by. Use two_point() then.
+    pub fn fit(points: &[(u16, f32)]) -> Option<Self> {
+        if points.len() < 3 {
+            return None;
+        }
+        let n = points.len() as f32;
+        let mean_raw = points.iter().map(|&(r, _)| r as f32).sum::<f32>() / n;
+        let mean_mv = points.iter().map(|&(_, mv)| mv).sum::<f32>() / n;
+
+        let (mut sxy, mut sxx) = (0.0, 0.0);
+        for &(r, mv) in points {
+            let dx = r as f32 - mean_raw;
+            sxy += dx * (mv - mean_mv);
+            sxx += dx * dx;
+        }
+        if sxx == 0.0 {
+            return None;
+        }
+
+        let mv_per_count = sxy / sxx;
+        let mut line = Self { mv_per_count, offset_mv: mean_mv - mean_raw * mv_per_count, uncertainty_mv: 0.0 };
+        line.uncertainty_mv = points.iter()
+            .map(|&(r, mv)| (line.millivolts(r as f32) - mv).abs())
+            .fold(0.0, f32::max);
+        Some(line)
+    }
+
+    // Raw -> mV. Raw is f32: it may be an average.
+    pub fn millivolts(&self, raw: f32) -> f32 {
+        raw * self.mv_per_count + self.offset_mv
+    }
+
+    // mV -> raw: what reading to expect
+    pub fn raw(&self, mv: f32) -> f32 {
+        (mv - self.offset_mv) / self.mv_per_count
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_two_point() {
+        // GND reads as 20, 2000mV reads as 2420: 1.2 mV per count, offset of 20 counts
+        let line = Line::two_point((20, 0.0), (2420, 2000.0), 5.0).unwrap();
+        assert!((line.mv_per_count - 2000.0 / 2400.0).abs() < 1e-6);
+        assert!((line.millivolts(20.0)).abs() < 1e-3);
+        assert!((line.millivolts(1220.0) - 1000.0).abs() < 1e-3);
+        assert!((line.raw(1000.0) - 1220.0).abs() < 1e-3);
+
+        assert_eq!(Line::two_point((100, 0.0), (100, 1000.0), 0.0), None);
+    }
+
+    #[test]
+    fn test_fit() {
+        // Points on a line, one is off by 3mV
+        let line = Line::fit(&[(0, 100.0), (1000, 600.0), (2000, 1103.0), (3000, 1600.0)]).unwrap();
+        assert!((line.mv_per_count - 0.5).abs() < 0.01, "{line:?}");
+        assert!((line.offset_mv - 100.0).abs() < 3.0, "{line:?}");
+        assert!(line.uncertainty_mv > 1.0 && line.uncertainty_mv <= 3.0, "{line:?}");
+
+        // Not enough points; all at the same raw value
+        assert_eq!(Line::fit(&[(0, 0.0), (1000, 500.0)]), None);
+        assert_eq!(Line::fit(&[(1000, 0.0), (1000, 500.0), (1000, 600.0)]), None);
+    }
+}
@@ -0,0 +1,104 @@
+// Oversampling: read the ADC many times, and average.
+//
+// ADC readings are noisy: ±10 counts is normal. Averaging N readings cuts the noise by √N:
+// 16 samples = 4x less noise. `Samples` also keeps the spread: that's how we know how noisy it was.
+
+use crate::ADC_MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Samples {
+    len: u32,
+    sum: u64,
+    sum_sq: u64,
+    min: u16,
+    max: u16,
+}
+
+impl Samples {
+    pub const fn new() -> Self {
+        Self { len: 0, sum: 0, sum_sq: 0, min: u16::MAX, max: 0 }
+    }
+
+    pub fn push(&mut self, raw: u16) {
+        self.len += 1;
+        self.sum += raw as u64;
+        self.sum_sq += raw as u64 * raw as u64;
+        self.min = self.min.min(raw);
+        self.max = self.max.max(raw);
+    }
+
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Average, counts
+    pub fn mean(&self) -> f32 {
+        if self.len == 0 {
+            return 0.0;
+        }
+        self.sum as f32 / self.len as f32
+    }
+
+    // Standard deviation, counts: how noisy the readings are
+    pub fn std_dev(&self) -> f32 {
+        if self.len < 2 {
+            return 0.0;
+        }
+        // Integer math: no precision lost on large sums
+        let n = self.len as u64;
+        let variance = (n * self.sum_sq - self.sum * self.sum) as f32 / (n * (n - 1)) as f32;
+        libm::sqrtf(variance)
+    }
+
+    // Some reading hit the rail: the real voltage may be beyond it
+    pub fn clipped(&self) -> bool {
+        !self.is_empty() && (self.min == 0 || self.max >= ADC_MAX)
+    }
+}
+
+impl Default for Samples {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Read `n` times.
+// `read`: one ADC reading, e.g. `|| nb::block!(adc1.read_oneshot(&mut pin))`.
+// Fails on the first error.
+pub fn oversample<E>(n: u32, mut read: impl FnMut() -> Result<u16, E>) -> Result<Samples, E> {
+    let mut samples = Samples::new();
+    for _ in 0..n {
+        samples.push(read()?);
+    }
+    Ok(samples)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_samples() {
+        let mut values = [2, 4, 4, 4, 5, 5, 7, 9].into_iter();
+        let samples = oversample(8, || values.next().ok_or(())).unwrap();
+        assert_eq!(samples.len(), 8);
+        assert_eq!(samples.mean(), 5.0);
+        // Sample standard deviation: √(32/7)
+        assert!((samples.std_dev() - libm::sqrtf(32.0 / 7.0)).abs() < 1e-5);
+        assert!(!samples.clipped());
+
+        // Errors propagate
+        let mut values = [1, 2].into_iter();
+        assert_eq!(oversample(3, || values.next().ok_or("empty")), Err("empty"));
+
+        // Empty
+        assert_eq!(Samples::new().mean(), 0.0);
+        assert_eq!(Samples::new().std_dev(), 0.0);
+        assert!(!Samples::new().clipped());
+    }
+}
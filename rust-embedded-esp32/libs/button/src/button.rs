@@ -0,0 +1,109 @@
+// Button on a pin: polling or interrupts.
+//
+// Works with any embedded-hal input pin and a millisecond clock.
+// On the host, we test it with a fake pin and a fake clock.
+
+use embedded_hal::digital::InputPin;
+use crate::{Clock, Config, Detector, Event};
+
+pub struct Button<P, C> {
+    pin: P,
+    clock: C,
+    detector: Detector,
+    // Pressed = LOW: a button to GND with a pull-up. The usual way.
+    active_low: bool,
+}
+
+impl<P: InputPin, C: Clock> Button<P, C> {
+    // A button to GND with a pull-up: pressed = LOW
+    pub fn new(pin: P, clock: C, config: Config) -> Self {
+        Self { pin, clock, detector: Detector::new(config), active_low: true }
+    }
+
+    // A button to VCC with a pull-down: pressed = HIGH
+    pub fn active_high(mut self) -> Self {
+        self.active_low = false;
+        self
+    }
+
+    // The pin: e.g. to clear its interrupt
+    pub fn pin_mut(&mut self) -> &mut P {
+        &mut self.pin
+    }
+
+    pub fn is_pressed(&self) -> bool {
+        self.detector.is_pressed()
+    }
+
+    // Polling mode: read the pin, get an event. Call it every ~10ms.
+    pub fn poll(&mut self) -> Result<Option<Event>, P::Error> {
+        self.on_interrupt()?;
+        Ok(self.tick())
+    }
+
+    // Interrupt mode: call it from the GPIO interrupt handler. Listen to any edge.
+    pub fn on_interrupt(&mut self) -> Result<(), P::Error> {
+        let pressed = self.pin.is_high()? != self.active_low;
+        let now = self.clock.now_ms();
+        self.detector.input(pressed, now);
+        Ok(())
+    }
+
+    // Interrupt mode: call it every ~10ms (at least once per `debounce_ms`) to get the events.
+    // Two events may come at once: call it until it returns `None`.
+    pub fn tick(&mut self) -> Option<Event> {
+        let now = self.clock.now_ms();
+        self.detector.tick(now)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::{cell::Cell, convert::Infallible};
+    use std::rc::Rc;
+
+    // Fake time: the test moves it forward
+    type Time = Rc<Cell<u64>>;
+
+    // Fake pin: pressed (LOW) during the given intervals
+    struct FakePin {
+        time: Time,
+        presses: Vec<(u64, u64)>,
+    }
+
+    impl embedded_hal::digital::ErrorType for FakePin {
+        type Error = Infallible;
+    }
+    impl InputPin for FakePin {
+        fn is_high(&mut self) -> Result<bool, Infallible> {
+            let now = self.time.get();
+            Ok(!self.presses.iter().any(|&(from, to)| (from..to).contains(&now)))
+        }
+        fn is_low(&mut self) -> Result<bool, Infallible> {
+            self.is_high().map(|high| !high)
+        }
+    }
+
+    #[test]
+    fn test_polling() {
+        let time = Time::default();
+        let pin = FakePin { time: time.clone(), presses: vec![(100, 200), (250, 350), (1000, 2000)] };
+        let clock = { let time = time.clone(); move || time.get() };
+        let mut button = Button::new(pin, clock, Config::default());
+
+        let mut events = vec![];
+        while time.get() < 3000 {
+            while let Some(event) = button.poll().unwrap() {
+                events.push((time.get(), event));
+            }
+            time.set(time.get() + 10);
+        }
+        assert_eq!(events, vec![
+            (220, Event::Release),
+            (370, Event::Release), (370, Event::DoubleClick),
+            (1800, Event::LongPress), (2020, Event::Release),
+        ]);
+    }
+}
@@ -0,0 +1,216 @@
+// Debouncing and gestures: a state machine over raw levels and timestamps.
+// No hardware, no clock: easy to test.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Click,
+    DoubleClick,
+    LongPress,
+    Release,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    // A level must hold this long to count
+    pub debounce_ms: u64,
+    // The second click must come within this time after the first release
+    pub double_click_ms: u64,
+    // Hold this long for a long press
+    pub long_press_ms: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self { debounce_ms: 20, double_click_ms: 300, long_press_ms: 800 }
+    }
+}
+
+pub struct Detector {
+    config: Config,
+
+    // Raw level, and since when
+    raw: bool,
+    raw_since_ms: u64,
+    // Debounced level
+    pressed: bool,
+
+    // Current press: when it started, and whether it's already a long press
+    pressed_at_ms: u64,
+    long_press: bool,
+    // A click that may turn into a double-click: when it was released
+    click_at_ms: Option<u64>,
+
+    // Sometimes one update gives two events: Release + DoubleClick. The second one waits here.
+    pending: Option<Event>,
+}
+
+impl Detector {
+    pub const fn new(config: Config) -> Self {
+        Self {
+            config,
+            raw: false, raw_since_ms: 0, pressed: false,
+            pressed_at_ms: 0, long_press: false, click_at_ms: None,
+            pending: None,
+        }
+    }
+
+    // Debounced state
+    pub fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+
+    // Raw input: the level has changed. Call it on every edge (interrupt), or on every poll.
+    // `pressed`: the *logical* level. For a button to GND with a pull-up, it's `pin.is_low()`.
+    pub fn input(&mut self, pressed: bool, now_ms: u64) {
+        if pressed != self.raw {
+            self.raw = pressed;
+            self.raw_since_ms = now_ms;
+        }
+    }
+
+    // Let the time pass: debounce, timeouts. Returns the next event, if any.
+    // Two events may come at the same time: call it again until it returns `None`.
+    pub fn tick(&mut self, now_ms: u64) -> Option<Event> {
+        if let Some(event) = self.pending.take() {
+            return Some(event);
+        }
+
+        // Debounce
+        if self.raw != self.pressed && now_ms.saturating_sub(self.raw_since_ms) >= self.config.debounce_ms {
+            self.pressed = self.raw;
+            // The time of the edge: when it started bouncing
+            let at = self.raw_since_ms;
+            return if self.pressed { self.on_press(at) } else { self.on_release(at) };
+        }
+
+        // Held long enough?
+        if self.pressed && !self.long_press && now_ms.saturating_sub(self.pressed_at_ms) >= self.config.long_press_ms {
+            self.long_press = true;
+            // Click-and-hold: it's all one long press
+            self.click_at_ms = None;
+            return Some(Event::LongPress);
+        }
+
+        // No second click: it was a single click
+        if let Some(at) = self.click_at_ms && !self.pressed && now_ms.saturating_sub(at) >= self.config.double_click_ms {
+            self.click_at_ms = None;
+            return Some(Event::Click);
+        }
+        None
+    }
+
+    // Polling: input + tick
+    pub fn update(&mut self, pressed: bool, now_ms: u64) -> Option<Event> {
+        self.input(pressed, now_ms);
+        self.tick(now_ms)
+    }
+
+    fn on_press(&mut self, at: u64) -> Option<Event> {
+        self.pressed_at_ms = at;
+        self.long_press = false;
+        // Too late for a double click? Then the previous one was a single click.
+        match self.click_at_ms {
+            Some(click) if at.saturating_sub(click) >= self.config.double_click_ms => {
+                self.click_at_ms = None;
+                Some(Event::Click)
+            }
+            _ => None,
+        }
+    }
+
+    fn on_release(&mut self, at: u64) -> Option<Event> {
+        if self.long_press {
+            // Long press: no click
+        } else if self.click_at_ms.take().is_some() {
+            self.pending = Some(Event::DoubleClick);
+        } else {
+            self.click_at_ms = Some(at);
+        }
+        Some(Event::Release)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: Config = Config { debounce_ms: 20, double_click_ms: 300, long_press_ms: 800 };
+
+    // Poll every 5ms until `until`. The button is pressed during the given intervals.
+    fn run(d: &mut Detector, presses: &[(u64, u64)], until: u64) -> Vec<(u64, Event)> {
+        let mut events = vec![];
+        for now in (0..=until).step_by(5) {
+            let pressed = presses.iter().any(|&(from, to)| (from..to).contains(&now));
+            d.input(pressed, now);
+            while let Some(event) = d.tick(now) {
+                events.push((now, event));
+            }
+        }
+        events
+    }
+
+    #[test]
+    fn test_click() {
+        let mut d = Detector::new(CONFIG);
+        let events = run(&mut d, &[(100, 200)], 1000);
+        // Release: after debouncing. Click: once the double-click window is over.
+        assert_eq!(events, vec![(220, Event::Release), (500, Event::Click)]);
+    }
+
+    #[test]
+    fn test_bounce() {
+        // Contacts bounce for 10ms on press and release: still one click
+        let mut d = Detector::new(CONFIG);
+        let events = run(&mut d, &[(100, 105), (110, 200), (205, 210)], 1000);
+        assert_eq!(events, vec![(230, Event::Release), (510, Event::Click)]);
+
+        // A glitch shorter than the debounce time: nothing at all
+        let mut d = Detector::new(CONFIG);
+        assert_eq!(run(&mut d, &[(100, 110)], 1000), vec![]);
+    }
+
+    #[test]
+    fn test_double_click() {
+        let mut d = Detector::new(CONFIG);
+        let events = run(&mut d, &[(100, 200), (300, 400)], 1000);
+        assert_eq!(events, vec![(220, Event::Release), (420, Event::Release), (420, Event::DoubleClick)]);
+
+        // Too slow: two single clicks
+        let mut d = Detector::new(CONFIG);
+        let events = run(&mut d, &[(100, 200), (600, 700)], 1500);
+        assert_eq!(events, vec![
+            (220, Event::Release), (500, Event::Click),
+            (720, Event::Release), (1000, Event::Click),
+        ]);
+    }
+
+    #[test]
+    fn test_long_press() {
+        let mut d = Detector::new(CONFIG);
+        let events = run(&mut d, &[(100, 2000)], 3000);
+        assert_eq!(events, vec![(900, Event::LongPress), (2020, Event::Release)]);
+
+        // Click-and-hold: just a long press
+        let mut d = Detector::new(CONFIG);
+        let events = run(&mut d, &[(100, 200), (300, 1500)], 2000);
+        assert_eq!(events, vec![(220, Event::Release), (1100, Event::LongPress), (1520, Event::Release)]);
+    }
+
+    #[test]
+    fn test_interrupts() {
+        // Interrupt mode: input() on every edge only, tick() every 10ms
+        let mut d = Detector::new(CONFIG);
+        let edges = [(100, true), (102, false), (104, true), (300, false), (450, true), (550, false)];
+        let mut events = vec![];
+        for now in (0..1000).step_by(10) {
+            for &(at, pressed) in edges.iter().filter(|&&(at, _)| (now..now + 10).contains(&at)) {
+                d.input(pressed, at);
+            }
+            while let Some(event) = d.tick(now + 9) {
+                events.push((now + 9, event));
+            }
+        }
+        assert_eq!(events, vec![(329, Event::Release), (579, Event::Release), (579, Event::DoubleClick)]);
+    }
+}
@@ -0,0 +1,24 @@
+// Button: from a bouncy pin to clicks.
+//
+// A mechanical button bounces: one press reads as HIGH/LOW/HIGH/LOW for a few milliseconds.
+// Debouncing: only believe a level once it's been stable for `debounce_ms`.
+// Then, gestures:
+// * `Event::Click`: press and release. Reported once the double-click window is over.
+// * `Event::DoubleClick`: two clicks within `double_click_ms`.
+// * `Event::LongPress`: held for `long_press_ms`. Reported while still held.
+// * `Event::Release`: every release, whatever the gesture.
+//
+// `Detector`: the pure logic. Feed it levels and timestamps.
+// `Button`: owns the pin. Two modes:
+// * Polling: call `poll()` every ~10ms.
+// * Interrupts: call `on_interrupt()` from the GPIO handler (any edge), and `tick()` every ~10ms to get the events.
+#![cfg_attr(not(test), no_std)]
+
+pub mod button;
+pub mod detector;
+
+pub use button::Button;
+pub use detector::{Config, Detector, Event};
+
+// A millisecond clock: see libs/clock
+pub use clock::Clock;
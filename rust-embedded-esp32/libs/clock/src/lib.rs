@@ -0,0 +1,15 @@
+// A millisecond clock: anything that tells the current time.
+//
+// The libs that need time take a `Clock` instead of a timer from a HAL: no hardware, easy to test.
+// Any closure works: `|| Instant::now().duration_since_epoch().as_millis()`
+#![no_std]
+
+pub trait Clock {
+    fn now_ms(&mut self) -> u64;
+}
+
+impl<F: FnMut() -> u64> Clock for F {
+    fn now_ms(&mut self) -> u64 {
+        self()
+    }
+}
@@ -0,0 +1,136 @@
+// Motion events & occupancy: a state machine over pin levels and timestamps.
+// No hardware, no clock: easy to test.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    // New motion: a rising edge that's not a re-trigger of the previous one
+    Motion,
+    // The room was empty, now there's motion. It's a motion, too: you won't get a separate `Motion` for it.
+    Occupied,
+    // No motion for `vacancy_timeout_ms`
+    Vacant,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    // Rising edges within this time after a `Motion` are re-triggers: no new event.
+    pub retrigger_ms: u64,
+    // No motion for this long: vacant.
+    pub vacancy_timeout_ms: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self { retrigger_ms: 5_000, vacancy_timeout_ms: 60_000 }
+    }
+}
+
+pub struct Detector {
+    config: Config,
+    // Pin level at the last update
+    level: bool,
+    // Last reported motion
+    last_motion_ms: Option<u64>,
+    // Last time the pin was HIGH
+    last_active_ms: u64,
+    occupied: bool,
+}
+
+impl Detector {
+    pub const fn new(config: Config) -> Self {
+        Self { config, level: false, last_motion_ms: None, last_active_ms: 0, occupied: false }
+    }
+
+    pub fn is_occupied(&self) -> bool {
+        self.occupied
+    }
+
+    // When the room becomes vacant, if nothing moves until then
+    pub fn vacancy_deadline_ms(&self) -> Option<u64> {
+        (self.occupied && !self.level).then(|| self.last_active_ms + self.config.vacancy_timeout_ms)
+    }
+
+    // Feed the pin level. Returns an event, if any.
+    pub fn update(&mut self, level: bool, now_ms: u64) -> Option<Event> {
+        let rising = level && !self.level;
+        self.level = level;
+        if level {
+            self.last_active_ms = now_ms;
+        }
+
+        if rising {
+            let retrigger = matches!(self.last_motion_ms, Some(t) if now_ms.saturating_sub(t) < self.config.retrigger_ms);
+            if !self.occupied {
+                self.occupied = true;
+                self.last_motion_ms = Some(now_ms);
+                return Some(Event::Occupied);
+            }
+            if !retrigger {
+                self.last_motion_ms = Some(now_ms);
+                return Some(Event::Motion);
+            }
+            return None;
+        }
+
+        if let Some(deadline) = self.vacancy_deadline_ms() && now_ms >= deadline {
+            self.occupied = false;
+            return Some(Event::Vacant);
+        }
+        None
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: Config = Config { retrigger_ms: 1000, vacancy_timeout_ms: 5000 };
+
+    // Feed (time, level) samples, collect the events
+    fn run(d: &mut Detector, samples: &[(u64, bool)]) -> Vec<(u64, Event)> {
+        samples.iter()
+            .filter_map(|&(t, level)| d.update(level, t).map(|e| (t, e)))
+            .collect()
+    }
+
+    #[test]
+    fn test_motion() {
+        let mut d = Detector::new(CONFIG);
+        let events = run(&mut d, &[
+            (0, false), (100, true), (200, true), // someone came in
+            (300, false), (400, true),            // re-trigger: ignored
+            (500, false), (1500, true),           // moving again: new motion
+            (1600, false),
+        ]);
+        assert_eq!(events, vec![(100, Event::Occupied), (1500, Event::Motion)]);
+        assert!(d.is_occupied());
+    }
+
+    #[test]
+    fn test_vacancy() {
+        let mut d = Detector::new(CONFIG);
+        let events = run(&mut d, &[(0, true), (1000, true), (2000, false)]);
+        assert_eq!(events, vec![(0, Event::Occupied)]);
+        // 5s after the pin was last seen HIGH
+        assert_eq!(d.vacancy_deadline_ms(), Some(6000));
+
+        let events = run(&mut d, &[(5000, false), (5999, false), (6000, false), (7000, false)]);
+        assert_eq!(events, vec![(6000, Event::Vacant)]);
+        assert!(!d.is_occupied());
+        assert_eq!(d.vacancy_deadline_ms(), None);
+
+        // Back again: occupied, even within the re-trigger window of nothing
+        let events = run(&mut d, &[(7100, true)]);
+        assert_eq!(events, vec![(7100, Event::Occupied)]);
+    }
+
+    #[test]
+    fn test_no_vacancy_while_high() {
+        // Someone stands right in front of it: the pin stays HIGH. The room is not vacant.
+        let mut d = Detector::new(CONFIG);
+        let events = run(&mut d, &[(0, true), (10_000, true), (20_000, true)]);
+        assert_eq!(events, vec![(0, Event::Occupied)]);
+        assert_eq!(d.vacancy_deadline_ms(), None);
+    }
+}
@@ -0,0 +1,21 @@
+// PIR motion sensor: from a raw HIGH/LOW pin to events you can act on.
+//
+// A PIR sensor (HC-SR501 & co) raises its output when it sees motion, and holds it HIGH for a few seconds.
+// While someone keeps moving, it re-triggers: the output flickers LOW/HIGH.
+// So "the pin is HIGH" is not a very useful signal. Instead:
+// * `Event::Motion`: a new motion. Rising edges right after the previous one are suppressed: one motion, one event.
+// * `Event::Occupied`: first motion in an empty room.
+// * `Event::Vacant`: no motion for `vacancy_timeout_ms`: the room is empty.
+//
+// `Detector`: the pure logic. Feed it pin levels and timestamps.
+// `Pir`: owns the pin, and gives you `async` `next_event()`, `wait_for_motion()`, `wait_for_vacancy()`.
+#![cfg_attr(not(test), no_std)]
+
+pub mod detector;
+pub mod sensor;
+
+pub use detector::{Config, Detector, Event};
+pub use sensor::Pir;
+
+// A millisecond clock: see libs/clock
+pub use clock::Clock;
@@ -0,0 +1,140 @@
+// PIR sensor on a pin: async events.
+//
+// Polls the pin every `poll_ms`: the PIR holds its output for seconds, so 50ms is plenty.
+// Works with any embedded-hal input pin and an async delay: e.g. `embassy_time::Delay`.
+// On the host, we test it with a simulated pin and clock.
+
+use embedded_hal::digital::InputPin;
+use embedded_hal_async::delay::DelayNs;
+use crate::{Clock, Config, Detector, Event};
+
+pub struct Pir<P, D, C> {
+    pin: P,
+    delay: D,
+    clock: C,
+    detector: Detector,
+    pub poll_ms: u32,
+}
+
+impl<P: InputPin, D: DelayNs, C: Clock> Pir<P, D, C> {
+    pub fn new(pin: P, delay: D, clock: C, config: Config) -> Self {
+        Self { pin, delay, clock, detector: Detector::new(config), poll_ms: 50 }
+    }
+
+    pub fn is_occupied(&self) -> bool {
+        self.detector.is_occupied()
+    }
+
+    // Wait for the next event: motion, occupied, vacant
+    pub async fn next_event(&mut self) -> Result<Event, P::Error> {
+        loop {
+            let level = self.pin.is_high()?;
+            if let Some(event) = self.detector.update(level, self.clock.now_ms()) {
+                return Ok(event);
+            }
+            self.delay.delay_ms(self.poll_ms).await;
+        }
+    }
+
+    // Wait for motion: a new one, or the one that makes the room occupied
+    pub async fn wait_for_motion(&mut self) -> Result<(), P::Error> {
+        loop {
+            if let Event::Motion | Event::Occupied = self.next_event().await? {
+                return Ok(());
+            }
+        }
+    }
+
+    // Wait until the room is empty. Returns right away if it is already.
+    pub async fn wait_for_vacancy(&mut self) -> Result<(), P::Error> {
+        while self.detector.is_occupied() {
+            self.next_event().await?;
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::{cell::Cell, convert::Infallible};
+    use std::rc::Rc;
+    use embassy_futures::block_on;
+
+    // Simulated time: the delay moves it forward
+    type Time = Rc<Cell<u64>>;
+
+    // Simulated pin: plays a script of (time, level) changes
+    struct SimPin {
+        time: Time,
+        script: Vec<(u64, bool)>,
+    }
+
+    impl embedded_hal::digital::ErrorType for SimPin {
+        type Error = Infallible;
+    }
+    impl InputPin for SimPin {
+        fn is_high(&mut self) -> Result<bool, Infallible> {
+            let now = self.time.get();
+            Ok(self.script.iter().rev().find(|&&(t, _)| t <= now).is_some_and(|&(_, level)| level))
+        }
+        fn is_low(&mut self) -> Result<bool, Infallible> {
+            self.is_high().map(|high| !high)
+        }
+    }
+
+    struct SimDelay(Time);
+
+    impl DelayNs for SimDelay {
+        async fn delay_ns(&mut self, ns: u32) {
+            self.0.set(self.0.get() + (ns / 1_000_000) as u64);
+        }
+    }
+
+    fn pir(script: &[(u64, bool)]) -> (Pir<SimPin, SimDelay, impl Clock>, Time) {
+        let time = Time::default();
+        let pin = SimPin { time: time.clone(), script: script.to_vec() };
+        let clock = { let time = time.clone(); move || time.get() };
+        let config = Config { retrigger_ms: 1000, vacancy_timeout_ms: 5000 };
+        (Pir::new(pin, SimDelay(time.clone()), clock, config), time)
+    }
+
+    #[test]
+    fn test_wait_for_motion() {
+        let (mut pir, time) = pir(&[(1000, true), (1200, false), (1400, true), (1500, false), (3000, true)]);
+        block_on(pir.wait_for_motion()).unwrap();
+        assert_eq!(time.get(), 1000);
+        assert!(pir.is_occupied());
+
+        // 1400 is a re-trigger
+        block_on(pir.wait_for_motion()).unwrap();
+        assert_eq!(time.get(), 3000);
+    }
+
+    #[test]
+    fn test_wait_for_vacancy() {
+        let (mut pir, time) = pir(&[(100, true), (2000, false)]);
+        // Empty from the start
+        block_on(pir.wait_for_vacancy()).unwrap();
+        assert_eq!(time.get(), 0);
+
+        block_on(pir.wait_for_motion()).unwrap();
+        assert_eq!(time.get(), 100);
+
+        // Last seen HIGH at 1950 (polled every 50ms), +5s
+        block_on(pir.wait_for_vacancy()).unwrap();
+        assert_eq!(time.get(), 6950);
+        assert!(!pir.is_occupied());
+    }
+
+    #[test]
+    fn test_events() {
+        let (mut pir, time) = pir(&[(0, true), (500, false), (2000, true), (2100, false)]);
+        let mut events = vec![];
+        for _ in 0..3 {
+            events.push((block_on(pir.next_event()).unwrap(), time.get()));
+        }
+        assert_eq!(events, vec![(Event::Occupied, 0), (Event::Motion, 2000), (Event::Vacant, 7050)]);
+    }
+}
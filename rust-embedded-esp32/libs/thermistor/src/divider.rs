@@ -0,0 +1,62 @@
+// Voltage divider: how the thermistor is wired. Measured voltage -> thermistor resistance.
+//
+// NTC on the low side (`NtcLow`):
+//                         ┌──────────────── ADC
+//   V_DD ──────[ R_1 ]────┴───[ R_T ]────── GND
+//   $V = V_{DD} * R_T / (R_1 + R_T)$  =>  $R_T = R_1 * V / (V_{DD} - V)$
+//   Hotter: lower R_T, lower voltage.
+//
+// NTC on the high side (`NtcHigh`):
+//                         ┌──────────────── ADC
+//   V_DD ──────[ R_T ]────┴───[ R_1 ]────── GND
+//   $V = V_{DD} * R_1 / (R_1 + R_T)$  =>  $R_T = R_1 * (V_{DD} - V) / V$
+//   Hotter: lower R_T, higher voltage.
+
+use crate::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Topology {
+    NtcLow,
+    NtcHigh,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Divider {
+    pub topology: Topology,
+    // Supply voltage, V
+    pub vdd: f64,
+    // The fixed resistor, Ohm
+    pub r_fixed: f64,
+}
+
+impl Divider {
+    // Thermistor resistance from the measured voltage, Ohm.
+    // At 0V or V_DD one of the resistors is either open or shorted: no sensible answer.
+    pub fn resistance(&self, voltage: f64) -> Result<f64, Error> {
+        if voltage <= 0.0 || voltage.is_nan() {
+            return Err(match self.topology {
+                Topology::NtcLow => Error::ShortCircuit,
+                Topology::NtcHigh => Error::OpenCircuit,
+            });
+        }
+        if voltage >= self.vdd {
+            return Err(match self.topology {
+                Topology::NtcLow => Error::OpenCircuit,
+                Topology::NtcHigh => Error::ShortCircuit,
+            });
+        }
+
+        Ok(match self.topology {
+            Topology::NtcLow => self.r_fixed * voltage / (self.vdd - voltage),
+            Topology::NtcHigh => self.r_fixed * (self.vdd - voltage) / voltage,
+        })
+    }
+
+    // Expected voltage for the given thermistor resistance: the inverse of resistance()
+    pub fn voltage(&self, r_thermistor: f64) -> f64 {
+        match self.topology {
+            Topology::NtcLow => self.vdd * r_thermistor / (self.r_fixed + r_thermistor),
+            Topology::NtcHigh => self.vdd * self.r_fixed / (self.r_fixed + r_thermistor),
+        }
+    }
+}
@@ -0,0 +1,115 @@
+// NTC thermistor: voltage divider + a temperature model.
+//
+// Usage:
+//   let thermistor = Thermistor {
+//       divider: Divider { topology: Topology::NtcLow, vdd: 3.3, r_fixed: 10_000.0 },
+//       model: BParameter::NTC_103_B3950,
+//   };
+//   let celsius = thermistor.temperature_c(voltage)?;
+//
+// Voltage comes from the ADC: convert raw counts to volts first.
+#![cfg_attr(not(test), no_std)]
+
+pub mod divider;
+pub mod model;
+
+pub use divider::{Divider, Topology};
+pub use model::{BParameter, Model, SteinhartHart};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+    // Voltage says the thermistor is disconnected
+    OpenCircuit,
+    // Voltage says the thermistor is shorted
+    ShortCircuit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Thermistor<M: Model> {
+    pub divider: Divider,
+    pub model: M,
+}
+
+impl<M: Model> Thermistor<M> {
+    // Temperature, °C, from the voltage measured on the divider
+    pub fn temperature_c(&self, voltage: f64) -> Result<f64, Error> {
+        let resistance = self.divider.resistance(voltage)?;
+        Ok(self.model.temperature_c(resistance))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Datasheet: Semitec 103AT-2. 10k at 25°C, B25/85 = 3435.
+    // (°C, Ohm)
+    const SEMITEC_103AT: [(f64, f64); 15] = [
+        (-30.0, 111_300.0), (-20.0, 67_770.0), (-10.0, 42_470.0), (0.0, 27_280.0),
+        (10.0, 17_960.0), (20.0, 12_090.0), (25.0, 10_000.0), (30.0, 8_313.0),
+        (40.0, 5_828.0), (50.0, 4_161.0), (60.0, 3_021.0), (70.0, 2_229.0),
+        (80.0, 1_669.0), (90.0, 1_266.0), (100.0, 973.5),
+    ];
+
+    #[test]
+    fn test_steinhart_hart_vs_datasheet() {
+        // Fit to 3 points, check against the whole table
+        let sh = SteinhartHart::fit([(0.0, 27_280.0), (25.0, 10_000.0), (100.0, 973.5)]).unwrap();
+        for (t, r) in SEMITEC_103AT {
+            let error = sh.temperature_c(r) - t;
+            assert!(error.abs() < 0.15, "{t}°C: off by {error}");
+
+            // Inverse
+            let r_back = sh.resistance(t);
+            assert!((r_back - r).abs() / r < 0.01, "{t}°C: {r_back} vs {r}");
+        }
+
+        // Garbage in, nothing out
+        assert_eq!(SteinhartHart::fit([(0.0, 10_000.0), (25.0, 10_000.0), (50.0, 10_000.0)]), None);
+    }
+
+    #[test]
+    fn test_b_parameter_vs_datasheet() {
+        // B-parameter is good near 25°C, and drifts away at the extremes
+        let b = BParameter { b: 3435.0, r0: 10_000.0, t0_c: 25.0 };
+        for (t, r) in SEMITEC_103AT {
+            let error = b.temperature_c(r) - t;
+            let tolerance = if (10.0..=40.0).contains(&t) { 0.6 } else { 3.5 };
+            assert!(error.abs() < tolerance, "{t}°C: off by {error}");
+        }
+        assert!((b.resistance(25.0) - 10_000.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_divider() {
+        let low = Divider { topology: Topology::NtcLow, vdd: 3.3, r_fixed: 10_000.0 };
+        let high = Divider { topology: Topology::NtcHigh, ..low };
+
+        // Equal resistors: half the voltage
+        assert!((low.resistance(1.65).unwrap() - 10_000.0).abs() < 1e-6);
+        assert!((high.resistance(1.65).unwrap() - 10_000.0).abs() < 1e-6);
+        // Round trip
+        for r in [1_000.0, 27_280.0, 111_300.0] {
+            assert!((low.resistance(low.voltage(r)).unwrap() - r).abs() < 1e-6);
+            assert!((high.resistance(high.voltage(r)).unwrap() - r).abs() < 1e-6);
+        }
+
+        // Rails
+        assert_eq!(low.resistance(0.0), Err(Error::ShortCircuit));
+        assert_eq!(low.resistance(3.3), Err(Error::OpenCircuit));
+        assert_eq!(high.resistance(0.0), Err(Error::OpenCircuit));
+        assert_eq!(high.resistance(3.3), Err(Error::ShortCircuit));
+    }
+
+    #[test]
+    fn test_thermistor() {
+        let thermistor = Thermistor {
+            divider: Divider { topology: Topology::NtcLow, vdd: 3.3, r_fixed: 10_000.0 },
+            model: BParameter::NTC_103_B3950,
+        };
+        assert!((thermistor.temperature_c(1.65).unwrap() - 25.0).abs() < 1e-9);
+        // Lower voltage: lower resistance: hotter
+        assert!(thermistor.temperature_c(1.0).unwrap() > 25.0);
+    }
+}
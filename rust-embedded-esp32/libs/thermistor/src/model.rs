@@ -0,0 +1,86 @@
+// Thermistor models: resistance <-> temperature.
+//
+// * `BParameter`: $1/T = 1/T_0 + (1/B) * ln(R/R_0)$
+//   Two numbers from the datasheet: B and R_0 at T_0 (usually 25°C).
+//   Accurate near T_0, off by a few degrees at the extremes.
+// * `SteinhartHart`: $1/T = A + B * ln(R) + C * ln(R)^3$
+//   Accurate to ~0.1°C over a wide range. Get A, B, C with `SteinhartHart::fit()`
+//   from three points: from the datasheet table, or your own calibration.
+//
+// Temperatures are in °C, resistances in Ohm.
+use libm::{cbrt, exp, log, pow, sqrt};
+
+// Zero Celsius, in Kelvin
+pub const KELVIN: f64 = 273.15;
+
+pub trait Model {
+    // Temperature at the given resistance
+    fn temperature_c(&self, resistance: f64) -> f64;
+    // Resistance at the given temperature
+    fn resistance(&self, temperature_c: f64) -> f64;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BParameter {
+    pub b: f64,
+    // Resistance at the reference temperature
+    pub r0: f64,
+    pub t0_c: f64,
+}
+
+impl BParameter {
+    // The popular "NTC 103" thermistor: 10k at 25°C, B=3950
+    pub const NTC_103_B3950: Self = Self { b: 3950.0, r0: 10_000.0, t0_c: 25.0 };
+}
+
+impl Model for BParameter {
+    fn temperature_c(&self, resistance: f64) -> f64 {
+        let inv_t = 1.0 / (self.t0_c + KELVIN) + log(resistance / self.r0) / self.b;
+        1.0 / inv_t - KELVIN
+    }
+
+    fn resistance(&self, temperature_c: f64) -> f64 {
+        self.r0 * exp(self.b * (1.0 / (temperature_c + KELVIN) - 1.0 / (self.t0_c + KELVIN)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SteinhartHart {
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+}
+
+impl SteinhartHart {
+    // Fit the coefficients to three (temperature °C, resistance) points.
+    // Pick them far apart: e.g. 0°C, 25°C, 100°C.
+    // Returns `None` if the points don't make sense: same resistance twice, etc.
+    pub fn fit(points: [(f64, f64); 3]) -> Option<Self> {
+        let [(t1, r1), (t2, r2), (t3, r3)] = points;
+        let (l1, l2, l3) = (log(r1), log(r2), log(r3));
+        let (y1, y2, y3) = (1.0 / (t1 + KELVIN), 1.0 / (t2 + KELVIN), 1.0 / (t3 + KELVIN));
+
+        let g2 = (y2 - y1) / (l2 - l1);
+        let g3 = (y3 - y1) / (l3 - l1);
+        let c = (g3 - g2) / (l3 - l2) / (l1 + l2 + l3);
+        let b = g2 - c * (l1 * l1 + l1 * l2 + l2 * l2);
+        let a = y1 - (b + l1 * l1 * c) * l1;
+
+        let fitted = Self { a, b, c };
+        [a, b, c].iter().all(|x| x.is_finite()).then_some(fitted)
+    }
+}
+
+impl Model for SteinhartHart {
+    fn temperature_c(&self, resistance: f64) -> f64 {
+        let l = log(resistance);
+        1.0 / (self.a + self.b * l + self.c * l * l * l) - KELVIN
+    }
+
+    fn resistance(&self, temperature_c: f64) -> f64 {
+        // Solve the cubic for ln(R)
+        let x = (self.a - 1.0 / (temperature_c + KELVIN)) / self.c;
+        let y = sqrt(pow(self.b / (3.0 * self.c), 3.0) + x * x / 4.0);
+        exp(cbrt(y - x / 2.0) - cbrt(y + x / 2.0))
+    }
+}
@@ -46,6 +46,10 @@ use esp_hal::{
     main,
 };
 
+// Debounced button: click, double-click, long press
+// Lives in ../../libs/button
+use button::{Button, Config as ButtonConfig, Event as ButtonEvent};
+
 // This creates a default app-descriptor required by the esp-idf bootloader.
 // For more information see: <https://docs.espressif.com/projects/esp-idf/en/stable/esp32/api-reference/system/app_image_format.html#application-description>
 esp_bootloader_esp_idf::esp_app_desc!();
@@ -121,7 +125,7 @@ fn main() -> ! {
     // We will use the BOOT button on GPIO9.
     // With pull-up: defaults to HIGH when nothing's connected; reads LOW when connected to ground.
     // Otherwise, when not grounded, will read random noise.
-    let mut button = Input::new(peripherals.GPIO9, InputConfig::default().with_pull(Pull::Up));
+    let button = Input::new(peripherals.GPIO9, InputConfig::default().with_pull(Pull::Up));
 
     loop {
         if button.is_high() {
@@ -136,6 +140,26 @@ fn main() -> ! {
         }
     }
 
+    //=== Example: Debounced button (polling)
+    // A raw `is_high()` bounces: one press may read as several. And it knows nothing about clicks.
+    // `Button` debounces, and recognizes gestures. Poll it every ~10ms.
+    println!("Click, double-click or long-press...");
+    let mut button = Button::new(button, now_ms as fn() -> u64, ButtonConfig::default());
+
+    loop {
+        match button.poll() {
+            Ok(Some(ButtonEvent::Click)) => led.toggle(),
+            Ok(Some(event)) => println!("{event:?}"),
+            Ok(None) => {},
+            Err(err) => match err {},  // Infallible
+        }
+        delay.delay_millis(10);
+
+        if started_at.elapsed() > Duration::from_secs(15) {
+            break
+        }
+    }
+
     //=== Example: Detect button press with interrupt
     println!("Blinking when you press (interrupt)...");
 
@@ -145,27 +169,37 @@ fn main() -> ! {
     io.set_interrupt_handler(handler);
 
     // Use a critical section to do things atomically.
-    // We start listening on GPIO events; at the same time we provide the `Input` object to the handler.
+    // We start listening on GPIO events; at the same time we provide the `Button` object to the handler.
     // `critical_section` Disables interrupts temporarily.
     // No interrupt can fire mid-execution.
     critical_section::with(|cs| {
-        // Listen for interrupts.
-        button.listen(Event::FallingEdge);
+        // Listen for interrupts: both edges. The debouncer needs to see presses and releases.
+        button.pin_mut().listen(Event::AnyEdge);
 
-        // Use the static variable to pass the Input that has fired the handler.
-        // Replace the actual value of the Option<Input>.
+        // Use the static variable to pass the Button that has fired the handler.
+        // Replace the actual value of the Option<Button>.
         BUTTON.borrow_ref_mut(cs).replace(button);
-
-        // Also provide the led
-        LED.borrow_ref_mut(cs).replace(led);
     });
 
-    // Keep waiting
+    // The handler only records the edges: it must be quick.
+    // Debouncing needs time to pass, so here we check for events every 10ms.
     loop {
+        let event = critical_section::with(|cs| {
+            BUTTON.borrow_ref_mut(cs).as_mut().and_then(|button| button.tick())
+        });
+        match event {
+            Some(ButtonEvent::Click) => led.toggle(),
+            Some(ButtonEvent::DoubleClick) => led.set_high(),
+            Some(ButtonEvent::LongPress) => led.set_low(),
+            Some(event) => println!("{event:?}"),
+            None => delay.delay_millis(10),
+        }
     }
+}
 
-    // main() must not quit
-    panic!("Done");
+// Milliseconds since boot: the button's clock
+fn now_ms() -> u64 {
+    Instant::now().duration_since_epoch().as_millis()
 }
 
 // Static button:
@@ -174,11 +208,8 @@ fn main() -> ! {
 // - RefCell = runtime borrow checking.
 // - Mutex = critical section wrapper for interrupt safety.
 // Together they let you safely share mutable state between main() and interrupt handlers without data races.
-static BUTTON: Mutex<RefCell<Option<Input>>> = Mutex::new(RefCell::new(None));
-
-// Static LED.
-// The main() fn will pass it to us.
-static LED: Mutex<RefCell<Option<Output>>> = Mutex::new(RefCell::new(None));
+type BootButton = Button<Input<'static>, fn() -> u64>;
+static BUTTON: Mutex<RefCell<Option<BootButton>>> = Mutex::new(RefCell::new(None));
 
 // Interrupt handler
 // One interrupt handler to rule them all
@@ -187,10 +218,8 @@ fn handler() {
     // `critical_section` Disables interrupts temporarily.
     // No interrupt can fire mid-execution.
     critical_section::with(|cs| {
-        println!("GPIO interrupt");
-
         // Do we have a button presset?
-        // Get the `Input` passed to us through the mutable `Option<Input>`
+        // Get the `Button` passed to us through the mutable `Option<Button>`
         let mut button = BUTTON.borrow_ref_mut(cs);
         let Some(button) = button.as_mut() else {
             // Some other interrupt has occurred
@@ -198,22 +227,14 @@ fn handler() {
             return;
         };
 
-        if button.is_interrupt_set() {
-            println!("Button pressed");
-
-            // If you're listening to EventLow/EventHigh, events will keep firing.
-            // Unlisten to stop the flow.
-            // button.unlisten();
-        }
-
-        // LED toggle
-        if let Some(led) = LED.borrow_ref_mut(cs).as_mut() {
-            led.toggle();
+        if button.pin_mut().is_interrupt_set() {
+            // Record the edge. Events come out of `button.tick()` in main()
+            button.on_interrupt().ok();
         }
 
         // Clear the interrupt status bit for this pin.
         // Hardware sets an interrupt flag when the event occurs.
         // If you don't clear it, the interrupt fires again immediately in an infinite loop.
-        button.clear_interrupt();
+        button.pin_mut().clear_interrupt();
     });
 }
@@ -1,3 +1,9 @@
 fn main() {
+    // ESP-only stuff. Host builds (unit-tests) don't need it.
+    // NOTE: build.rs runs on the host, so check the *target* OS via the env, not #[cfg]
+    if std::env::var("CARGO_CFG_TARGET_OS").as_deref() != Ok("espidf") {
+        return;
+    }
+
     embuild::espidf::sysenv::output();
 }
@@ -1,4 +1,16 @@
+// Pure logic: runs on the host too
+pub mod ws2812;
+pub mod matrix;
+pub mod metrics;
+
+// ESP-IDF drivers
+#[cfg(target_os = "espidf")]
 pub mod rgb_led;
+#[cfg(target_os = "espidf")]
 pub mod wifi;
+#[cfg(target_os = "espidf")]
 pub mod http_client;
+#[cfg(target_os = "espidf")]
 pub mod blinky_led;
+#[cfg(target_os = "espidf")]
+pub mod system_metrics;
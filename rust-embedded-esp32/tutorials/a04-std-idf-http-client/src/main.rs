@@ -31,7 +31,15 @@ use anyhow::{bail, Result};
 
 // Our libraries
 use a04_std_idf_http_client::{
-    blinky_led, http_client, rgb_led::{self, RGB8}, wifi
+    blinky_led, http_client, rgb_led::{self, RGB8}, wifi,
+    matrix::{LedMatrix, Layout, Rotation},
+};
+
+// Draw on LED panels
+use embedded_graphics::{
+    pixelcolor::Rgb888,
+    prelude::*,
+    primitives::{PrimitiveStyle, Rectangle},
 };
 
 // App: will connect to WiFi, blink the LED green/red to show the outcome.
@@ -63,8 +71,11 @@ fn main() -> Result<()> {
     blue_led_output.set_low()?;
     let mut blink = blinky_led::BlinkyLed::new(blue_led_output)?;
 
-    // Driver: RGB LED
-    let mut led = rgb_led::WS2812RMT::new(rgb_led_pin, rgb_led_rmt0)?;
+    // Driver: RGB LED.
+    // Pixel #0 is the onboard LED. Chain a 8x8 LED matrix to the same pin: that's pixels #1..#64.
+    let mut led = rgb_led::WS2812Strip::<{ 1 + 64 }>::new(rgb_led_pin, rgb_led_rmt0)?;
+    // Don't burn our eyes (and the USB port)
+    led.set_brightness(64);
 
     // Log
     log::info!("Board starting...");
@@ -74,21 +85,42 @@ fn main() -> Result<()> {
     // LED: blink yellow.
     // Use our module: rgb_led
     for _ in 0..3 {
-        // NOTE: This is inefficient. WS2812Z protocol supports animations.
-        led.set_pixel(RGB8::new(50, 50, 0))?;
+        // Draw into the frame buffer, then send it
+        led.set_pixel(0, RGB8::new(200, 200, 0));
+        led.show()?;
 
         // Sleep using FreeRTOS.
         // It hopefully uses proper wait.
         FreeRtos::delay_ms(200);
 
         // Turn off
-        led.set_pixel(RGB8::new(0, 0, 0))?;
+        led.set_pixel(0, RGB8::new(0, 0, 0));
+        led.show()?;
         FreeRtos::delay_ms(200);
     }
 
-    // LED: Control a 64x64 LED matrix of addressable LEDsw
-    log::info!("LED Heart 64x64");
-    led.heart64x64()?;
+    // LED: Control a 8x8 LED matrix of addressable LEDs.
+    // It's a `DrawTarget`: draw on it with embedded-graphics, like on the OLED display.
+    log::info!("LED Heart 8x8");
+    let mut matrix = LedMatrix::<8, 8>::new(Layout::Serpentine, Rotation::R0);
+    matrix.fill_contiguous(&matrix.bounding_box(), HEART)?;
+    matrix.render(&mut led.pixels_mut()[1..]);
+    led.show()?;
+    FreeRtos::delay_ms(1000);
+
+    // Draw shapes, rotate the panel
+    for rotation in [Rotation::R0, Rotation::R90, Rotation::R180, Rotation::R270] {
+        matrix.set_rotation(rotation);
+        matrix.clear(Rgb888::BLACK)?;
+        Rectangle::new(Point::zero(), matrix.size())
+            .into_styled(PrimitiveStyle::with_stroke(Rgb888::BLUE, 1))
+            .draw(&mut matrix)?;
+        // A dot in the top-left corner: shows where "up" is
+        Pixel(Point::new(2, 2), Rgb888::GREEN).draw(&mut matrix)?;
+        matrix.render(&mut led.pixels_mut()[1..]);
+        led.show()?;
+        FreeRtos::delay_ms(500);
+    }
 
     //=== WiFI connect ===//
 
@@ -107,7 +139,8 @@ fn main() -> Result<()> {
         Ok(inner) => inner,
         Err(err) => {
             // Red!
-            led.set_pixel(RGB8::new(50, 0, 0))?;
+            led.set_pixel(0, RGB8::new(200, 0, 0));
+            led.show()?;
             bail!("Could not connect to Wi-Fi network: {:?}", err);
 
             // We panicked.
@@ -148,3 +181,19 @@ fn main() -> Result<()> {
         FreeRtos::delay_ms(1000); // let other tasks run
     }
 }
+
+// A heart, 8x8, row by row
+const HEART: [Rgb888; 64] = {
+    const R: Rgb888 = Rgb888::RED;
+    const B: Rgb888 = Rgb888::BLACK;
+    [
+        B,R,R,B,B,R,R,B,
+        R,B,B,R,R,B,B,R,
+        R,B,B,B,B,B,B,R,
+        R,B,B,B,B,B,B,R,
+        B,R,B,B,B,B,R,B,
+        B,B,R,B,B,R,B,B,
+        B,B,B,R,R,B,B,B,
+        B,B,B,B,B,B,B,B,
+    ]
+};
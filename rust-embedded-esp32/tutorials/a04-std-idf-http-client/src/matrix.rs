@@ -0,0 +1,203 @@
+// LED matrix: a 2D canvas on top of a 1D strip of WS2812 LEDs.
+//
+// An LED panel is just a strip folded into rows. Two common ways to wire it:
+// * Row-major: every row goes left to right: the wire jumps back at the end of each row.
+// * Serpentine ("zig-zag"): even rows go left to right, odd rows go right to left. Most panels are like this.
+//
+// `LedMatrix` implements embedded-graphics' `DrawTarget`: the same text, shapes and BMP images
+// that we draw on the OLED display (see b10) can be drawn on an LED panel.
+//
+// Double buffering: you draw into the back buffer; the LEDs don't see it until you `render()`
+// it into the strip's frame buffer, all at once. No half-drawn frames.
+//
+// Usage:
+//   let mut matrix = LedMatrix::<8, 8>::new(Layout::Serpentine, Rotation::R0);
+//   matrix.clear(Rgb888::BLACK)?;
+//   Circle::new(Point::new(1, 1), 6).into_styled(PrimitiveStyle::with_stroke(Rgb888::RED, 1)).draw(&mut matrix)?;
+//   matrix.render(strip.pixels_mut());
+//   strip.show()?;
+
+use core::convert::Infallible;
+use embedded_graphics::{pixelcolor::Rgb888, prelude::*};
+use crate::ws2812::RGB8;
+
+// How the strip is folded into rows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    RowMajor,
+    Serpentine,
+}
+
+// How the panel is mounted: the picture is rotated clockwise
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    R0,
+    R90,
+    R180,
+    R270,
+}
+
+// W x H: the physical panel, in LEDs
+pub struct LedMatrix<const W: usize, const H: usize> {
+    layout: Layout,
+    rotation: Rotation,
+    // Back buffer: physical rows, not rotated
+    back: [[Rgb888; W]; H],
+}
+
+impl<const W: usize, const H: usize> LedMatrix<W, H> {
+    pub const fn new(layout: Layout, rotation: Rotation) -> Self {
+        Self { layout, rotation, back: [[Rgb888::BLACK; W]; H] }
+    }
+
+    pub fn set_rotation(&mut self, rotation: Rotation) {
+        self.rotation = rotation;
+    }
+
+    // Physical (x, y) -> LED index on the strip
+    pub fn index(&self, x: usize, y: usize) -> usize {
+        let x = match self.layout {
+            Layout::Serpentine if y % 2 == 1 => W - 1 - x,
+            _ => x,
+        };
+        y * W + x
+    }
+
+    // Logical point, as drawn -> physical (x, y). `None`: off the panel.
+    fn physical(&self, p: Point) -> Option<(usize, usize)> {
+        let size = self.size();
+        if p.x < 0 || p.y < 0 || p.x >= size.width as i32 || p.y >= size.height as i32 {
+            return None;
+        }
+        let (x, y) = (p.x as usize, p.y as usize);
+        Some(match self.rotation {
+            Rotation::R0 => (x, y),
+            Rotation::R90 => (W - 1 - y, x),
+            Rotation::R180 => (W - 1 - x, H - 1 - y),
+            Rotation::R270 => (y, H - 1 - x),
+        })
+    }
+
+    // Copy the back buffer into the strip's frame buffer, in wiring order.
+    // `front`: W*H pixels. Then `show()` the strip.
+    pub fn render(&self, front: &mut [RGB8]) {
+        for (y, row) in self.back.iter().enumerate() {
+            for (x, c) in row.iter().enumerate() {
+                if let Some(pixel) = front.get_mut(self.index(x, y)) {
+                    *pixel = RGB8::new(c.r(), c.g(), c.b());
+                }
+            }
+        }
+    }
+}
+
+impl<const W: usize, const H: usize> OriginDimensions for LedMatrix<W, H> {
+    // Rotated 90°: width and height swap
+    fn size(&self) -> Size {
+        match self.rotation {
+            Rotation::R0 | Rotation::R180 => Size::new(W as u32, H as u32),
+            Rotation::R90 | Rotation::R270 => Size::new(H as u32, W as u32),
+        }
+    }
+}
+
+impl<const W: usize, const H: usize> DrawTarget for LedMatrix<W, H> {
+    type Color = Rgb888;
+    type Error = Infallible;
+
+    // Out of bounds pixels are ignored: that's what DrawTarget expects
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            if let Some((x, y)) = self.physical(point) {
+                self.back[y][x] = color;
+            }
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.back = [[color; W]; H];
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_graphics::primitives::{Line, PrimitiveStyle};
+
+    const R: RGB8 = RGB8::new(255, 0, 0);
+    const O: RGB8 = RGB8::new(0, 0, 0);
+
+    // Draw a pixel at logical (x, y); return the strip
+    fn strip_with_pixel(layout: Layout, rotation: Rotation, x: i32, y: i32) -> [RGB8; 12] {
+        let mut matrix = LedMatrix::<4, 3>::new(layout, rotation);
+        Pixel(Point::new(x, y), Rgb888::RED).draw(&mut matrix).unwrap();
+        let mut strip = [O; 12];
+        matrix.render(&mut strip);
+        strip
+    }
+
+    fn lit(strip: &[RGB8]) -> Vec<usize> {
+        strip.iter().enumerate().filter(|(_, &c)| c == R).map(|(i, _)| i).collect()
+    }
+
+    #[test]
+    fn test_layout() {
+        // 4x3, row-major:     serpentine:
+        //   0  1  2  3          0  1  2  3
+        //   4  5  6  7          7  6  5  4
+        //   8  9 10 11          8  9 10 11
+        let row_major = LedMatrix::<4, 3>::new(Layout::RowMajor, Rotation::R0);
+        let serpentine = LedMatrix::<4, 3>::new(Layout::Serpentine, Rotation::R0);
+        assert_eq!(row_major.index(0, 1), 4);
+        assert_eq!(serpentine.index(0, 1), 7);
+        assert_eq!(serpentine.index(3, 1), 4);
+        assert_eq!(serpentine.index(1, 2), 9);
+
+        assert_eq!(lit(&strip_with_pixel(Layout::Serpentine, Rotation::R0, 1, 1)), vec![6]);
+    }
+
+    #[test]
+    fn test_rotation() {
+        // Logical top-left corner ends up in a different physical corner
+        let corner = |rotation| lit(&strip_with_pixel(Layout::RowMajor, rotation, 0, 0));
+        assert_eq!(corner(Rotation::R0), vec![0]);
+        assert_eq!(corner(Rotation::R90), vec![3]);
+        assert_eq!(corner(Rotation::R180), vec![11]);
+        assert_eq!(corner(Rotation::R270), vec![8]);
+
+        // Rotated: 3 wide, 4 tall
+        let matrix = LedMatrix::<4, 3>::new(Layout::RowMajor, Rotation::R90);
+        assert_eq!(matrix.size(), Size::new(3, 4));
+        assert_eq!(lit(&strip_with_pixel(Layout::RowMajor, Rotation::R90, 2, 3)), vec![8]);
+        // Off the panel: ignored
+        assert_eq!(lit(&strip_with_pixel(Layout::RowMajor, Rotation::R90, 3, 0)), vec![]);
+        assert_eq!(lit(&strip_with_pixel(Layout::RowMajor, Rotation::R0, -1, 0)), vec![]);
+    }
+
+    #[test]
+    fn test_draw() {
+        // A vertical line, through embedded-graphics
+        let mut matrix = LedMatrix::<4, 3>::new(Layout::Serpentine, Rotation::R0);
+        Line::new(Point::new(1, 0), Point::new(1, 2))
+            .into_styled(PrimitiveStyle::with_stroke(Rgb888::RED, 1))
+            .draw(&mut matrix).unwrap();
+
+        // Double buffering: the strip doesn't change until we render
+        let mut strip = [O; 12];
+        assert_eq!(lit(&strip), vec![]);
+        matrix.render(&mut strip);
+        assert_eq!(lit(&strip), vec![1, 6, 9]);
+
+        // Clear: the old frame stays on the strip until the next render
+        matrix.clear(Rgb888::BLACK).unwrap();
+        assert_eq!(lit(&strip), vec![1, 6, 9]);
+        matrix.render(&mut strip);
+        assert_eq!(lit(&strip), vec![]);
+    }
+}
@@ -0,0 +1,419 @@
+// Metrics registry: counters, gauges, histograms. Renders the Prometheus text format.
+//
+//   let registry = Registry::new();
+//   let requests = registry.counter("http_requests_total", "HTTP requests", &[("path", "/blink")]);
+//   requests.inc();
+//   registry.gauge_fn("esp_uptime_seconds", "Uptime", &[], || Some(uptime()));
+//
+//   $ curl http://192.168.2.150/metrics
+//   # HELP http_requests_total HTTP requests
+//   # TYPE http_requests_total counter
+//   http_requests_total{path="/blink"} 1
+//
+// Pure logic, no hardware: `system_metrics` adds the chip's vitals and serves the page.
+// Unit-tests run on the host:
+// $ cargo test --lib --target x86_64-unknown-linux-gnu -Zbuild-std=std,panic_unwind,test
+//
+// The registry is cheap to clone: every module gets a copy and registers its own metrics.
+// A metric handle is cheap to clone, too: it's an atomic, shared with the registry.
+// Registering the same name and labels again returns the same metric.
+
+use std::{
+    fmt::{self, Write},
+    sync::{atomic::{AtomicU64, Ordering}, Arc, Mutex},
+};
+
+// The `Content-Type` of `Registry::render()`
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+// Prometheus' default buckets: seconds, from 5ms to 10s. Good for request durations.
+pub const DEFAULT_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+#[derive(Clone, Default)]
+pub struct Registry {
+    families: Arc<Mutex<Vec<Family>>>,
+}
+
+// Metrics with the same name, different labels: `http_requests_total{path="/"}`, `http_requests_total{path="/blink"}`
+struct Family {
+    name: String,
+    help: String,
+    kind: Kind,
+    metrics: Vec<(Labels, Metric)>,
+}
+
+type Labels = Vec<(String, String)>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Counter,
+    Gauge,
+    Histogram,
+}
+
+enum Metric {
+    Counter(Counter),
+    Gauge(Gauge),
+    // Read when scraped. `None`: no value right now, skip it.
+    GaugeFn(Box<dyn Fn() -> Option<f64> + Send + Sync>),
+    Histogram(Histogram),
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // A number that only goes up: requests, errors, bytes sent
+    pub fn counter(&self, name: &str, help: &str, labels: &[(&str, &str)]) -> Counter {
+        self.register(name, help, Kind::Counter, labels, || Metric::Counter(Counter::default()), |metric| match metric {
+            Metric::Counter(counter) => Some(counter.clone()),
+            _ => None,
+        })
+    }
+
+    // A number that goes up and down: queue length, LED brightness
+    pub fn gauge(&self, name: &str, help: &str, labels: &[(&str, &str)]) -> Gauge {
+        self.register(name, help, Kind::Gauge, labels, || Metric::Gauge(Gauge::default()), |metric| match metric {
+            Metric::Gauge(gauge) => Some(gauge.clone()),
+            _ => None,
+        })
+    }
+
+    // A gauge that is read when scraped: temperature, free heap.
+    // NOTE: it's called with the registry locked: don't touch the registry from it.
+    pub fn gauge_fn(&self, name: &str, help: &str, labels: &[(&str, &str)], f: impl Fn() -> Option<f64> + Send + Sync + 'static) {
+        let mut families = self.families.lock().unwrap();
+        let family = Self::family(&mut families, name, help, Kind::Gauge);
+        let labels = owned(labels);
+        // Replace the old one
+        family.metrics.retain(|(existing, _)| *existing != labels);
+        family.metrics.push((labels, Metric::GaugeFn(Box::new(f))));
+    }
+
+    // Counts values in buckets: request durations, sizes.
+    // `buckets`: upper bounds, ascending. "+Inf" is added automatically.
+    pub fn histogram(&self, name: &str, help: &str, labels: &[(&str, &str)], buckets: &[f64]) -> Histogram {
+        self.register(name, help, Kind::Histogram, labels, || Metric::Histogram(Histogram::new(buckets)), |metric| match metric {
+            Metric::Histogram(histogram) => Some(histogram.clone()),
+            _ => None,
+        })
+    }
+
+    // Find or create
+    fn register<T>(
+        &self, name: &str, help: &str, kind: Kind, labels: &[(&str, &str)],
+        create: impl FnOnce() -> Metric, get: impl Fn(&Metric) -> Option<T>,
+    ) -> T {
+        let mut families = self.families.lock().unwrap();
+        let family = Self::family(&mut families, name, help, kind);
+        let labels = owned(labels);
+        if let Some(existing) = family.metrics.iter().find(|(existing, _)| *existing == labels).and_then(|(_, metric)| get(metric)) {
+            return existing;
+        }
+        // Replaces a `gauge_fn()`, if any
+        family.metrics.retain(|(existing, _)| *existing != labels);
+        let metric = create();
+        let handle = get(&metric).unwrap();
+        family.metrics.push((labels, metric));
+        handle
+    }
+
+    // Find or create. Same name, another kind: that's a bug.
+    fn family<'a>(families: &'a mut Vec<Family>, name: &str, help: &str, kind: Kind) -> &'a mut Family {
+        assert!(is_valid_name(name), "Invalid metric name: {name:?}");
+        match families.iter().position(|family| family.name == name) {
+            Some(i) => {
+                let family = &mut families[i];
+                assert_eq!(family.kind, kind, "Metric {name:?} is already registered as another kind");
+                family
+            },
+            None => {
+                families.push(Family { name: name.into(), help: help.into(), kind, metrics: vec![] });
+                families.last_mut().unwrap()
+            },
+        }
+    }
+
+    // The text exposition format.
+    // Docs: https://prometheus.io/docs/instrumenting/exposition_formats/
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        self.render_into(&mut out).unwrap(); // writing into a String can't fail
+        out
+    }
+
+    pub fn render_into(&self, out: &mut impl Write) -> fmt::Result {
+        let families = self.families.lock().unwrap();
+        for family in families.iter() {
+            let Family { name, help, kind, metrics } = family;
+            writeln!(out, "# HELP {name} {}", escape(help, false))?;
+            writeln!(out, "# TYPE {name} {}", match kind {
+                Kind::Counter => "counter",
+                Kind::Gauge => "gauge",
+                Kind::Histogram => "histogram",
+            })?;
+
+            for (labels, metric) in metrics {
+                match metric {
+                    Metric::Counter(counter) => writeln!(out, "{name}{} {}", LabelSet(labels, None), counter.get())?,
+                    Metric::Gauge(gauge) => writeln!(out, "{name}{} {}", LabelSet(labels, None), Float(gauge.get()))?,
+                    Metric::GaugeFn(f) => if let Some(value) = f() {
+                        writeln!(out, "{name}{} {}", LabelSet(labels, None), Float(value))?
+                    },
+                    Metric::Histogram(histogram) => {
+                        let data = histogram.0.lock().unwrap();
+                        // Buckets are cumulative: "how many are <= le"
+                        let mut cumulative = 0;
+                        for (le, count) in data.bounds.iter().zip(&data.counts) {
+                            cumulative += count;
+                            writeln!(out, "{name}_bucket{} {cumulative}", LabelSet(labels, Some(*le)))?;
+                        }
+                        writeln!(out, "{name}_bucket{} {}", LabelSet(labels, Some(f64::INFINITY)), data.count)?;
+                        writeln!(out, "{name}_sum{} {}", LabelSet(labels, None), Float(data.sum))?;
+                        writeln!(out, "{name}_count{} {}", LabelSet(labels, None), data.count)?;
+                    },
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Counter(Arc<AtomicU64>);
+
+impl Counter {
+    pub fn inc(&self) {
+        self.inc_by(1);
+    }
+
+    pub fn inc_by(&self, n: u64) {
+        self.0.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// f64, stored as bits: there's no `AtomicF64`
+#[derive(Debug, Clone, Default)]
+pub struct Gauge(Arc<AtomicU64>);
+
+impl Gauge {
+    pub fn set(&self, value: f64) {
+        self.0.store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn add(&self, delta: f64) {
+        // Load, add, store: unless someone has changed it in between. Then retry.
+        let mut bits = self.0.load(Ordering::Relaxed);
+        loop {
+            let new = (f64::from_bits(bits) + delta).to_bits();
+            match self.0.compare_exchange_weak(bits, new, Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => return,
+                Err(actual) => bits = actual,
+            }
+        }
+    }
+
+    pub fn get(&self) -> f64 {
+        f64::from_bits(self.0.load(Ordering::Relaxed))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Histogram(Arc<Mutex<HistogramData>>);
+
+#[derive(Debug)]
+struct HistogramData {
+    // Upper bounds
+    bounds: Vec<f64>,
+    // Values in every bucket. Not cumulative: those above the last bound only go into `count`
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &[f64]) -> Self {
+        assert!(bounds.windows(2).all(|w| w[0] < w[1]), "Histogram buckets must be ascending: {bounds:?}");
+        Self(Arc::new(Mutex::new(HistogramData {
+            bounds: bounds.to_vec(),
+            counts: vec![0; bounds.len()],
+            sum: 0.0,
+            count: 0,
+        })))
+    }
+
+    pub fn observe(&self, value: f64) {
+        let mut data = self.0.lock().unwrap();
+        if let Some(i) = data.bounds.iter().position(|&le| value <= le) {
+            data.counts[i] += 1;
+        }
+        data.sum += value;
+        data.count += 1;
+    }
+}
+
+fn owned(labels: &[(&str, &str)]) -> Labels {
+    for (name, _) in labels {
+        assert!(is_valid_name(name) && !name.contains(':'), "Invalid label name: {name:?}");
+    }
+    labels.iter().map(|&(name, value)| (name.into(), value.into())).collect()
+}
+
+// [a-zA-Z_:][a-zA-Z0-9_:]*
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == ':')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+}
+
+// HELP: escape `\` and newlines. Label values: quotes, too.
+fn escape(s: &str, quotes: bool) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str(r"\\"),
+            '\n' => escaped.push_str(r"\n"),
+            '"' if quotes => escaped.push_str(r#"\""#),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// `{path="/",le="0.1"}`. Nothing at all, if there are no labels.
+struct LabelSet<'a>(&'a Labels, Option<f64>);
+
+impl fmt::Display for LabelSet<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self(labels, le) = self;
+        if labels.is_empty() && le.is_none() {
+            return Ok(());
+        }
+        let mut separator = "";
+        f.write_str("{")?;
+        for (name, value) in labels.iter() {
+            write!(f, "{separator}{name}=\"{}\"", escape(value, true))?;
+            separator = ",";
+        }
+        if let Some(le) = le {
+            write!(f, "{separator}le=\"{}\"", Float(*le))?;
+        }
+        f.write_str("}")
+    }
+}
+
+// Rust prints "inf" and "NaN"; Prometheus wants "+Inf" and "NaN"
+struct Float(f64);
+
+impl fmt::Display for Float {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            f64::INFINITY => f.write_str("+Inf"),
+            f64::NEG_INFINITY => f.write_str("-Inf"),
+            value => write!(f, "{value}"),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let registry = Registry::new();
+        let requests = registry.counter("http_requests_total", "HTTP requests", &[("path", "/")]);
+        let blinks = registry.counter("http_requests_total", "HTTP requests", &[("path", "/blink")]);
+        registry.gauge("led_brightness", "LED brightness, 0..1", &[]).set(0.5);
+        registry.gauge_fn("esp_uptime_seconds", "Uptime", &[], || Some(12.0));
+        registry.gauge_fn("esp_wifi_rssi_dbm", "Wi-Fi signal", &[], || None);
+
+        requests.inc();
+        blinks.inc_by(2);
+        // Same name and labels: same counter
+        registry.counter("http_requests_total", "", &[("path", "/")]).inc();
+
+        assert_eq!(registry.render(), [
+            "# HELP http_requests_total HTTP requests",
+            "# TYPE http_requests_total counter",
+            r#"http_requests_total{path="/"} 2"#,
+            r#"http_requests_total{path="/blink"} 2"#,
+            "# HELP led_brightness LED brightness, 0..1",
+            "# TYPE led_brightness gauge",
+            "led_brightness 0.5",
+            "# HELP esp_uptime_seconds Uptime",
+            "# TYPE esp_uptime_seconds gauge",
+            "esp_uptime_seconds 12",
+            // No value: no sample. The family is still there.
+            "# HELP esp_wifi_rssi_dbm Wi-Fi signal",
+            "# TYPE esp_wifi_rssi_dbm gauge",
+            "",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn test_histogram() {
+        let registry = Registry::new();
+        let duration = registry.histogram("http_request_duration_seconds", "Request duration", &[("path", "/")], &[0.1, 1.0]);
+        for value in [0.05, 0.1, 0.5, 3.0] {
+            duration.observe(value);
+        }
+
+        assert_eq!(registry.render(), [
+            "# HELP http_request_duration_seconds Request duration",
+            "# TYPE http_request_duration_seconds histogram",
+            r#"http_request_duration_seconds_bucket{path="/",le="0.1"} 2"#,
+            r#"http_request_duration_seconds_bucket{path="/",le="1"} 3"#,
+            r#"http_request_duration_seconds_bucket{path="/",le="+Inf"} 4"#,
+            r#"http_request_duration_seconds_sum{path="/"} 3.65"#,
+            r#"http_request_duration_seconds_count{path="/"} 4"#,
+            "",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn test_gauge() {
+        let gauge = Registry::new().gauge("temperature", "", &[]);
+        gauge.set(20.0);
+        gauge.add(1.5);
+        gauge.add(-0.5);
+        assert_eq!(gauge.get(), 21.0);
+        gauge.set(f64::NEG_INFINITY);
+        assert_eq!(Float(gauge.get()).to_string(), "-Inf");
+    }
+
+    #[test]
+    fn test_escape() {
+        let registry = Registry::new();
+        registry.counter("errors_total", "Errors.\nBy \\reason", &[("reason", "bad \"quote\"\n")]).inc();
+        assert_eq!(registry.render(), [
+            r"# HELP errors_total Errors.\nBy \\reason",
+            "# TYPE errors_total counter",
+            r#"errors_total{reason="bad \"quote\"\n"} 1"#,
+            "",
+        ].join("\n"));
+    }
+
+    #[test]
+    #[should_panic(expected = "already registered as another kind")]
+    fn test_kind_mismatch() {
+        let registry = Registry::new();
+        registry.counter("requests", "", &[]);
+        registry.gauge("requests", "", &[]);
+    }
+
+    #[test]
+    fn test_names() {
+        assert!(is_valid_name("http_requests_total"));
+        assert!(is_valid_name("esp:heap_bytes"));
+        assert!(!is_valid_name("2xx"));
+        assert!(!is_valid_name("free-heap"));
+        assert!(!is_valid_name(""));
+    }
+}
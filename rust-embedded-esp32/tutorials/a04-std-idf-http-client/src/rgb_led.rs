@@ -3,39 +3,47 @@ use core::time::Duration;
 use esp_idf_hal::{
     gpio::OutputPin,
     peripheral::Peripheral,
-    rmt::{config::TransmitConfig, FixedLengthSignal, PinState, Pulse, RmtChannel, TxRmtDriver},
+    rmt::{config::TransmitConfig, PinState, Pulse, RmtChannel, TxRmtDriver, VariableLengthSignal},
 };
 
-pub use rgb::RGB8;
+use crate::ws2812::{self, ColorOrder, Timing};
+pub use crate::ws2812::RGB8;
 
-// Driver for the WS2812 LED.
+// Driver for a strip of WS2812 LEDs: N pixels, daisy-chained on one pin. A single LED is a strip of 1.
 // Using the RMT (Remote Control) channel:
 //   can generate many periodic sequences with minimal CPU intervention.
 //   That is, send 10101001 binary sequences with specific lengths.
 // See also:
 // - https://crates.io/crates/ws2812-esp32-rmt-driver
 // - https://crates.io/crates/ws2812-spi
-pub struct WS2812RMT<'a> {
+//
+// Usage: draw into the frame buffer, then `show()` it:
+//   let mut strip = WS2812Strip::<64>::new(pin, rmt_channel)?;
+//   strip.set_brightness(32);
+//   strip.pixels_mut()[0] = RGB8::new(255, 0, 0);
+//   strip.show()?;
+pub struct WS2812Strip<'d, const N: usize> {
     // RTM driver
-    rmt: TxRmtDriver<'a>,
+    rmt: TxRmtDriver<'d>,
 
-    // Pre-configured pulse lengths for WS2812
-    pulse: PulseConfig,
-}
+    // Frame buffer: what `show()` will send
+    pixels: [RGB8; N],
+    order: ColorOrder,
+    // Global brightness limit: 255 = no limit
+    brightness: u8,
+
+    // Pre-configured pulses for WS2812: [HIGH, LOW] for 0 and for 1
+    zero: [Pulse; 2],
+    one: [Pulse; 2],
+    reset: Pulse,
 
-// Pre-configured pulse lengths for WS2812
-struct PulseConfig {
-    // Transmit 0: short HIGH + long LOW
-    t0h: Pulse,
-    t0l: Pulse,
-    // Transmit 1: short LOW + long HIGH
-    t1h: Pulse,
-    t1l: Pulse,
+    // The signal: rebuilt once per frame. Reused to avoid allocations.
+    signal: VariableLengthSignal,
 }
 
 // This is mostly copied from the std training:
 //   https://github.com/esp-rs/std-training/blob/main/common/lib/rgb-led/src/lib.rs
-impl<'d> WS2812RMT<'d> {
+impl<'d, const N: usize> WS2812Strip<'d, N> {
     // Init: led + RMT peripheral
     pub fn new(
         led: impl Peripheral<P = impl OutputPin> + 'd,
@@ -46,8 +54,7 @@ impl<'d> WS2812RMT<'d> {
         // Clock divider: slow down the internal RMT clock (80 Mhz on ESP32-C3) to get the timing resolution you need.
         // This is because RMT works with ticks, not with milliseconds.
         // So with clk_div=2: one tick = 2/80 Mhz = 0.025μs = 25ns.
-        // Because pulse length is a u16, you can generate pulses with lengths 25ns..1600μs
-        // With Pulse length in ticks being a u16, you can generate signals between 25ns..819μs
+        // With Pulse length in ticks being a u15, you can generate signals between 25ns..819μs
         // Btw, the range for RMT:
         // - min: clk_div=1  : 12.5ns .. 409μs
         // - max: clk_div=255:    3μs .. 104ms
@@ -60,88 +67,65 @@ impl<'d> WS2812RMT<'d> {
         let ticks_hz = rmt_tx_driver.counter_clock()?;
         log::info!("hz={ticks_hz}");
 
-
         // Pre-calculate the actual pulse lengths.
         // They depend on the clock freq and the clk_div clock divider factor.
-        let pulse_config = PulseConfig{
-            t0h: Pulse::new_with_duration(ticks_hz, PinState::High, &ns(400))?,
-            t0l: Pulse::new_with_duration(ticks_hz, PinState::Low , &ns(850))?,
-            t1h: Pulse::new_with_duration(ticks_hz, PinState::High, &ns(800))?,
-            t1l: Pulse::new_with_duration(ticks_hz, PinState::Low , &ns(450))?,
-        };
+        let t = Timing::WS2812;
+        let pulse = |state, nanos| Pulse::new_with_duration(ticks_hz, state, &ns(nanos));
 
         // RMT Tx driver, configured.
         Ok(Self {
             rmt: rmt_tx_driver,
-            pulse: pulse_config,
+            pixels: [RGB8::default(); N],
+            order: ColorOrder::default(),
+            brightness: 255,
+            zero: [pulse(PinState::High, t.t0h_ns)?, pulse(PinState::Low, t.t0l_ns)?],
+            one: [pulse(PinState::High, t.t1h_ns)?, pulse(PinState::Low, t.t1l_ns)?],
+            reset: pulse(PinState::Low, t.reset_ns)?,
+            // 24 bits per LED, 2 pulses per bit, plus the reset
+            signal: VariableLengthSignal::with_capacity(N * 24 * 2 + 1),
         })
     }
 
-    // Sed the color of exactly one pixel.
-    pub fn set_pixel(&mut self, rgb: RGB8) -> Result<()> {
-        // Color: convert to GRB encoding
-        let color: u32 = ((rgb.g as u32) << 16) | ((rgb.r as u32) << 8) | rgb.b as u32;
-
-        // Prepare the lengths: 24 bits of data, encoded.
-        let mut signal = FixedLengthSignal::<24>::new();
-        let p = &self.pulse;
-        for i in (0..24).rev() {
-            // Get the bit
-            // TODO: More efficiently: shift "color >> 1" every iteration, destructuring the value
-            let bit = (color & 1<<i) != 0;
-            // Convert to a pair of Pulses: level + duration
-            let (high_pulse, low_pulse) = if bit { (p.t1h, p.t1l) } else { (p.t0h, p.t0l) };
-            // Add to signal
-            signal.set(23 - i as usize, &(high_pulse, low_pulse))?;
-        }
+    // Color order. Default: GRB, like WS2812
+    pub fn with_order(mut self, order: ColorOrder) -> Self {
+        self.order = order;
+        self
+    }
 
-        // Send the signal
-        self.rmt.start_blocking(&signal)?;
+    // Global brightness limit, applied when sending: the frame buffer keeps the original colors
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.brightness = brightness;
+    }
 
-        // Done
-        Ok(())
+    pub fn pixels(&self) -> &[RGB8; N] {
+        &self.pixels
+    }
+
+    // Draw here, then show()
+    pub fn pixels_mut(&mut self) -> &mut [RGB8; N] {
+        &mut self.pixels
     }
 
-    // Draw a heart on a 64x64 LED array.
-    // This has nothing to do with board test; it's just a fun experiment
-    pub fn heart64x64(&mut self) -> Result<()> {
-        // NOTE: This HUGE array requires a bigger stack.
-        // Size: 64 * 24 = 1.5K
-        // Better allocate it in the memory
-        const R: RGB8 = RGB8::new(100,0,0);
-        const B: RGB8 = RGB8::new(0,0,0);
-        const COLORS: [RGB8; 64] = [
-            B,R,R,B,B,R,R,B,
-            R,B,B,R,R,B,B,R,
-            R,B,B,B,B,B,B,R,
-            R,B,B,B,B,B,B,R,
-            B,R,B,B,B,B,R,B,
-            B,B,R,B,B,R,B,B,
-            B,B,B,R,R,B,B,B,
-            B,B,B,B,B,B,B,B,
-        ];
-
-        // Contruct signal
-        const SIGNAL_LEN: usize = COLORS.len() * 24;  // 24 bits per LED
-        let mut signal = FixedLengthSignal::<SIGNAL_LEN>::new();
-        let p = &self.pulse; // shortcut
-        for (j, rgb) in COLORS.iter().enumerate() {
-            // Color: convert to GRB encoding
-            let color: u32 = ((rgb.g as u32) << 16) | ((rgb.r as u32) << 8) | rgb.b as u32;
-
-            // Prepare the lengths: 24 bits of data, encoded.
-            for i in 0..24 {
-                // Get the bit
-                let bit = (color & 1<<(23-i)) != 0;
-                // Convert to a pair of Pulses: level + duration
-                let (high_pulse, low_pulse) = if bit { (p.t1h, p.t1l) } else { (p.t0h, p.t0l) };
-                // Add to signal
-                signal.set(j*24 + i as usize, &(high_pulse, low_pulse))?;
-            }
+    // Set one pixel. Out of range: ignored.
+    pub fn set_pixel(&mut self, index: usize, rgb: RGB8) {
+        if let Some(pixel) = self.pixels.get_mut(index) {
+            *pixel = rgb;
         }
+    }
+
+    pub fn fill(&mut self, rgb: RGB8) {
+        self.pixels = [rgb; N];
+    }
+
+    // Send the frame buffer to the LEDs
+    pub fn show(&mut self) -> Result<()> {
+        // Build the signal: the whole frame at once
+        self.signal.clear();
+        self.signal.push(ws2812::encode(&self.pixels, self.order, self.brightness, &self.zero, &self.one))?;
+        self.signal.push([&self.reset])?;
 
         // Send the signal
-        self.rmt.start_blocking(&signal)?;
+        self.rmt.start_blocking(&self.signal)?;
 
         // Done
         Ok(())
@@ -0,0 +1,59 @@
+// The chip's vitals, for the metrics registry. And the `/metrics` page that Prometheus scrapes.
+//
+// prometheus.yml:
+//   scrape_configs:
+//     - job_name: esp32
+//       static_configs:
+//         - targets: ['192.168.2.150:80']
+//
+// Every value is read when scraped: nothing runs in the background.
+
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+use esp_idf_svc::{
+    hal::temp_sensor::TempSensorDriver,
+    http::server::{EspHttpServer, Method},
+    io::{EspIOError, Write},
+    sys,
+};
+
+use crate::metrics::{self, Registry};
+
+// Free heap, uptime, Wi-Fi RSSI
+pub fn register(registry: &Registry) {
+    registry.gauge_fn("esp_free_heap_bytes", "Free heap, bytes", &[], || {
+        Some(unsafe { sys::esp_get_free_heap_size() } as f64)
+    });
+    // Heap fragmentation and leaks show up here: the lowest it has ever been
+    registry.gauge_fn("esp_min_free_heap_bytes", "Lowest free heap since boot, bytes", &[], || {
+        Some(unsafe { sys::esp_get_minimum_free_heap_size() } as f64)
+    });
+    registry.gauge_fn("esp_uptime_seconds", "Time since boot, seconds", &[], || {
+        // μs since boot
+        Some(unsafe { sys::esp_timer_get_time() } as f64 / 1_000_000.0)
+    });
+    // Not connected: no value. Prometheus shows a gap, not a fake number.
+    registry.gauge_fn("esp_wifi_rssi_dbm", "Wi-Fi signal strength, dBm", &[], || {
+        let mut ap_info: sys::wifi_ap_record_t = unsafe { core::mem::zeroed() };
+        sys::esp!(unsafe { sys::esp_wifi_sta_get_ap_info(&mut ap_info) })
+            .ok()
+            .map(|_| ap_info.rssi as f64)
+    });
+}
+
+// Chip temperature. The sensor is shared: the app reads it, too.
+pub fn register_temperature(registry: &Registry, sensor: Arc<Mutex<TempSensorDriver<'static>>>) {
+    registry.gauge_fn("esp_chip_temperature_celsius", "Chip temperature, °C", &[], move || {
+        sensor.lock().unwrap().get_celsius().ok().map(f64::from)
+    });
+}
+
+// GET /metrics
+pub fn serve(server: &mut EspHttpServer<'static>, registry: Registry) -> Result<()> {
+    server.fn_handler("/metrics", Method::Get, move |request| -> Result<(), EspIOError> {
+        let text = registry.render();
+        let mut response = request.into_response(200, None, &[("Content-Type", metrics::CONTENT_TYPE)])?;
+        response.write_all(text.as_bytes())
+    })?;
+    Ok(())
+}
@@ -0,0 +1,122 @@
+// WS2812 protocol: pixels -> bits -> pulses.
+//
+// Pure logic, no hardware: `rgb_led` feeds the pulses to the RMT. Unit-tests run on the host:
+// $ cargo test --lib --target x86_64-unknown-linux-gnu -Zbuild-std=std,panic_unwind,test
+//
+// Every LED takes 24 bits, most significant bit first, then passes the rest down the strip.
+// Every bit is a HIGH pulse followed by a LOW pulse; their lengths tell a 0 from a 1.
+// After the last pixel, the line stays LOW for >50μs ("reset"): the LEDs latch the new colors.
+
+pub use rgb::RGB8;
+
+// Pulse lengths, ns.
+// The datasheet allows ±150ns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timing {
+    // Transmit 0: short HIGH + long LOW
+    pub t0h_ns: u64,
+    pub t0l_ns: u64,
+    // Transmit 1: long HIGH + short LOW
+    pub t1h_ns: u64,
+    pub t1l_ns: u64,
+    // Latch: LOW for this long
+    pub reset_ns: u64,
+}
+
+impl Timing {
+    // WS2812B. Newer chips need a longer reset: 280μs.
+    pub const WS2812: Self = Self { t0h_ns: 400, t0l_ns: 850, t1h_ns: 800, t1l_ns: 450, reset_ns: 300_000 };
+}
+
+// The order in which the LED expects the color bytes.
+// WS2812 is GRB. Some WS2811 strips are RGB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorOrder {
+    #[default]
+    Grb,
+    Rgb,
+}
+
+impl ColorOrder {
+    // Color -> bytes on the wire
+    pub fn bytes(self, c: RGB8) -> [u8; 3] {
+        match self {
+            Self::Grb => [c.g, c.r, c.b],
+            Self::Rgb => [c.r, c.g, c.b],
+        }
+    }
+}
+
+// Scale the color down to the brightness limit: 255 = as is, 0 = off.
+// Full white draws ~60mA per LED: 64 LEDs at full brightness is ~4A. Your USB port won't like it.
+pub fn dim(c: RGB8, brightness: u8) -> RGB8 {
+    let scale = |v: u8| (v as u16 * brightness as u16 / 255) as u8;
+    RGB8::new(scale(c.r), scale(c.g), scale(c.b))
+}
+
+// Pixels -> bits, in transmission order
+pub fn bits(pixels: &[RGB8], order: ColorOrder, brightness: u8) -> impl Iterator<Item = bool> + '_ {
+    pixels.iter()
+        .flat_map(move |&c| order.bytes(dim(c, brightness)))
+        .flat_map(|byte| (0..8).rev().map(move |i| byte & (1 << i) != 0))
+}
+
+// Pixels -> pulses: every bit becomes a (HIGH, LOW) pair: `zero` or `one`.
+// Generic over the pulse type: RMT `Pulse` on the chip, anything in the tests.
+pub fn encode<'a, P>(
+    pixels: &'a [RGB8], order: ColorOrder, brightness: u8,
+    zero: &'a [P; 2], one: &'a [P; 2],
+) -> impl Iterator<Item = &'a P> + 'a {
+    bits(pixels, order, brightness)
+        .flat_map(move |bit| if bit { one } else { zero })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color_order() {
+        let c = RGB8::new(1, 2, 3);
+        assert_eq!(ColorOrder::Grb.bytes(c), [2, 1, 3]);
+        assert_eq!(ColorOrder::Rgb.bytes(c), [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_dim() {
+        let c = RGB8::new(255, 128, 1);
+        assert_eq!(dim(c, 255), c);
+        assert_eq!(dim(c, 0), RGB8::new(0, 0, 0));
+        assert_eq!(dim(c, 64), RGB8::new(64, 32, 0));
+    }
+
+    #[test]
+    fn test_bits() {
+        // Green first, MSB first
+        let bits: String = bits(&[RGB8::new(0x0F, 0x81, 0x00)], ColorOrder::Grb, 255)
+            .map(|b| if b { '1' } else { '0' })
+            .collect();
+        assert_eq!(bits, "10000001_00001111_00000000".replace('_', ""));
+    }
+
+    #[test]
+    fn test_encode() {
+        const ZERO: [&str; 2] = ["h", "LLL"];
+        const ONE: [&str; 2] = ["HHH", "l"];
+        let pixels = [RGB8::new(0, 0x80, 0), RGB8::new(0, 0, 0x01)];
+        let pulses: Vec<&str> = encode(&pixels, ColorOrder::Grb, 255, &ZERO, &ONE).copied().collect();
+
+        // 2 pixels * 24 bits * 2 pulses
+        assert_eq!(pulses.len(), 2 * 24 * 2);
+        // Pixel 0: G=0x80: a one, then zeroes
+        assert_eq!(pulses[..4], ["HHH", "l", "h", "LLL"]);
+        // Pixel 1: B=0x01: the very last bit is a one
+        assert_eq!(pulses[pulses.len() - 4..], ["h", "LLL", "HHH", "l"]);
+        assert_eq!(pulses.iter().filter(|&&p| p == "HHH").count(), 2);
+
+        // Brightness applies
+        let dark = encode(&pixels, ColorOrder::Grb, 0, &ZERO, &ONE);
+        assert!(dark.step_by(2).all(|&p| p == "h"));
+    }
+}
@@ -0,0 +1,313 @@
+// Typed handlers for `EspHttpServer::fn_handler()`: get structs, not strings.
+//
+//   #[derive(Deserialize)]
+//   struct Blink { color: Color }
+//
+//   server.fn_handler("/blink", Method::Get, extract::handler(|request, Query(blink): Query<Blink>| {
+//       ...
+//   }))?;
+//
+// Extractors:
+// * `Query<T>`: the query string, see query.rs
+// * `Json<T>`: the request body, JSON
+// * `(A, B)`: both
+//
+// Bad input: the handler isn't called; the client gets "400 Bad Request" with the reason.
+//
+// Works with any `embedded_svc` connection: the unit-tests use a fake one, and run on the host.
+
+use std::fmt;
+use embedded_svc::{
+    http::{server::{Connection, Request}, Headers, Query as _},
+    io::Write,
+};
+use serde::de::DeserializeOwned;
+
+use crate::{query, url::Url};
+
+// JSON bodies larger than this are rejected
+pub const MAX_BODY: usize = 1024;
+
+// Something that can be extracted from a request
+pub trait FromRequest<C: Connection>: Sized {
+    fn from_request(request: &mut Request<C>) -> Result<Self, Rejection>;
+}
+
+// Why the request is rejected
+#[derive(Debug)]
+pub enum Rejection {
+    Query(query::Error),
+    Json(serde_json::Error),
+    UnsupportedMediaType,
+    TooLarge,
+    Io,
+}
+
+impl Rejection {
+    pub fn status(&self) -> u16 {
+        match self {
+            Self::Query(_) | Self::Json(_) => 400,
+            Self::UnsupportedMediaType => 415,
+            Self::TooLarge => 413,
+            Self::Io => 500,
+        }
+    }
+
+    // Respond: status code, and the reason as text
+    pub fn respond<C: Connection>(self, request: Request<C>) -> Result<(), C::Error> {
+        let mut response = request.into_response(self.status(), None, &[("Content-Type", "text/plain; charset=utf-8")])?;
+        response.write_all(self.to_string().as_bytes())
+    }
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Query(e) => write!(f, "Invalid query: {e}"),
+            Self::Json(e) => write!(f, "Invalid JSON: {e}"),
+            Self::UnsupportedMediaType => f.write_str("Expected Content-Type: application/json"),
+            Self::TooLarge => write!(f, "Request body is larger than {MAX_BODY} bytes"),
+            Self::Io => f.write_str("Failed to read the request"),
+        }
+    }
+}
+
+impl std::error::Error for Rejection {}
+
+// Query string -> `T`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query<T>(pub T);
+
+impl<C: Connection, T: DeserializeOwned> FromRequest<C> for Query<T> {
+    fn from_request(request: &mut Request<C>) -> Result<Self, Rejection> {
+        let url = Url::parse(request.uri());
+        query::from_str(url.query.unwrap_or("")).map(Self).map_err(Rejection::Query)
+    }
+}
+
+// JSON body -> `T`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Json<T>(pub T);
+
+impl<C: Connection, T: DeserializeOwned> FromRequest<C> for Json<T> {
+    fn from_request(request: &mut Request<C>) -> Result<Self, Rejection> {
+        // "application/json; charset=utf-8" is fine too
+        let is_json = request.content_type()
+            .is_some_and(|content_type| content_type.split(';').next().unwrap().trim() == "application/json");
+        if !is_json {
+            return Err(Rejection::UnsupportedMediaType);
+        }
+        if request.content_len().unwrap_or(0) > MAX_BODY as u64 {
+            return Err(Rejection::TooLarge);
+        }
+
+        // Read it all. One more byte than allowed: to know that it's too much.
+        let mut body = vec![0; MAX_BODY + 1];
+        let mut len = 0;
+        loop {
+            match request.read(&mut body[len..]).map_err(|_| Rejection::Io)? {
+                0 => break,
+                n => len += n,
+            }
+            if len > MAX_BODY {
+                return Err(Rejection::TooLarge);
+            }
+        }
+        serde_json::from_slice(&body[..len]).map(Self).map_err(Rejection::Json)
+    }
+}
+
+impl<C: Connection, A: FromRequest<C>, B: FromRequest<C>> FromRequest<C> for (A, B) {
+    fn from_request(request: &mut Request<C>) -> Result<Self, Rejection> {
+        Ok((A::from_request(request)?, B::from_request(request)?))
+    }
+}
+
+// Wrap a typed handler for `fn_handler()`: extract `T`, or reject
+#[cfg(target_os = "espidf")]
+pub fn handler<T, F>(f: F) -> impl for<'r> Fn(Request<&mut esp_idf_svc::http::server::EspHttpConnection<'r>>) -> Result<(), esp_idf_svc::io::EspIOError> + Send + 'static
+where
+    T: for<'a, 'r> FromRequest<&'a mut esp_idf_svc::http::server::EspHttpConnection<'r>>,
+    F: for<'r> Fn(Request<&mut esp_idf_svc::http::server::EspHttpConnection<'r>>, T) -> Result<(), esp_idf_svc::io::EspIOError> + Send + 'static,
+{
+    move |mut request| match T::from_request(&mut request) {
+        Ok(value) => f(request, value),
+        Err(rejection) => rejection.respond(request),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+    use embedded_svc::{http::Method, io::{ErrorType, Read}};
+    use serde::Deserialize;
+
+    // Fake connection: a request, and the response it gets.
+    // Split like the real one: the request line and headers, and the body.
+    #[derive(Default)]
+    struct FakeConnection {
+        request: FakeRequest,
+        body: FakeBody,
+        status: Option<u16>,
+        response: Vec<u8>,
+    }
+
+    #[derive(Default)]
+    struct FakeRequest {
+        uri: &'static str,
+        headers: Vec<(&'static str, String)>,
+    }
+
+    #[derive(Default)]
+    struct FakeBody(&'static [u8]);
+
+    impl FakeConnection {
+        fn new(uri: &'static str) -> Self {
+            Self { request: FakeRequest { uri, ..Default::default() }, ..Default::default() }
+        }
+
+        fn json(mut self, body: &'static str) -> Self {
+            self.request.headers.push(("Content-Type", "application/json".into()));
+            self.request.headers.push(("Content-Length", body.len().to_string()));
+            self.body = FakeBody(body.as_bytes());
+            self
+        }
+    }
+
+    impl embedded_svc::http::Query for FakeRequest {
+        fn uri(&self) -> &str {
+            self.uri
+        }
+        fn method(&self) -> Method {
+            Method::Get
+        }
+    }
+    impl Headers for FakeRequest {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.headers.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+        }
+    }
+
+    impl ErrorType for FakeBody {
+        type Error = Infallible;
+    }
+    impl Read for FakeBody {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Infallible> {
+            self.0.read(buf)
+        }
+    }
+
+    // The connection: the request and the body, put together
+    impl ErrorType for FakeConnection {
+        type Error = Infallible;
+    }
+    impl Read for FakeConnection {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Infallible> {
+            self.body.read(buf)
+        }
+    }
+    impl Write for FakeConnection {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Infallible> {
+            self.response.write(buf)
+        }
+        fn flush(&mut self) -> Result<(), Infallible> {
+            Ok(())
+        }
+    }
+    impl embedded_svc::http::Query for FakeConnection {
+        fn uri(&self) -> &str {
+            self.request.uri()
+        }
+        fn method(&self) -> Method {
+            self.request.method()
+        }
+    }
+    impl Headers for FakeConnection {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.request.header(name)
+        }
+    }
+    impl Connection for FakeConnection {
+        type Headers = FakeRequest;
+        type Read = FakeBody;
+        type RawConnectionError = Infallible;
+        type RawConnection = Self;
+
+        fn split(&mut self) -> (&FakeRequest, &mut FakeBody) {
+            (&self.request, &mut self.body)
+        }
+        fn initiate_response(&mut self, status: u16, _message: Option<&str>, _headers: &[(&str, &str)]) -> Result<(), Infallible> {
+            self.status = Some(status);
+            Ok(())
+        }
+        fn is_response_initiated(&self) -> bool {
+            self.status.is_some()
+        }
+        fn raw_connection(&mut self) -> Result<&mut Self, Infallible> {
+            Ok(self)
+        }
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Blink {
+        color: String,
+        times: Option<u8>,
+    }
+
+    fn extract<T: FromRequest<FakeConnection>>(connection: FakeConnection) -> Result<T, Rejection> {
+        T::from_request(&mut Request::wrap(connection))
+    }
+
+    #[test]
+    fn test_query() {
+        let Query(blink) = extract::<Query<Blink>>(FakeConnection::new("/blink?x=1&color=red")).unwrap();
+        assert_eq!(blink, Blink { color: "red".into(), times: None });
+
+        let rejection = extract::<Query<Blink>>(FakeConnection::new("/blink?color=red&color=blue")).unwrap_err();
+        assert_eq!(rejection.to_string(), "Invalid query: duplicate parameter `color`");
+        assert_eq!(rejection.status(), 400);
+        assert!(extract::<Query<Blink>>(FakeConnection::new("/blink")).is_err());
+    }
+
+    #[test]
+    fn test_json() {
+        let connection = FakeConnection::new("/blink").json(r#"{"color": "blue", "times": 3}"#);
+        let Json(blink) = extract::<Json<Blink>>(connection).unwrap();
+        assert_eq!(blink, Blink { color: "blue".into(), times: Some(3) });
+
+        let connection = FakeConnection::new("/blink").json(r#"{"times": 3}"#);
+        assert!(matches!(extract::<Json<Blink>>(connection), Err(Rejection::Json(_))));
+
+        // Not JSON
+        let connection = FakeConnection { body: FakeBody(b"{}"), ..FakeConnection::new("/blink") };
+        assert!(matches!(extract::<Json<Blink>>(connection), Err(Rejection::UnsupportedMediaType)));
+
+        // Too large
+        let big = format!(r#"{{"color": "{}"}}"#, "x".repeat(MAX_BODY));
+        let connection = FakeConnection::new("/blink").json(Box::leak(big.into_boxed_str()));
+        assert!(matches!(extract::<Json<Blink>>(connection), Err(Rejection::TooLarge)));
+    }
+
+    #[test]
+    fn test_both() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Led {
+            id: u8,
+        }
+
+        let connection = FakeConnection::new("/led?id=2").json(r#"{"color": "green"}"#);
+        let (Query(led), Json(blink)) = extract::<(Query<Led>, Json<Blink>)>(connection).unwrap();
+        assert_eq!((led.id, blink.color.as_str()), (2, "green"));
+    }
+
+    #[test]
+    fn test_respond() {
+        let rejection = extract::<Query<Blink>>(FakeConnection::new("/blink?times=1")).unwrap_err();
+        let mut connection = FakeConnection::new("/blink?times=1");
+        rejection.respond(Request::wrap(&mut connection)).unwrap();
+        assert_eq!(connection.status, Some(400));
+        assert_eq!(String::from_utf8(connection.response).unwrap(), "Invalid query: missing field `color`");
+    }
+}
@@ -1,16 +1,34 @@
-use a04_std_idf_http_client::blinky_led;
+use a04_std_idf_http_client::{
+    blinky_led,
+    metrics::{self, Registry},
+    system_metrics,
+};
 use anyhow::Result;
 use std::{
-    sync::{Arc, Mutex}, time::Duration,
+    sync::{Arc, Mutex}, time::{Duration, Instant},
 };
 use esp_idf_hal::{
     io::EspIOError,
     gpio,
 };
 use esp_idf_svc::{
-    http::server::{EspHttpServer, Configuration, Method},
+    http::server::{EspHttpServer, EspHttpConnection, Configuration, Method, Request},
+    io::Write,
+};
+use askama::Template;
+use serde::Deserialize;
+
+use crate::{
+    extract::{self, Query},
+    pages::{self, Color},
 };
 
+// GET /blink?led=red
+#[derive(Deserialize)]
+struct BlinkParams {
+    led: Option<Color>,
+}
+
 
 // See this moved value, `temp_sensor`?
 // Problem: it only lives for the duration of the function: i.e. it gets dropped when func quits.
@@ -26,6 +44,8 @@ pub fn new<RedLedPin, GreenLedPin, BlueLedPin>(
     red_led: blinky_led::BlinkyLed<'static, RedLedPin, gpio::Output>,
     green_led: blinky_led::BlinkyLed<'static, GreenLedPin, gpio::Output>,
     blue_led: blinky_led::BlinkyLed<'static, BlueLedPin, gpio::Output>,
+    // Metrics: we add ours, and serve them all on /metrics
+    registry: Registry,
 ) -> Result<EspHttpServer<'static>>
     where
         RedLedPin:    gpio::Pin,
@@ -34,6 +54,7 @@ pub fn new<RedLedPin, GreenLedPin, BlueLedPin>(
 {
     // Wrap the owned driver in a `Mutex` for safe mutable access, and an `Arc` for shared ownership.
     let shared_sensor = Arc::new(Mutex::new(temp_sensor));
+    system_metrics::register_temperature(&registry, shared_sensor.clone());
 
     // Wrap the leds
     let red_led = Arc::new(Mutex::new(red_led));
@@ -45,84 +66,90 @@ pub fn new<RedLedPin, GreenLedPin, BlueLedPin>(
         http_port: 80,
         ..Default::default()
     })?;
-    server.fn_handler("/", Method::Get, |request| -> core::result::Result<(), EspIOError> {
+    server.fn_handler("/", Method::Get, metered(&registry, "/", |request| -> core::result::Result<(), EspIOError> {
         // Show index page
-        let html = index_html_templated("Hello from ESP32!");
-        let mut response = request.into_ok_response()?;
-        response.write(html.as_bytes())?;
-        Ok(())
-    })?;
+        respond_html(request, pages::Index { greeting: "Hello from ESP32!" })
+    }))?;
 
     // Callback uses the value. It's moved.
-    server.fn_handler("/temperature", Method::Get, move |request| -> core::result::Result<(), EspIOError> {
+    server.fn_handler("/temperature", Method::Get, metered(&registry, "/temperature", move |request| -> core::result::Result<(), EspIOError> {
         // Inside the closure, lock the Mutex to gain exclusive access to the driver.
         // It will get unlocked when the function quits: with .drop()
         let sensor = shared_sensor.lock().unwrap();
         let temp = sensor.get_celsius()?;
 
         // Show temperature page
-        let html = index_html_templated(format!("Chip temperature: {:.2}°C", temp));
-        let mut response = request.into_ok_response()?;
-        response.write(html.as_bytes())?;
-        Ok(())
-    })?;
+        respond_html(request, pages::Temperature { celsius: temp })
+    }))?;
 
-    server.fn_handler("/blink", Method::Get, move |request| -> core::result::Result<(), EspIOError> {
+    // Typed handler: the query string is parsed into `BlinkParams`.
+    // "?led=pink", or "?led=red&led=blue": 400 Bad Request, and this function isn't even called.
+    server.fn_handler("/blink", Method::Get, metered(&registry, "/blink", extract::handler(move |request, Query(params): Query<BlinkParams>| {
         // Blink
-        let uri = request.uri();
-        if uri.contains("?blink=red") {
-            red_led.lock().unwrap().blink(3, Duration::from_millis(100), Duration::from_millis(100));
-        }
-        if uri.contains("?blink=green") {
-            green_led.lock().unwrap().blink(3, Duration::from_millis(100), Duration::from_millis(100));
-        }
-        if uri.contains("?blink=blue") {
-            blue_led.lock().unwrap().blink(3, Duration::from_millis(100), Duration::from_millis(100));
+        let (on, off) = (Duration::from_millis(100), Duration::from_millis(100));
+        let blinked = match params.led {
+            Some(Color::Red) => red_led.lock().unwrap().blink(3, on, off),
+            Some(Color::Green) => green_led.lock().unwrap().blink(3, on, off),
+            Some(Color::Blue) => blue_led.lock().unwrap().blink(3, on, off),
+            None => Ok(()),
+        };
+        let mut page = pages::Blink::new(params.led);
+        if let Err(e) = blinked {
+            log::warn!("Blink failed: {e}");
+            page.blinked = None;
+            page.error = Some(e.to_string());
         }
 
         // Show page
-        let html = index_html_templated(r#"
-        <ul>
-            <li><a href="?blink=red">Red</a></li>
-            <li><a href="?blink=green">Green</a></li>
-            <li><a href="?blink=blue">Blue</a></li>
-        </ul>
-        "#);
-        let mut response = request.into_ok_response()?;
-        response.write(html.as_bytes())?;
-        Ok(())
-    })?;
+        respond_html(request, page)
+    })))?;
+
+    // Prometheus
+    system_metrics::serve(&mut server, registry)?;
 
 
     // We need to return the server so that someone owns it.
     Ok(server)
 }
 
-fn index_html_templated(content: impl AsRef<str>) -> String {
-    format!(r#"
-<!DOCTYPE html>
-<html>
-    <head>
-        <meta charset="utf-8">
-        <title>esp-rs web server</title>
-        <meta name="viewport" content="width=device-width, initial-scale=1">
-        <style>
-        /* Base styles */
-        body {{
-            font-size: 12pt;
-        }}
-
-        /* Smaller tablets and mobiles */
-        @media (max-width: 768px) {{
-            body {{
-                font-size: 16pt;
-            }}
-        }}
-        </style>
-    </head>
-    <body>
-        {}
-    </body>
-</html>
-    "#, content.as_ref())
+// Render a page, and send it.
+// Rendering fails only if some `Display` impl fails: then it's a 500.
+fn respond_html(request: Request<&mut EspHttpConnection>, page: impl Template) -> core::result::Result<(), EspIOError> {
+    match page.render() {
+        Ok(html) => {
+            let mut response = request.into_response(200, None, &[("Content-Type", "text/html; charset=utf-8")])?;
+            response.write_all(html.as_bytes())
+        },
+        Err(e) => {
+            log::error!("Template failed: {e}");
+            request.into_status_response(500)?;
+            Ok(())
+        },
+    }
+}
+
+// Count and time the requests to `path`:
+//   http_requests_total{path="/blink"}
+//   http_request_errors_total{path="/blink"}
+//   http_request_duration_seconds{path="/blink"}: a histogram
+fn metered<E>(
+    registry: &Registry,
+    path: &'static str,
+    handler: impl for<'r> Fn(Request<&mut EspHttpConnection<'r>>) -> core::result::Result<(), E> + Send + 'static,
+) -> impl for<'r> Fn(Request<&mut EspHttpConnection<'r>>) -> core::result::Result<(), E> + Send + 'static {
+    let labels = [("path", path)];
+    let requests = registry.counter("http_requests_total", "HTTP requests", &labels);
+    let errors = registry.counter("http_request_errors_total", "HTTP requests that failed", &labels);
+    let duration = registry.histogram("http_request_duration_seconds", "HTTP request duration, seconds", &labels, metrics::DEFAULT_BUCKETS);
+
+    move |request| {
+        let start = Instant::now();
+        let result = handler(request);
+        requests.inc();
+        if result.is_err() {
+            errors.inc();
+        }
+        duration.observe(start.elapsed().as_secs_f64());
+        result
+    }
 }
@@ -1 +1,9 @@
+// Pure logic: runs on the host too
+pub mod url;
+pub mod query;
+pub mod extract;
+pub mod pages;
+
+// ESP-IDF drivers
+#[cfg(target_os = "espidf")]
 pub mod http_server;
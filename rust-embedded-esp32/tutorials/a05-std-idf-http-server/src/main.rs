@@ -18,6 +18,8 @@ use esp_idf_svc::{
 // Our libraries
 use a04_std_idf_http_client::{
     blinky_led,
+    metrics::Registry,
+    system_metrics,
     wifi,
 };
 
@@ -61,12 +63,17 @@ fn main() -> Result<()> {
     let _wifi = wifi::new(config.wifi_ssid, config.wifi_psk, drv.modem, sysloop)?;
     drv.status_led.blink(3, Duration::from_millis(100), Duration::from_millis(200))?;
 
+    // Metrics: heap, uptime, RSSI. The server adds the rest.
+    let registry = Registry::new();
+    system_metrics::register(&registry);
+
     // Init HTTP server
     let _server = a05_std_idf_http_server::http_server::new(
         drv.internal_temp,
         drv.red_led,
         drv.green_led,
         drv.blue_led,
+        registry,
     )?;
     println!("Server awaiting connection");
 
@@ -0,0 +1,128 @@
+// HTML pages: askama templates, see templates/
+//
+//   let html = pages::Temperature { celsius: 23.5 }.render()?;
+//
+// Templates are compiled into Rust code at build time: a typo in a variable name is a compile error,
+// not a blank spot on the page.
+// * Auto-escaping: every `{{ value }}` in an .html template is HTML-escaped. "<script>" shows up as text.
+//   Don't want it? `{{ value|safe }}`: only for HTML you made yourself.
+// * Layouts: a page `{% extends "layout.html" %}` and fills its `{% block %}`s
+// * Partials: `{% include "partials/nav.html" %}`: sees the same variables as the page that includes it
+//
+// Pure logic: renders on the host, in unit-tests.
+
+use std::fmt;
+use askama::Template;
+use serde::Deserialize;
+
+// GET /
+#[derive(Template)]
+#[template(path = "index.html")]
+pub struct Index<'a> {
+    pub greeting: &'a str,
+}
+
+// GET /temperature
+#[derive(Template)]
+#[template(path = "temperature.html")]
+pub struct Temperature {
+    pub celsius: f32,
+}
+
+// GET /blink
+#[derive(Template)]
+#[template(path = "blink.html")]
+pub struct Blink {
+    // The LED that has just blinked
+    pub blinked: Option<Color>,
+    // Why it didn't
+    pub error: Option<String>,
+    // Links to these: partials/leds.html
+    pub leds: &'static [Color],
+}
+
+impl Blink {
+    pub fn new(blinked: Option<Color>) -> Self {
+        Self { blinked, error: None, leds: &Color::ALL }
+    }
+}
+
+// LED color: "?led=red"
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Color {
+    Red,
+    Green,
+    Blue,
+}
+
+impl Color {
+    pub const ALL: [Self; 3] = [Self::Red, Self::Green, Self::Blue];
+
+    // For humans: "Red"
+    pub fn title(&self) -> &'static str {
+        match self {
+            Self::Red => "Red",
+            Self::Green => "Green",
+            Self::Blue => "Blue",
+        }
+    }
+}
+
+// As in the URL: "red"
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Red => "red",
+            Self::Green => "green",
+            Self::Blue => "blue",
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout() {
+        let html = Index { greeting: "Hello from ESP32!" }.render().unwrap();
+        assert!(html.starts_with("<!DOCTYPE html>"), "{html}");
+        assert!(html.contains("<title>Home · esp-rs web server</title>"), "{html}");
+        assert!(html.contains("<h1>Hello from ESP32!</h1>"), "{html}");
+        // The nav partial
+        assert!(html.contains(r#"<a href="/temperature">Temperature</a>"#), "{html}");
+    }
+
+    #[test]
+    fn test_escaping() {
+        let html = Index { greeting: r#"<script>alert("hi")</script>"# }.render().unwrap();
+        assert!(html.contains("<h1>&#60;script&#62;alert(&#34;hi&#34;)&#60;/script&#62;</h1>"), "{html}");
+        assert!(!html.contains("<script>"));
+
+        let html = Blink { error: Some("<b>GPIO</b> & co".into()), ..Blink::new(None) }.render().unwrap();
+        assert!(html.contains("<p>Blink failed: &#60;b&#62;GPIO&#60;/b&#62; &#38; co</p>"), "{html}");
+    }
+
+    #[test]
+    fn test_temperature() {
+        let html = Temperature { celsius: 23.456 }.render().unwrap();
+        assert!(html.contains("<title>Temperature · esp-rs web server</title>"), "{html}");
+        assert!(html.contains("<p>Chip temperature: 23.46°C</p>"), "{html}");
+    }
+
+    #[test]
+    fn test_blink() {
+        let html = Blink::new(Some(Color::Green)).render().unwrap();
+        assert!(html.contains("<p>Blinked: green</p>"), "{html}");
+        assert!(!html.contains("Blink failed"));
+        // The leds partial
+        for (url, title) in [("red", "Red"), ("green", "Green"), ("blue", "Blue")] {
+            assert!(html.contains(&format!(r#"<li><a href="/blink?led={url}">{title}</a></li>"#)), "{html}");
+        }
+
+        let html = Blink::new(None).render().unwrap();
+        assert!(!html.contains("Blinked"));
+    }
+}
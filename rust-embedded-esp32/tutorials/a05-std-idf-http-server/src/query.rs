@@ -0,0 +1,250 @@
+// Query string -> struct, with serde.
+//
+//   #[derive(Deserialize)]
+//   struct Blink { color: Color, times: Option<u8> }
+//
+//   let blink: Blink = query::from_str("color=red&times=3")?;
+//
+// * Values are percent-decoded: see url.rs
+// * Numbers, bools ("true", "false", "on": a checked checkbox), strings, unit enums: `#[serde(rename_all = "lowercase")]`
+// * `Option<T>`: missing or empty is `None`
+// * A parameter given twice is an error: "?blink=red&blink=blue" is ambiguous
+// * Unknown parameters are ignored, unless `#[serde(deny_unknown_fields)]`
+
+use std::{borrow::Cow, fmt};
+use serde::de::{self, value::BorrowedStrDeserializer, Deserializer, IntoDeserializer, Visitor};
+use crate::url::{self, Encoded, Pairs};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    // Bad percent-encoding
+    Url(url::Error),
+    // A parameter is given more than once
+    Duplicate(String),
+    // Missing field, wrong type, etc: serde's message
+    Custom(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Url(e) => write!(f, "bad query string: {e}"),
+            Self::Duplicate(name) => write!(f, "duplicate parameter `{name}`"),
+            Self::Custom(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self::Custom(msg.to_string())
+    }
+}
+
+// Deserialize a query string: without the "?"
+pub fn from_str<'de, T: de::Deserialize<'de>>(query: &'de str) -> Result<T, Error> {
+    T::deserialize(QueryDeserializer(query))
+}
+
+// The whole query: a map
+struct QueryDeserializer<'de>(&'de str);
+
+impl<'de> Deserializer<'de> for QueryDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_map(PairsAccess { pairs: Pairs::new(self.0), value: None, seen: vec![] })
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct PairsAccess<'de> {
+    pairs: Pairs<'de>,
+    // The value of the key we've just returned
+    value: Option<Encoded<'de>>,
+    // Keys so far: to catch duplicates
+    seen: Vec<Cow<'de, str>>,
+}
+
+impl<'de> de::MapAccess<'de> for PairsAccess<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Error> {
+        let Some((key, value)) = self.pairs.next() else { return Ok(None) };
+        let key = decode(key)?;
+        if self.seen.contains(&key) {
+            return Err(Error::Duplicate(key.into_owned()));
+        }
+        self.seen.push(key.clone());
+        self.value = Some(value);
+        seed.deserialize(ValueDeserializer(key)).map(Some)
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let value = self.value.take().expect("next_value() before next_key()");
+        seed.deserialize(ValueDeserializer(decode(value)?))
+    }
+}
+
+// Percent-decode. Borrow when there's nothing to decode.
+fn decode(encoded: Encoded<'_>) -> Result<Cow<'_, str>, Error> {
+    if let Some(plain) = encoded.as_plain() {
+        return Ok(Cow::Borrowed(plain));
+    }
+    let bytes = encoded.bytes().collect::<Result<Vec<u8>, _>>().map_err(Error::Url)?;
+    String::from_utf8(bytes).map(Cow::Owned).map_err(|_| Error::Url(url::Error::NotUtf8))
+}
+
+// A single value: a string, parsed into whatever the field wants
+struct ValueDeserializer<'de>(Cow<'de, str>);
+
+impl ValueDeserializer<'_> {
+    fn parse<T: std::str::FromStr>(&self, what: &str) -> Result<T, Error> {
+        self.0.parse().map_err(|_| Error::Custom(format!("invalid {what}: `{}`", self.0)))
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident: $what:literal,)*) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            visitor.$visit(self.parse($what)?)
+        }
+    )*};
+}
+
+impl<'de> Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = Error;
+
+    // Strings
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Cow::Borrowed(s) => visitor.visit_borrowed_str(s),
+            Cow::Owned(s) => visitor.visit_string(s),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0.as_ref() {
+            "true" | "on" | "1" => visitor.visit_bool(true),
+            "false" | "off" | "0" => visitor.visit_bool(false),
+            other => Err(Error::Custom(format!("invalid bool: `{other}`"))),
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_i8 => visit_i8: "integer",
+        deserialize_i16 => visit_i16: "integer",
+        deserialize_i32 => visit_i32: "integer",
+        deserialize_i64 => visit_i64: "integer",
+        deserialize_u8 => visit_u8: "integer",
+        deserialize_u16 => visit_u16: "integer",
+        deserialize_u32 => visit_u32: "integer",
+        deserialize_u64 => visit_u64: "integer",
+        deserialize_f32 => visit_f32: "number",
+        deserialize_f64 => visit_f64: "number",
+        deserialize_char => visit_char: "char",
+    }
+
+    // "?x=" is the same as no "x" at all
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0.is_empty() {
+            true => visitor.visit_none(),
+            false => visitor.visit_some(self),
+        }
+    }
+
+    // Unit variants only: "?color=red"
+    fn deserialize_enum<V: Visitor<'de>>(
+        self, _name: &'static str, _variants: &'static [&'static str], visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.0 {
+            Cow::Borrowed(s) => visitor.visit_enum(BorrowedStrDeserializer::new(s)),
+            Cow::Owned(s) => visitor.visit_enum(s.into_deserializer()),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        i128 u128 str string bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Color {
+        Red,
+        Green,
+        Blue,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Blink {
+        color: Color,
+        times: Option<u8>,
+        #[serde(default)]
+        fast: bool,
+    }
+
+    #[test]
+    fn test_struct() {
+        assert_eq!(
+            from_str::<Blink>("color=red&times=3&fast=on"),
+            Ok(Blink { color: Color::Red, times: Some(3), fast: true }),
+        );
+        // Any order; unknown parameters are ignored; empty is `None`
+        assert_eq!(
+            from_str::<Blink>("x=1&times=&color=blue"),
+            Ok(Blink { color: Color::Blue, times: None, fast: false }),
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |query| from_str::<Blink>(query).unwrap_err().to_string();
+        assert_eq!(error("times=3"), "missing field `color`");
+        assert_eq!(error("color=pink"), "unknown variant `pink`, expected one of `red`, `green`, `blue`");
+        assert_eq!(error("color=red&times=many"), "invalid integer: `many`");
+        assert_eq!(error("color=red&times=300"), "invalid integer: `300`");
+        assert_eq!(error("color=red&fast=maybe"), "invalid bool: `maybe`");
+        assert_eq!(error("color=red&color=blue"), "duplicate parameter `color`");
+        assert_eq!(error("color=%zz"), "bad query string: invalid percent-encoding");
+    }
+
+    #[test]
+    fn test_decoding() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Message<'a> {
+            // Borrowed: nothing to decode
+            #[serde(borrow)]
+            to: Cow<'a, str>,
+            text: String,
+        }
+
+        let message: Message = from_str("to=bob&te%78t=hello+world%21").unwrap();
+        assert_eq!(message.text, "hello world!");
+        assert!(matches!(message.to, Cow::Borrowed("bob")));
+    }
+
+    #[test]
+    fn test_map() {
+        let map: std::collections::BTreeMap<String, u32> = from_str("a=1&b=2").unwrap();
+        assert_eq!(map.into_iter().collect::<Vec<_>>(), [("a".into(), 1), ("b".into(), 2)]);
+    }
+}
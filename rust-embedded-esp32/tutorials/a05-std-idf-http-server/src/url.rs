@@ -0,0 +1,219 @@
+// URL parser: path, query string, percent-decoding.
+//
+// "/blink?color=light%20blue&times=3#top"
+//  path:  "/blink"
+//  query: "color=light%20blue&times=3" -> ("color", "light blue"), ("times", "3")
+//
+// Uses `core` only: no_std-friendly, no allocations. Everything borrows from the URL:
+// values are decoded lazily, into a buffer you provide, or compared as is.
+// Want structs? See query.rs: it deserializes the query with serde.
+
+use core::{fmt, str};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Url<'a> {
+    pub path: &'a str,
+    // Without the "?". `None`: no "?" at all
+    pub query: Option<&'a str>,
+    // Without the "#". Browsers don't send it, but who knows
+    pub fragment: Option<&'a str>,
+}
+
+impl<'a> Url<'a> {
+    // Split a request URI. Can't fail: any string is a path, at least.
+    pub fn parse(uri: &'a str) -> Self {
+        let (rest, fragment) = match uri.split_once('#') {
+            Some((rest, fragment)) => (rest, Some(fragment)),
+            None => (uri, None),
+        };
+        let (path, query) = match rest.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (rest, None),
+        };
+        Self { path, query, fragment }
+    }
+
+    // Query parameters, in order. Duplicates included.
+    pub fn query_pairs(&self) -> Pairs<'a> {
+        Pairs::new(self.query.unwrap_or(""))
+    }
+
+    // The first value of a query parameter
+    pub fn query_param(&self, name: &str) -> Option<Encoded<'a>> {
+        self.query_pairs().find(|(key, _)| *key == *name).map(|(_, value)| value)
+    }
+}
+
+// Iterator: "a=1&b=2" -> ("a", "1"), ("b", "2").
+// "a" -> ("a", ""). Empty parts ("a=1&&b=2") are skipped.
+#[derive(Debug, Clone)]
+pub struct Pairs<'a> {
+    parts: str::Split<'a, char>,
+}
+
+impl<'a> Pairs<'a> {
+    pub fn new(query: &'a str) -> Self {
+        Self { parts: query.split('&') }
+    }
+}
+
+impl<'a> Iterator for Pairs<'a> {
+    type Item = (Encoded<'a>, Encoded<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let part = self.parts.find(|part| !part.is_empty())?;
+        let (key, value) = part.split_once('=').unwrap_or((part, ""));
+        Some((Encoded(key), Encoded(value)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    // "%" not followed by two hex digits
+    InvalidEscape,
+    // Decoded bytes are not UTF-8
+    NotUtf8,
+    // Doesn't fit into the buffer
+    TooLong,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::InvalidEscape => "invalid percent-encoding",
+            Self::NotUtf8 => "not UTF-8",
+            Self::TooLong => "too long",
+        })
+    }
+}
+
+// A percent-encoded string, as found in the URL: "light%20blue", "light+blue".
+// Compare it to a `&str`: decoded on the fly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Encoded<'a>(pub &'a str);
+
+impl<'a> Encoded<'a> {
+    // As is, not decoded
+    pub fn raw(&self) -> &'a str {
+        self.0
+    }
+
+    // Nothing to decode? Then the raw string is the value: no copying.
+    pub fn as_plain(&self) -> Option<&'a str> {
+        (!self.0.contains(['%', '+'])).then_some(self.0)
+    }
+
+    // Decoded bytes, one by one. "+" is a space.
+    pub fn bytes(&self) -> Decode<'a> {
+        Decode { bytes: self.0.as_bytes().iter() }
+    }
+
+    // Decode into `buf`
+    pub fn decode_into<'b>(&self, buf: &'b mut [u8]) -> Result<&'b str, Error> {
+        let mut len = 0;
+        for byte in self.bytes() {
+            *buf.get_mut(len).ok_or(Error::TooLong)? = byte?;
+            len += 1;
+        }
+        str::from_utf8(&buf[..len]).map_err(|_| Error::NotUtf8)
+    }
+}
+
+impl PartialEq<str> for Encoded<'_> {
+    fn eq(&self, other: &str) -> bool {
+        self.bytes().eq(other.bytes().map(Ok))
+    }
+}
+
+impl PartialEq<&str> for Encoded<'_> {
+    fn eq(&self, other: &&str) -> bool {
+        *self == **other
+    }
+}
+
+// Iterator: decoded bytes
+#[derive(Debug, Clone)]
+pub struct Decode<'a> {
+    bytes: core::slice::Iter<'a, u8>,
+}
+
+impl Iterator for Decode<'_> {
+    type Item = Result<u8, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(match *self.bytes.next()? {
+            b'+' => Ok(b' '),
+            b'%' => {
+                let hi = self.bytes.next().and_then(|&c| hex(c));
+                let lo = self.bytes.next().and_then(|&c| hex(c));
+                match (hi, lo) {
+                    (Some(hi), Some(lo)) => Ok(hi << 4 | lo),
+                    _ => Err(Error::InvalidEscape),
+                }
+            }
+            c => Ok(c),
+        })
+    }
+}
+
+fn hex(c: u8) -> Option<u8> {
+    (c as char).to_digit(16).map(|d| d as u8)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let url = Url::parse("/blink?color=red&times=3#top");
+        assert_eq!(url, Url { path: "/blink", query: Some("color=red&times=3"), fragment: Some("top") });
+
+        assert_eq!(Url::parse("/"), Url { path: "/", query: None, fragment: None });
+        assert_eq!(Url::parse("/a?"), Url { path: "/a", query: Some(""), fragment: None });
+        // "?" after "#" is a part of the fragment
+        assert_eq!(Url::parse("/a#b?c"), Url { path: "/a", query: None, fragment: Some("b?c") });
+    }
+
+    #[test]
+    fn test_pairs() {
+        let url = Url::parse("/?x=1&blink=red&&flag&blink=blue&empty=");
+        let pairs: Vec<_> = url.query_pairs().map(|(k, v)| (k.raw(), v.raw())).collect();
+        assert_eq!(pairs, [("x", "1"), ("blink", "red"), ("flag", ""), ("blink", "blue"), ("empty", "")]);
+
+        // First one wins
+        assert_eq!(url.query_param("blink"), Some(Encoded("red")));
+        assert_eq!(url.query_param("nope"), None);
+        // Not a substring match
+        assert_eq!(Url::parse("/?xblink=red").query_param("blink"), None);
+    }
+
+    #[test]
+    fn test_decode() {
+        let mut buf = [0; 32];
+        assert_eq!(Encoded("light%20blue").decode_into(&mut buf), Ok("light blue"));
+        assert_eq!(Encoded("a+b%2Bc").decode_into(&mut buf), Ok("a b+c"));
+        assert_eq!(Encoded("%D0%BF%D1%80%D0%B8%D0%B2%D0%B5%D1%82").decode_into(&mut buf), Ok("привет"));
+        assert_eq!(Encoded("%zz").decode_into(&mut buf), Err(Error::InvalidEscape));
+        assert_eq!(Encoded("%2").decode_into(&mut buf), Err(Error::InvalidEscape));
+        assert_eq!(Encoded("%FF").decode_into(&mut buf), Err(Error::NotUtf8));
+        assert_eq!(Encoded("abcd").decode_into(&mut buf[..3]), Err(Error::TooLong));
+
+        // Compare without decoding into a buffer
+        assert!(Encoded("light%20blue") == "light blue");
+        assert!(Encoded("light+blue") == "light blue");
+        assert!(Encoded("light%20blue") != "light%20blue");
+        assert!(Encoded("%zz") != "%zz");
+
+        assert_eq!(Encoded("red").as_plain(), Some("red"));
+        assert_eq!(Encoded("a+b").as_plain(), None);
+    }
+
+    #[test]
+    fn test_query_param_encoded_name() {
+        // Names are encoded, too
+        let url = Url::parse("/?led%20color=red");
+        assert_eq!(url.query_param("led color"), Some(Encoded("red")));
+    }
+}
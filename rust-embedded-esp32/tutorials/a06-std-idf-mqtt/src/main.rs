@@ -3,6 +3,7 @@ use anyhow::Result;
 // Our libraries
 use a04_std_idf_http_client::{
     blinky_led,
+    rgb_led::WS2812Strip,
     wifi,
 };
 
@@ -79,14 +80,16 @@ fn main() -> Result<()> {
     }
 }
 
-fn process_message(data: &[u8], details: Details, led: &mut WS2812RMT) {
+// The board LED: a strip of 1
+fn process_message(data: &[u8], details: Details, led: &mut WS2812Strip<'_, 1>) {
     match details {
         Complete => {
             info!("{:?}", data);
             let message_data: &[u8] = data;
             if let Ok(ColorData::BoardLed(color)) = ColorData::try_from(message_data) {
                 info!("{}", color);
-                if let Err(e) = led.set_pixel(color) {
+                led.set_pixel(0, color);
+                if let Err(e) = led.show() {
                     error!("Could not set board LED: {:?}", e)
                 };
             }
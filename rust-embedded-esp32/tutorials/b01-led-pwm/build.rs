@@ -1,4 +1,10 @@
 fn main() {
+    // ESP-only stuff. Host builds (unit-tests) don't need it.
+    // NOTE: build.rs runs on the host, so check the *target* arch via the env, not #[cfg]
+    if std::env::var("CARGO_CFG_TARGET_ARCH").as_deref() != Ok("riscv32") {
+        return;
+    }
+
     linker_be_nice();
     // make sure linkall.x is the last linker script (otherwise might cause problems with flip-link)
     println!("cargo:rustc-link-arg=-Tlinkall.x");
@@ -17,8 +17,9 @@ esp_bootloader_esp_idf::esp_app_desc!();
 use esp_backtrace as _;  // Provides a panic handler
 use log;  // Use for output/logging: "log" or "defmt"
 
+use b01_led_pwm::{effects::Effect, player::{Led, Step}};
 use esp_hal::{
-    clock::CpuClock, gpio, ledc::{self, channel::ChannelHW}, main, time::{Duration, Instant, Rate}
+    clock::CpuClock, gpio, ledc, main, time::{Duration, Instant, Rate}
 };
 
 // Node that in no_std main() can't have a return value.
@@ -54,8 +55,9 @@ fn main() -> ! {
         // > duty_resolution_bits = log2( clock_freq / ( desired_pwm_freq * clk_div ) ), where
         // Solve for `clk_div`=1 (max) and `clk_div`=1023 (min)
         // For 24 Khz: min=2, max=12. Choose any number in between.
+        // 5 bits is only 32 steps: too coarse for gamma correction, the dim end would jump.
         frequency: Rate::from_khz(24),
-        duty: ledc::timer::config::Duty::Duty5Bit,
+        duty: ledc::timer::config::Duty::Duty10Bit,
     }).unwrap();
 
     // PWM channel. Configure.
@@ -72,22 +74,47 @@ fn main() -> ! {
         drive_mode: gpio::DriveMode::PushPull,
     }).unwrap();
 
-    channel0.set_duty(30); // 30%
+    channel0.set_duty(30).unwrap(); // 30%
+
+    // PWM has `start_duty_fade()`: gradually changes from one duty cycle percentage to another.
+    // Fade in, fade out
+    channel0.start_duty_fade(30, 100, 500).unwrap();
+    while channel0.is_duty_fade_running() {} // wait
+    channel0.start_duty_fade(100, 30, 500).unwrap();
+    while channel0.is_duty_fade_running() {} // wait
+
+    // That's linear, and blocking. Our eyes aren't linear: see `effects::gamma()`.
+    // Let the effects engine compute the brightness, and tick it: nothing blocks.
+    // The LEDC channel implements `embedded_hal::pwm::SetDutyCycle`, so the `Led` can drive it.
+    // The onboard LED is active-low: 10% duty => 90% brightness.
+    let mut led = Led::new(channel0).active_low();
+    led.play(SEQUENCE, true, now_ms());
 
     loop {
-        // PWM has `start_duty_fade()`: gradually changes from one duty cycle percentage to another.
-        // Fade in
-        channel0.start_duty_fade(30, 100, 500).unwrap();
-        while channel0.is_duty_fade_running() {} // wait
-        // Fade out
-        channel0.start_duty_fade(100, 30, 500).unwrap();
-        while channel0.is_duty_fade_running() {} // wait
-
-        // Sleep. Busy wait:
-        busy_wait(Duration::from_millis(500));
+        // Update the LED
+        led.tick(now_ms()).unwrap();
+
+        // Free to do other stuff here: e.g. poll a button.
+        // Just don't take longer than ~10ms, or the fades will look choppy.
+        busy_wait(Duration::from_millis(10));
     }
 }
 
+// Our show: loops forever
+const SEQUENCE: &[Step] = &[
+    Step::new(Effect::Breathe { period_ms: 3000 }, 6000),
+    Step::new(Effect::Heartbeat { period_ms: 1000 }, 4000),
+    Step::new(Effect::Strobe { on_ms: 30, off_ms: 120 }, 1500),
+    Step::new(Effect::OFF, 500),
+    // SOS: 34 units of 150ms
+    Step::new(Effect::SOS, 5100),
+];
+
+// Milliseconds since boot
+fn now_ms() -> u64 {
+    Instant::now().duration_since_epoch().as_millis()
+}
+
 // Blocking delay: burns CPU cycles until `duration` passes.
 fn busy_wait(duration: Duration) {
     let delay_start = Instant::now();
@@ -0,0 +1,180 @@
+// LED effects: brightness as a function of time.
+//
+// Every effect answers one question: how bright is the LED `t` milliseconds in?
+// Brightness is perceptual: 0.5 looks half as bright. Turn it into a duty cycle with `gamma()`.
+//
+// * `Solid`: constant
+// * `Breathe`: slow fade in/out, like a sleeping laptop
+// * `Heartbeat`: lub-dub ... lub-dub
+// * `Strobe`: on/off
+// * `Morse`: a message in Morse code. `Effect::SOS`: ... --- ...
+//
+// Effects loop: `period_ms()` is one cycle.
+
+use core::f32::consts::PI;
+
+// Our eyes are not linear: 50% duty cycle looks way brighter than half.
+// Gamma correction: duty = brightness ^ 2.2
+pub const GAMMA: f32 = 2.2;
+
+// Perceptual brightness [0; 1] -> duty cycle [0; 1]
+pub fn gamma(brightness: f32) -> f32 {
+    libm::powf(brightness.clamp(0.0, 1.0), GAMMA)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Effect {
+    Solid(f32),
+    Breathe { period_ms: u32 },
+    Heartbeat { period_ms: u32 },
+    Strobe { on_ms: u32, off_ms: u32 },
+    // Upper case letters, digits, spaces. `unit_ms`: the length of a dot.
+    Morse { text: &'static str, unit_ms: u32 },
+}
+
+impl Effect {
+    pub const OFF: Self = Self::Solid(0.0);
+    pub const ON: Self = Self::Solid(1.0);
+    pub const SOS: Self = Self::Morse { text: "SOS", unit_ms: 150 };
+
+    // One cycle, ms. Too long for a u32? Capped at ~49 days: it won't loop, but it won't panic either.
+    pub fn period_ms(&self) -> u32 {
+        match *self {
+            Self::Solid(_) => 1,
+            Self::Breathe { period_ms } | Self::Heartbeat { period_ms } => period_ms.max(1),
+            Self::Strobe { on_ms, off_ms } => on_ms.saturating_add(off_ms).max(1),
+            Self::Morse { text, unit_ms } => morse_units(text).saturating_mul(unit_ms).max(1),
+        }
+    }
+
+    // Brightness [0; 1] at `t_ms`
+    pub fn brightness(&self, t_ms: u32) -> f32 {
+        let t = t_ms % self.period_ms();
+        match *self {
+            Self::Solid(level) => level.clamp(0.0, 1.0),
+            Self::Breathe { period_ms } => {
+                // Cosine: starts dark, peaks in the middle
+                (1.0 - libm::cosf(2.0 * PI * t as f32 / period_ms as f32)) / 2.0
+            }
+            Self::Heartbeat { period_ms } => {
+                // Two beats at 0% and 25% of the period: a strong one, and a weaker one.
+                // Each beat: instant rise, fading over 15% of the period.
+                let beat = |start: f32, peak: f32| {
+                    let x = (t as f32 / period_ms as f32 - start) / 0.15;
+                    if (0.0..1.0).contains(&x) { peak * (1.0 - x) } else { 0.0 }
+                };
+                beat(0.0, 1.0).max(beat(0.25, 0.6))
+            }
+            Self::Strobe { on_ms, .. } => if t < on_ms { 1.0 } else { 0.0 },
+            Self::Morse { text, unit_ms } => {
+                if morse_is_on(text, t / unit_ms.max(1)) { 1.0 } else { 0.0 }
+            }
+        }
+    }
+}
+
+
+// Morse code.
+// Timing, in units: dot = 1, dash = 3; gap within a letter = 1, between letters = 3, between words = 7.
+fn morse_code(c: char) -> &'static str {
+    match c.to_ascii_uppercase() {
+        'A' => ".-", 'B' => "-...", 'C' => "-.-.", 'D' => "-..", 'E' => ".", 'F' => "..-.",
+        'G' => "--.", 'H' => "....", 'I' => "..", 'J' => ".---", 'K' => "-.-", 'L' => ".-..",
+        'M' => "--", 'N' => "-.", 'O' => "---", 'P' => ".--.", 'Q' => "--.-", 'R' => ".-.",
+        'S' => "...", 'T' => "-", 'U' => "..-", 'V' => "...-", 'W' => ".--", 'X' => "-..-",
+        'Y' => "-.--", 'Z' => "--..",
+        '0' => "-----", '1' => ".----", '2' => "..---", '3' => "...--", '4' => "....-",
+        '5' => ".....", '6' => "-....", '7' => "--...", '8' => "---..", '9' => "----.",
+        _ => "", // unknown: skip
+    }
+}
+
+// The signal as a sequence of (on, units) intervals. The message ends with a word gap: ready to loop.
+fn morse_intervals(text: &'static str) -> impl Iterator<Item = (bool, u32)> {
+    text.split(' ')
+        .flat_map(|word| {
+            let letters = word.chars().map(morse_code).filter(|code| !code.is_empty());
+            letters.enumerate().flat_map(|(i, code)| {
+                // Gap between letters: 3 units, but within a word only
+                let letter_gap = (i > 0).then_some((false, 3));
+                let symbols = code.chars().enumerate().flat_map(|(j, symbol)| {
+                    let gap = (j > 0).then_some((false, 1));
+                    let on = (true, if symbol == '-' { 3 } else { 1 });
+                    gap.into_iter().chain([on])
+                });
+                letter_gap.into_iter().chain(symbols)
+            })
+            .chain([(false, 7)])
+        })
+}
+
+fn morse_units(text: &'static str) -> u32 {
+    morse_intervals(text).map(|(_, units)| units).sum()
+}
+
+fn morse_is_on(text: &'static str, unit: u32) -> bool {
+    let mut start = 0;
+    for (on, units) in morse_intervals(text) {
+        if unit < start + units {
+            return on;
+        }
+        start += units;
+    }
+    false
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Render an effect: 1 = on, 0 = off, one char per unit
+    fn render(effect: &Effect, step_ms: u32) -> String {
+        (0..effect.period_ms() / step_ms)
+            .map(|i| if effect.brightness(i * step_ms) > 0.5 { '#' } else { '_' })
+            .collect()
+    }
+
+    #[test]
+    fn test_gamma() {
+        assert_eq!(gamma(0.0), 0.0);
+        assert_eq!(gamma(1.0), 1.0);
+        // Half the brightness is only ~22% duty
+        assert!((gamma(0.5) - 0.2176).abs() < 0.001);
+        assert_eq!(gamma(2.0), 1.0);
+    }
+
+    #[test]
+    fn test_morse() {
+        // S = ..., O = ---, then a word gap
+        assert_eq!(
+            render(&Effect::SOS, 150),
+            "#_#_#___###_###_###___#_#_#_______",
+        );
+        assert_eq!(Effect::SOS.period_ms(), 34 * 150);
+        // Words, unknown chars
+        let e = Effect::Morse { text: "E E?", unit_ms: 1 };
+        assert_eq!(render(&e, 1), "#_______#_______");
+    }
+
+    #[test]
+    fn test_effects() {
+        let breathe = Effect::Breathe { period_ms: 1000 };
+        assert_eq!(breathe.brightness(0), 0.0);
+        assert!((breathe.brightness(500) - 1.0).abs() < 1e-6);
+        assert!((breathe.brightness(250) - 0.5).abs() < 1e-6);
+        assert_eq!(breathe.brightness(1000), 0.0); // loops
+
+        let strobe = Effect::Strobe { on_ms: 20, off_ms: 80 };
+        assert_eq!(render(&strobe, 20), "#____");
+        // Overflow: capped
+        assert_eq!(Effect::Strobe { on_ms: u32::MAX, off_ms: 1 }.period_ms(), u32::MAX);
+        assert_eq!(Effect::Morse { text: "SOS", unit_ms: u32::MAX }.period_ms(), u32::MAX);
+
+        let heart = Effect::Heartbeat { period_ms: 1000 };
+        assert_eq!(heart.brightness(0), 1.0);
+        assert_eq!(heart.brightness(250), 0.6);
+        assert_eq!(heart.brightness(600), 0.0);
+        assert_eq!(render(&heart, 50), "##___#______________"); // lub-dub
+    }
+}
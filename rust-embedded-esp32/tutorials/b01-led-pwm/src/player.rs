@@ -0,0 +1,210 @@
+// Plays effects on a LED: sequences, and a non-blocking tick.
+//
+// A sequence is a list of steps: an effect, and how long to play it. Steps follow each other;
+// the whole sequence plays once, or loops forever.
+//
+// Works with anything that implements `embedded_hal::pwm::SetDutyCycle`: e.g. an LEDC channel.
+// Nothing blocks: call `tick()` often enough (every ~10ms looks smooth), and do something else in between.
+//
+// Usage:
+//   const SEQUENCE: &[Step] = &[Step::new(Effect::Breathe { period_ms: 2000 }, 4000), Step::new(Effect::SOS, 5100)];
+//   let mut led = Led::new(pwm_channel).active_low();
+//   led.play(SEQUENCE, true, now_ms);
+//   loop { led.tick(now_ms)?; /* other stuff */ }
+
+use embedded_hal::pwm::SetDutyCycle;
+use crate::effects::{gamma, Effect};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Step {
+    pub effect: Effect,
+    // The effect loops for this long
+    pub duration_ms: u32,
+}
+
+impl Step {
+    pub const fn new(effect: Effect, duration_ms: u32) -> Self {
+        Self { effect, duration_ms }
+    }
+
+    // Exactly one cycle of the effect
+    pub fn once(effect: Effect) -> Self {
+        Self { effect, duration_ms: effect.period_ms() }
+    }
+}
+
+// The whole sequence, ms
+pub fn duration_ms(steps: &[Step]) -> u32 {
+    steps.iter().map(|step| step.duration_ms).sum()
+}
+
+// Brightness at `t_ms` into the sequence. `None`: the sequence is over.
+pub fn brightness_at(steps: &[Step], t_ms: u32) -> Option<f32> {
+    let mut start = 0;
+    for step in steps {
+        if t_ms < start + step.duration_ms {
+            return Some(step.effect.brightness(t_ms - start));
+        }
+        start += step.duration_ms;
+    }
+    None
+}
+
+pub struct Led<'a, P: SetDutyCycle> {
+    pwm: P,
+    // LED between VCC and the pin: 0% duty = full brightness. The onboard LED is like that.
+    active_low: bool,
+    // What we're playing, and since when
+    steps: &'a [Step],
+    repeat: bool,
+    started_ms: u64,
+}
+
+impl<'a, P: SetDutyCycle> Led<'a, P> {
+    pub fn new(pwm: P) -> Self {
+        Self { pwm, active_low: false, steps: &[], repeat: false, started_ms: 0 }
+    }
+
+    // Inverted output
+    pub fn active_low(mut self) -> Self {
+        self.active_low = true;
+        self
+    }
+
+    pub fn is_playing(&self) -> bool {
+        !self.steps.is_empty()
+    }
+
+    // Start a sequence. Replaces the current one. Call tick() to actually play it.
+    pub fn play(&mut self, steps: &'a [Step], repeat: bool, now_ms: u64) {
+        self.steps = steps;
+        self.repeat = repeat && duration_ms(steps) > 0;
+        self.started_ms = now_ms;
+    }
+
+    // Stop playing, and set the brightness
+    pub fn set_brightness(&mut self, brightness: f32) -> Result<(), P::Error> {
+        self.steps = &[];
+        self.write(brightness)
+    }
+
+    // Update the LED for this moment. Returns `true` while still playing.
+    // When a sequence is over, the LED goes dark.
+    pub fn tick(&mut self, now_ms: u64) -> Result<bool, P::Error> {
+        if !self.is_playing() {
+            return Ok(false);
+        }
+
+        let mut elapsed = now_ms.saturating_sub(self.started_ms);
+        if self.repeat {
+            elapsed %= duration_ms(self.steps) as u64;
+        }
+        let elapsed = elapsed.min(u32::MAX as u64) as u32;
+
+        match brightness_at(self.steps, elapsed) {
+            Some(brightness) => self.write(brightness)?,
+            None => self.set_brightness(0.0)?,
+        }
+        Ok(self.is_playing())
+    }
+
+    // Stop, and release the PWM channel
+    pub fn release(self) -> P {
+        self.pwm
+    }
+
+    fn write(&mut self, brightness: f32) -> Result<(), P::Error> {
+        let max = self.pwm.max_duty_cycle();
+        let duty = libm::roundf(gamma(brightness) * max as f32) as u16;
+        self.pwm.set_duty_cycle(if self.active_low { max - duty } else { duty })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::convert::Infallible;
+
+    // Remembers every duty cycle
+    struct FakePwm(Vec<u16>);
+
+    impl embedded_hal::pwm::ErrorType for FakePwm {
+        type Error = Infallible;
+    }
+    impl SetDutyCycle for FakePwm {
+        fn max_duty_cycle(&self) -> u16 {
+            1023 // 10 bit
+        }
+        fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Infallible> {
+            self.0.push(duty);
+            Ok(())
+        }
+    }
+
+    const BLINK: Effect = Effect::Strobe { on_ms: 100, off_ms: 100 };
+
+    // Play with a fixed tick; collect the duty cycles
+    fn timeline(led: &mut Led<FakePwm>, from_ms: u64, to_ms: u64, tick_ms: u64) -> Vec<u16> {
+        led.pwm.0.clear();
+        for now in (from_ms..to_ms).step_by(tick_ms as usize) {
+            led.tick(now).unwrap();
+        }
+        led.pwm.0.clone()
+    }
+
+    #[test]
+    fn test_sequence() {
+        let steps = [Step::new(BLINK, 400), Step::new(Effect::Solid(0.5), 100)];
+        assert_eq!(duration_ms(&steps), 500);
+        assert_eq!(brightness_at(&steps, 0), Some(1.0));
+        assert_eq!(brightness_at(&steps, 100), Some(0.0));
+        assert_eq!(brightness_at(&steps, 300), Some(0.0));
+        assert_eq!(brightness_at(&steps, 399), Some(0.0));
+        assert_eq!(brightness_at(&steps, 450), Some(0.5));
+        assert_eq!(brightness_at(&steps, 500), None);
+
+        assert_eq!(Step::once(Effect::SOS).duration_ms, 34 * 150);
+    }
+
+    #[test]
+    fn test_play_once() {
+        let steps = [Step::new(BLINK, 400), Step::new(Effect::Solid(0.5), 100)];
+        let mut led = Led::new(FakePwm(vec![]));
+        led.play(&steps, false, 1000);
+
+        // 100ms ticks: on, off, on, off, half, then dark
+        assert_eq!(timeline(&mut led, 1000, 1600, 100), vec![1023, 0, 1023, 0, 223, 0]);
+        assert!(!led.is_playing());
+        assert_eq!(led.tick(1700), Ok(false));
+    }
+
+    #[test]
+    fn test_play_repeat() {
+        let steps = [Step::new(BLINK, 200), Step::new(Effect::OFF, 100)];
+        let mut led = Led::new(FakePwm(vec![])).active_low();
+        led.play(&steps, true, 0);
+
+        // Inverted: 0 = full brightness
+        assert_eq!(timeline(&mut led, 0, 900, 100), vec![0, 1023, 1023, 0, 1023, 1023, 0, 1023, 1023]);
+        assert!(led.is_playing());
+
+        // Stops
+        led.set_brightness(1.0).unwrap();
+        assert_eq!(led.tick(1000), Ok(false));
+        assert_eq!(led.release().0.last(), Some(&0));
+    }
+
+    #[test]
+    fn test_breathe_gamma() {
+        // Breathing: duty grows slowly at first, then faster: the gamma curve
+        let steps = [Step::once(Effect::Breathe { period_ms: 1000 })];
+        let mut led = Led::new(FakePwm(vec![]));
+        led.play(&steps, false, 0);
+        let duties = timeline(&mut led, 0, 500, 50);
+        assert_eq!(duties.first(), Some(&0));
+        assert!(duties.windows(2).all(|w| w[0] <= w[1]), "{duties:?}");
+        // Half the brightness at 1/4 of the period: ~22% duty
+        assert_eq!(duties[5], 223);
+    }
+}
@@ -1,4 +1,13 @@
 fn main() {
+    // Song library: compile songs/*.txt into Rust code. See `src/songs.rs`
+    generate_songs();
+
+    // ESP-only stuff. Host builds (unit-tests, `wav` tools) don't need it.
+    // NOTE: build.rs runs on the host, so check the *target* arch via the env, not #[cfg]
+    if std::env::var("CARGO_CFG_TARGET_ARCH").as_deref() != Ok("riscv32") {
+        return;
+    }
+
     linker_be_nice();
     // make sure linkall.x is the last linker script (otherwise might cause problems with flip-link)
     println!("cargo:rustc-link-arg=-Tlinkall.x");
@@ -54,3 +63,118 @@ fn linker_be_nice() {
         std::env::current_exe().unwrap().display()
     );
 }
+
+
+// Song library: parse songs/*.txt and generate `$OUT_DIR/songs.rs` with a `SONGS` registry.
+// A malformed song fails the build: better now than on the device.
+//
+// Song file format: the name is the file name. Then:
+//   # comment
+//   tempo = 180
+//   E5:8 D5:8 FS4 GS4   # note:divider. Divider defaults to 4 (a quarter note)
+//   A4:-4 REST:2        # negative divider: dotted note (x1.5)
+fn generate_songs() {
+    use std::fmt::Write;
+
+    const SONGS_DIR: &str = "songs";
+    println!("cargo:rerun-if-changed={SONGS_DIR}");
+
+    // Song files, sorted: so that indexes are stable
+    let mut paths: Vec<_> = std::fs::read_dir(SONGS_DIR)
+        .expect("Failed to read the songs/ directory")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+        .collect();
+    paths.sort();
+
+    let mut code = String::from("pub static SONGS: &[SongInfo] = &[\n");
+    for path in paths {
+        let name = path.file_stem().unwrap().to_str().unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        let (tempo, melody) = parse_song(&text).unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+
+        writeln!(code, "    SongInfo {{").unwrap();
+        writeln!(code, "        name: {name:?},").unwrap();
+        writeln!(code, "        tempo: {tempo},").unwrap();
+        writeln!(code, "        melody: &[").unwrap();
+        for (note, divider) in melody {
+            writeln!(code, "            ({note}, {divider}),").unwrap();
+        }
+        writeln!(code, "        ],").unwrap();
+        writeln!(code, "    }},").unwrap();
+    }
+    code.push_str("];\n");
+
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    std::fs::write(std::path::Path::new(&out_dir).join("songs.rs"), code).unwrap();
+}
+
+// Parse a song: returns (tempo, [(note constant, divider)]).
+// Errors are "line N: message"
+fn parse_song(text: &str) -> Result<(u16, Vec<(String, i16)>), String> {
+    let mut tempo = None;
+    let mut melody = Vec::new();
+
+    for (n, line) in text.lines().enumerate() {
+        let n = n + 1;
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        // tempo = 120
+        if let Some(value) = line.strip_prefix("tempo") {
+            let value = value.trim().strip_prefix('=').ok_or(format!("line {n}: expected `tempo = <bpm>`"))?;
+            tempo = match value.trim().parse::<u16>() {
+                Ok(t) if t > 0 => Some(t),
+                _ => return Err(format!("line {n}: invalid tempo `{}`", value.trim())),
+            };
+            continue;
+        }
+
+        // E5:8 D5:8 FS4 GS4
+        for token in line.split_whitespace() {
+            let (note, divider) = token.split_once(':').unwrap_or((token, "4"));
+            let divider: i16 = divider.parse().map_err(|_| format!("line {n}: invalid divider in `{token}`"))?;
+            if ![1, 2, 4, 8, 16, 32].contains(&divider.abs()) {
+                return Err(format!("line {n}: divider must be 1, 2, 4, 8, 16, 32 (negative: dotted), got `{token}`"));
+            }
+            melody.push((note_constant(note).ok_or(format!("line {n}: unknown note `{note}`"))?, divider));
+        }
+    }
+
+    let tempo = tempo.ok_or("missing `tempo = <bpm>`")?;
+    if melody.is_empty() {
+        return Err("no notes".into());
+    }
+    Ok((tempo, melody))
+}
+
+// Note name ("FS4") to the constant in `music.rs` ("NOTE_FS4").
+// `music.rs` has notes from B0 to DS8, sharps only.
+fn note_constant(note: &str) -> Option<String> {
+    if note == "REST" {
+        return Some(note.into());
+    }
+
+    let (letter, rest) = note.split_at_checked(1)?;
+    let (sharp, octave) = match rest.strip_prefix('S') {
+        Some(octave) => (1, octave),
+        None => (0, rest),
+    };
+    let semitone = match letter {
+        "C" => 0, "D" => 2, "E" => 4, "F" => 5, "G" => 7, "A" => 9, "B" => 11,
+        _ => return None,
+    };
+    if sharp == 1 && (letter == "E" || letter == "B") {
+        return None; // E# is F, B# is C
+    }
+    if octave.len() != 1 {
+        return None;
+    }
+    let octave: u32 = octave.parse().ok()?;
+    let key = octave * 12 + semitone + sharp;
+    const B0: u32 = 11;
+    const DS8: u32 = 8 * 12 + 3;
+    (B0..=DS8).contains(&key).then(|| format!("NOTE_{note}"))
+}
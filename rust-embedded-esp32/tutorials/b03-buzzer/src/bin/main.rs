@@ -1,7 +1,7 @@
 #![no_std]
 #![no_main]
 #![deny(clippy::mem_forget)]
-use b03_buzzer::{music, nokia};
+use b03_buzzer::{music, nokia, poly, songs};
 use esp_backtrace as _;
 esp_bootloader_esp_idf::esp_app_desc!();
 
@@ -25,16 +25,18 @@ fn main() -> ! {
     let mut buzzer_pin = peripherals.GPIO8;
 
     // Active buzzer: just give it voltate.
-    // NOTE: comment it out because it consumes the pin
-    let mut buzzer = gpio::Output::new(buzzer_pin, gpio::Level::High,
-        // Use OpenDrain if GPIO is connected to the transistor's base and there's external pull up.
-        // Oherwise it will give a continuous buzz and that's it.
-        gpio::OutputConfig::default()
-        .with_drive_mode(gpio::DriveMode::OpenDrain)
-    );
-    for i in 1..30 {
-        buzzer.toggle();
-        busy_wait(Duration::from_millis(i*5));
+    // NOTE: reborrow() the pin: otherwise it's consumed, and we need it for PWM later.
+    {
+        let mut buzzer = gpio::Output::new(buzzer_pin.reborrow(), gpio::Level::High,
+            // Use OpenDrain if GPIO is connected to the transistor's base and there's external pull up.
+            // Oherwise it will give a continuous buzz and that's it.
+            gpio::OutputConfig::default()
+            .with_drive_mode(gpio::DriveMode::OpenDrain)
+        );
+        for i in 1..30 {
+            buzzer.toggle();
+            busy_wait(Duration::from_millis(i*5));
+        }
     }
 
 
@@ -43,13 +45,68 @@ fn main() -> ! {
     ledc.set_global_slow_clock(ledc::LSGlobalClkSource::APBClk); // nothing works without this line!
 
     // Let's play a melody
-    let song = music::Song::new(nokia::TEMPO);
-    for (note, duration_type) in nokia::MELODY {
-        // Get music note
-        let note_duration = song.calc_note_duration(duration_type) as u64;
-        let pause_duration = note_duration / 10; // 10% of note_duration
+    play_melody(&ledc, buzzer_pin.reborrow().into(), &music::Song::new(nokia::TEMPO), &nokia::MELODY);
+
+    // Song library: play them all.
+    // A button or a web UI could pick one instead: `songs::by_index()`, `songs::by_name()`
+    for song_info in songs::SONGS {
+        busy_wait(Duration::from_millis(1000));
+        info!("Playing: {}", song_info.name);
+        play_melody(&ledc, buzzer_pin.reborrow().into(), &song_info.song(), song_info.melody);
+    }
+
+
+    // Polyphony: melody + bass, on two passive buzzers.
+    // The frequency is set by the timer, so every buzzer needs its own timer *and* channel: a "slot".
+    // `poly::play()` decides which note goes to which slot and when; we just apply the commands.
+    const N_BUZZERS: usize = 2;
+    const TIMERS: [ledc::timer::Number; N_BUZZERS] = [ledc::timer::Number::Timer0, ledc::timer::Number::Timer1];
+    const CHANNELS: [ledc::channel::Number; N_BUZZERS] = [ledc::channel::Number::Channel0, ledc::channel::Number::Channel1];
+    let mut buzzer_pins: [gpio::AnyPin; N_BUZZERS] = [buzzer_pin.into(), peripherals.GPIO10.into()];
+
+    busy_wait(Duration::from_millis(1000));
+    info!("Playing: melody + bass");
+    let start = Instant::now();
+    for (at_ms, command) in poly::play::<2, N_BUZZERS>(&nokia::SCORE) {
+        // Wait for the right moment
+        while start.elapsed() < Duration::from_millis(at_ms as u64) {}
+
+        // Silence: duty=0. The timer still needs some frequency.
+        let (freq, duty_pct) = match command.freq {
+            Some(freq) => (freq, 50),
+            None => (1000, 0),
+        };
+
+        use ledc::timer::TimerIFace;
+        use ledc::channel::ChannelIFace;
+        let mut pwm_timer = ledc.timer::<ledc::LowSpeed>(TIMERS[command.slot]);
+        let mut pwm_channel = ledc.channel(CHANNELS[command.slot], buzzer_pins[command.slot].reborrow());
+        pwm_timer.configure(ledc::timer::config::Config {
+            clock_source: ledc::timer::LSClockSource::APBClk,
+            duty: ledc::timer::config::Duty::Duty10Bit,
+            frequency: Rate::from_hz(freq),
+        }).unwrap();
+        pwm_channel.configure(ledc::channel::config::Config {
+            timer: &pwm_timer,
+            duty_pct,
+            drive_mode: gpio::DriveMode::PushPull,
+        }).unwrap();
+    }
+
+    loop{
+        busy_wait(Duration::from_millis(100));
+    }
+}
+
+// Play a melody on one buzzer
+fn play_melody(ledc: &ledc::Ledc, mut buzzer_pin: gpio::AnyPin, song: &music::Song, melody: &[(f64, i16)]) {
+    for &(note, duration_type) in melody {
+        // Get music note: 90% sound, 10% pause.
+        // NOTE: `render.rs` models the same timing to preview melodies on the host
+        let (play_duration, pause_duration) = song.calc_note_play_pause(duration_type);
+        let (play_duration, pause_duration) = (play_duration as u64, pause_duration as u64);
         if note == music::REST {
-            busy_wait(Duration::from_millis(note_duration));
+            busy_wait(Duration::from_millis(play_duration + pause_duration));
             continue;
         }
         let freq = Rate::from_hz(note as u32);
@@ -76,17 +133,13 @@ fn main() -> ! {
         }).unwrap();
 
         // Play
-        busy_wait(Duration::from_millis(note_duration - pause_duration)); // play 90%
+        busy_wait(Duration::from_millis(play_duration)); // play 90%
 
         // Pause.
         // Disable PWM by setting duty=0: effectively, no signal
         pwm_channel.set_duty(0).unwrap();
         busy_wait(Duration::from_millis(pause_duration)); // Pause for 10%
     }
-
-    loop{
-        busy_wait(Duration::from_millis(100));
-    }
 }
 
 // Blocking delay: burns CPU cycles until `duration` passes.
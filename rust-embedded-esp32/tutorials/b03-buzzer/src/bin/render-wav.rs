@@ -0,0 +1,36 @@
+// Host-side preview: render the melody into a WAV file, the way the buzzer would play it.
+// Listen to it, or diff the file to regression-test a tune.
+//
+// Run me on the host:
+// $ cargo run --bin render-wav --features wav --target x86_64-unknown-linux-gnu -Zbuild-std=std,panic_unwind -- nokia.wav
+// $ aplay nokia.wav
+// Render a song from the library, `songs/*.txt`:
+// $ cargo run --bin render-wav ... -- twinkle.wav twinkle
+use std::{fs::File, io::BufWriter};
+use b03_buzzer::{music, nokia, render, songs};
+
+// Plenty for a buzzer: notes go up to ~5kHz
+const SAMPLE_RATE: u32 = 44_100;
+
+fn main() -> std::io::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let path = args.next().unwrap_or_else(|| "nokia.wav".into());
+
+    // Song: from the library, or the Nokia ringtone
+    let (song, melody) = match args.next() {
+        Some(name) => {
+            let Some(info) = songs::by_name(&name) else {
+                let names: Vec<_> = songs::SONGS.iter().map(|s| s.name).collect();
+                eprintln!("Unknown song: {name}. Available: {}", names.join(", "));
+                std::process::exit(1);
+            };
+            (info.song(), info.melody)
+        }
+        None => (music::Song::new(nokia::TEMPO), &nokia::MELODY[..]),
+    };
+
+    render::write_wav(BufWriter::new(File::create(&path)?), &song, melody, SAMPLE_RATE)?;
+
+    println!("Written: {path}");
+    Ok(())
+}
@@ -1,3 +1,7 @@
-#![no_std]
+// no_std on the device; std in unit-tests and in the host-side `wav` tools
+#![cfg_attr(not(any(test, feature = "wav")), no_std)]
 pub mod music;
 pub mod nokia;
+pub mod poly;
+pub mod render;
+pub mod songs;
@@ -25,6 +25,14 @@ impl Song {
             (duration as f64 * 1.5) as u32
         }
     }
+
+    // Splits the note into (play, pause) durations in milliseconds: 90% sound, 10% silence.
+    // The gap makes two identical notes in a row sound like two notes, not one long note.
+    pub fn calc_note_play_pause(&self, divider: i16) -> (u32, u32) {
+        let note_duration = self.calc_note_duration(divider);
+        let pause_duration = note_duration / 10; // 10% of note_duration
+        (note_duration - pause_duration, pause_duration)
+    }
 }
 
 // Note frequencies in Hertz as f64
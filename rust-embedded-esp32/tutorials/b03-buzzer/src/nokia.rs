@@ -1,4 +1,5 @@
 use crate::music::*;
+use crate::poly::Score;
 
 pub const TEMPO: u16 = 180;
 
@@ -17,4 +18,19 @@ pub const MELODY: [(f64, i16); 13] = [
     (NOTE_CS4, 4),
     (NOTE_E4, 4),
     (NOTE_A4, 2),
-];
\ No newline at end of file
+];
+
+// Bass line: one dotted half note per bar (3/4).
+// Play it together with the melody on a second buzzer.
+pub const BASS: [(f64, i16); 4] = [
+    (NOTE_E3, -2),
+    (NOTE_E3, -2),
+    (NOTE_A2, -2),
+    (NOTE_A2, 2),
+];
+
+// Two voices: melody + bass
+pub const SCORE: Score<2> = Score {
+    tempo: TEMPO,
+    voices: [&MELODY, &BASS],
+};
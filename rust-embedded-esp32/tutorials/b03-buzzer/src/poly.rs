@@ -0,0 +1,240 @@
+// Polyphony: play several voices at once, on several passive buzzers.
+//
+// A buzzer plays one frequency at a time, and the frequency is set by the LEDC *timer*.
+// So every buzzer needs its own (timer, channel) pair: a "slot".
+// ESP32-C3 has 4 low-speed timers and 6 channels: up to 4 notes can sound at once.
+//
+// * `Score`: the music. Several voices, each is a melody in the same format as `nokia::MELODY`
+// * `ScoreEvents`: merges the voices into one timeline of NoteOn/NoteOff events
+// * `VoiceAllocator`: maps sounding notes onto N slots
+// * `play()`: all of the above. Yields commands for the firmware: "at time T, set slot S to frequency F"
+//
+// Nothing here touches the hardware: it's tested on the host.
+use crate::music::{REST, Song};
+
+// A multi-voice score: `V` voices played together
+pub struct Score<'a, const V: usize> {
+    pub tempo: u16,
+    pub voices: [&'a [(f64, i16)]; V],
+}
+
+// Score event
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    // Voice starts playing a note, frequency in Hz
+    NoteOn { voice: usize, freq: u32 },
+    // Voice goes silent: the 10% pause after the note
+    NoteOff { voice: usize },
+}
+
+// Merges the voices of a score into one timeline: (time_ms, Event), ordered by time.
+// Every note gets the same 90/10 treatment as in the single-voice buzzer loop.
+pub struct ScoreEvents<'a, const V: usize> {
+    song: Song,
+    voices: [&'a [(f64, i16)]; V],
+    cursors: [VoiceCursor; V],
+}
+
+// Playback position within one voice
+#[derive(Clone, Copy, Default)]
+struct VoiceCursor {
+    // Index of the next note in the voice
+    next_note: usize,
+    // When the next note starts
+    at_ms: u32,
+    // Pending NoteOff for the note that's playing now
+    off_at_ms: Option<u32>,
+}
+
+impl<'a, const V: usize> ScoreEvents<'a, V> {
+    pub fn new(score: &Score<'a, V>) -> Self {
+        Self {
+            song: Song::new(score.tempo),
+            voices: score.voices,
+            cursors: [VoiceCursor::default(); V],
+        }
+    }
+
+    // When is the next event in this voice? Returns (time, is_note_on)
+    fn peek(&mut self, voice: usize) -> Option<(u32, bool)> {
+        let cursor = &mut self.cursors[voice];
+        if let Some(off_at) = cursor.off_at_ms {
+            return Some((off_at, false));
+        }
+
+        // Rests produce no events: just skip the time
+        while let Some(&(note, divider)) = self.voices[voice].get(cursor.next_note) {
+            if note != REST {
+                return Some((cursor.at_ms, true));
+            }
+            cursor.at_ms += self.song.calc_note_duration(divider);
+            cursor.next_note += 1;
+        }
+        None
+    }
+}
+
+impl<const V: usize> Iterator for ScoreEvents<'_, V> {
+    type Item = (u32, Event);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Pick the voice with the earliest event.
+        // At the same time, NoteOff goes first (false < true): it frees up a slot for the NoteOn.
+        let (at_ms, is_note_on, voice) = (0..V)
+            .filter_map(|voice| self.peek(voice).map(|(at, is_note_on)| (at, is_note_on, voice)))
+            .min()?;
+
+        let cursor = &mut self.cursors[voice];
+        if !is_note_on {
+            cursor.off_at_ms = None;
+            return Some((at_ms, Event::NoteOff { voice }));
+        }
+
+        let (note, divider) = self.voices[voice][cursor.next_note];
+        let (play_ms, pause_ms) = self.song.calc_note_play_pause(divider);
+        cursor.next_note += 1;
+        cursor.off_at_ms = Some(at_ms + play_ms);
+        cursor.at_ms = at_ms + play_ms + pause_ms;
+        Some((at_ms, Event::NoteOn { voice, freq: note as u32 }))
+    }
+}
+
+
+// A command for the hardware: set slot's frequency, or silence it (`None`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Command {
+    pub slot: usize,
+    pub freq: Option<u32>,
+}
+
+// Maps sounding notes onto N slots.
+// When all slots are busy, the note that has been playing the longest is cut off.
+pub struct VoiceAllocator<const N: usize> {
+    slots: [Option<SlotState>; N],
+}
+
+// Who's using the slot
+#[derive(Clone, Copy)]
+struct SlotState {
+    voice: usize,
+    since_ms: u32,
+}
+
+impl<const N: usize> VoiceAllocator<N> {
+    pub const fn new() -> Self {
+        Self { slots: [None; N] }
+    }
+
+    // Which slot is the voice playing on?
+    pub fn slot_of(&self, voice: usize) -> Option<usize> {
+        self.slots.iter().position(|s| matches!(s, Some(s) if s.voice == voice))
+    }
+
+    // Start a note. Returns `None` only if there are no slots at all.
+    pub fn note_on(&mut self, voice: usize, freq: u32, at_ms: u32) -> Option<Command> {
+        let slot = self.slot_of(voice) // same voice: reuse its slot
+            .or_else(|| self.slots.iter().position(Option::is_none)) // a free slot
+            .or_else(|| { // steal the oldest one
+                self.slots.iter().enumerate()
+                    .filter_map(|(i, s)| s.map(|s| (s.since_ms, i)))
+                    .min()
+                    .map(|(_, i)| i)
+            })?;
+        self.slots[slot] = Some(SlotState { voice, since_ms: at_ms });
+        Some(Command { slot, freq: Some(freq) })
+    }
+
+    // Stop a note. Returns `None` if the voice had its slot stolen: nothing to silence.
+    pub fn note_off(&mut self, voice: usize) -> Option<Command> {
+        let slot = self.slot_of(voice)?;
+        self.slots[slot] = None;
+        Some(Command { slot, freq: None })
+    }
+}
+
+impl<const N: usize> Default for VoiceAllocator<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+// Play a score on N slots: yields (time_ms, Command), ordered by time.
+// The firmware waits until `time_ms` since the start, then applies the command.
+pub fn play<'a, const V: usize, const N: usize>(score: &Score<'a, V>) -> impl Iterator<Item = (u32, Command)> + 'a {
+    let mut allocator = VoiceAllocator::<N>::new();
+    ScoreEvents::new(score).filter_map(move |(at_ms, event)| {
+        let command = match event {
+            Event::NoteOn { voice, freq } => allocator.note_on(voice, freq, at_ms),
+            Event::NoteOff { voice } => allocator.note_off(voice),
+        };
+        command.map(|c| (at_ms, c))
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::music::*;
+
+    // tempo=120: whole note = 2000ms
+    const MELODY: [(f64, i16); 3] = [(NOTE_A4, 4), (REST, 4), (NOTE_B4, 4)];
+    const BASS: [(f64, i16); 1] = [(NOTE_A2, 2)];
+
+    #[test]
+    fn test_score_events() {
+        let score = Score { tempo: 120, voices: [&MELODY, &BASS] };
+        let events: Vec<_> = ScoreEvents::new(&score).collect();
+        assert_eq!(events, [
+            (0, Event::NoteOn { voice: 0, freq: 440 }),
+            (0, Event::NoteOn { voice: 1, freq: 110 }),
+            (450, Event::NoteOff { voice: 0 }),
+            (900, Event::NoteOff { voice: 1 }),
+            // The rest: no events
+            (1000, Event::NoteOn { voice: 0, freq: 494 }),
+            (1450, Event::NoteOff { voice: 0 }),
+        ]);
+    }
+
+    #[test]
+    fn test_allocator_steals_oldest() {
+        let mut alloc = VoiceAllocator::<2>::new();
+        assert_eq!(alloc.note_on(0, 440, 0), Some(Command { slot: 0, freq: Some(440) }));
+        assert_eq!(alloc.note_on(1, 110, 10), Some(Command { slot: 1, freq: Some(110) }));
+
+        // No free slots: voice 0 has been playing the longest
+        assert_eq!(alloc.note_on(2, 660, 20), Some(Command { slot: 0, freq: Some(660) }));
+        assert_eq!(alloc.slot_of(0), None);
+        // Voice 0 ends: its slot belongs to voice 2 now, nothing to silence
+        assert_eq!(alloc.note_off(0), None);
+
+        // Freed slot gets reused
+        assert_eq!(alloc.note_off(1), Some(Command { slot: 1, freq: None }));
+        assert_eq!(alloc.note_on(0, 440, 30), Some(Command { slot: 1, freq: Some(440) }));
+
+        // No slots at all: nothing plays
+        assert_eq!(VoiceAllocator::<0>::new().note_on(0, 440, 0), None);
+    }
+
+    #[test]
+    fn test_play_chords() {
+        // Two voices on two buzzers: each voice gets its own slot
+        let score = Score { tempo: 120, voices: [&MELODY, &BASS] };
+        let commands: Vec<_> = play::<2, 2>(&score).collect();
+        assert_eq!(commands[..4], [
+            (0, Command { slot: 0, freq: Some(440) }),
+            (0, Command { slot: 1, freq: Some(110) }),
+            (450, Command { slot: 0, freq: None }),
+            (900, Command { slot: 1, freq: None }),
+        ]);
+
+        // Two voices on one buzzer: the bass cuts the melody off, its NoteOff is dropped
+        let commands: Vec<_> = play::<2, 1>(&score).collect();
+        assert_eq!(commands[..3], [
+            (0, Command { slot: 0, freq: Some(440) }),
+            (0, Command { slot: 0, freq: Some(110) }),
+            (900, Command { slot: 0, freq: None }),
+        ]);
+    }
+}
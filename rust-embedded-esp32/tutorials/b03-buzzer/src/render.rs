@@ -0,0 +1,182 @@
+// Render a melody into PCM samples: preview the tune on the host, without hardware.
+//
+// It models what the LEDC PWM does in `main.rs`:
+// * The frequency is truncated to whole Hz, like `Rate::from_hz(note as u32)`
+// * The buzzer gets a square wave with 50% duty while the note plays (90% of the note)
+// * Then duty=0 for the remaining 10%: silence
+// * A REST is silent for the whole duration of the note
+use crate::music::{REST, Song};
+
+// Square wave amplitude. A quarter of the full scale: square waves are loud!
+pub const AMPLITUDE: i16 = i16::MAX / 4;
+
+// Render the melody into mono 16-bit PCM samples.
+// The iterator is lazy: nothing is allocated, so it works in no_std too.
+pub fn render<'a>(song: &'a Song, melody: &'a [(f64, i16)], sample_rate: u32) -> Samples<'a> {
+    Samples {
+        song,
+        melody,
+        sample_rate,
+        next_note: 0,
+        freq: 0,
+        play_samples: 0,
+        note_samples: 0,
+        pos: 0,
+    }
+}
+
+// Iterator over PCM samples: see render()
+pub struct Samples<'a> {
+    song: &'a Song,
+    melody: &'a [(f64, i16)],
+    sample_rate: u32,
+
+    // Index of the next note in the melody
+    next_note: usize,
+    // Current note: frequency (Hz), the number of samples with sound, total number of samples
+    freq: u32,
+    play_samples: u64,
+    note_samples: u64,
+    // Sample position within the current note
+    pos: u64,
+}
+
+impl Samples<'_> {
+    fn ms_to_samples(&self, ms: u32) -> u64 {
+        ms as u64 * self.sample_rate as u64 / 1000
+    }
+}
+
+impl Iterator for Samples<'_> {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        // Current note is over? Load the next one.
+        // `while`: a note may be too short to produce even a single sample
+        while self.pos >= self.note_samples {
+            let &(note, divider) = self.melody.get(self.next_note)?;
+            self.next_note += 1;
+
+            let (play_ms, pause_ms) = self.song.calc_note_play_pause(divider);
+            let (play_samples, pause_samples) = (self.ms_to_samples(play_ms), self.ms_to_samples(pause_ms));
+            if note == REST {
+                self.freq = 0;
+                self.play_samples = 0;
+            } else {
+                self.freq = note as u32;
+                self.play_samples = play_samples;
+            }
+            self.note_samples = play_samples + pause_samples;
+            self.pos = 0;
+        }
+
+        // Sound or silence
+        let sample = if self.pos < self.play_samples && self.freq > 0 {
+            // 50% duty: count half-periods since the note started. Even = high, odd = low.
+            let half_periods = self.pos * self.freq as u64 * 2 / self.sample_rate as u64;
+            if half_periods % 2 == 0 { AMPLITUDE } else { -AMPLITUDE }
+        } else {
+            0
+        };
+        self.pos += 1;
+        Some(sample)
+    }
+}
+
+// WAV file header: PCM, mono, 16 bit. Followed by `num_samples` little-endian i16 samples.
+pub fn wav_header(sample_rate: u32, num_samples: u32) -> [u8; 44] {
+    const CHANNELS: u16 = 1;
+    const BITS_PER_SAMPLE: u16 = 16;
+    const BLOCK_ALIGN: u16 = CHANNELS * BITS_PER_SAMPLE / 8;
+    let data_size = num_samples * BLOCK_ALIGN as u32;
+
+    let mut header = [0u8; 44];
+    let fields: [&[u8]; 13] = [
+        b"RIFF",
+        &(36 + data_size).to_le_bytes(), // size of everything that follows
+        b"WAVE",
+        b"fmt ",
+        &16u32.to_le_bytes(),            // "fmt " chunk size
+        &1u16.to_le_bytes(),             // format: PCM
+        &CHANNELS.to_le_bytes(),
+        &sample_rate.to_le_bytes(),
+        &(sample_rate * BLOCK_ALIGN as u32).to_le_bytes(), // byte rate
+        &BLOCK_ALIGN.to_le_bytes(),
+        &BITS_PER_SAMPLE.to_le_bytes(),
+        b"data",
+        &data_size.to_le_bytes(),
+    ];
+    let mut offset = 0;
+    for field in fields {
+        header[offset..offset + field.len()].copy_from_slice(field);
+        offset += field.len();
+    }
+    header
+}
+
+// Write the melody as a WAV file.
+// Host only: needs `std`. Enable with `--features wav`
+#[cfg(feature = "wav")]
+pub fn write_wav(
+    mut w: impl std::io::Write,
+    song: &Song,
+    melody: &[(f64, i16)],
+    sample_rate: u32,
+) -> std::io::Result<()> {
+    let num_samples = render(song, melody, sample_rate).count() as u32;
+    w.write_all(&wav_header(sample_rate, num_samples))?;
+    for sample in render(song, melody, sample_rate) {
+        w.write_all(&sample.to_le_bytes())?;
+    }
+    w.flush()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::music::NOTE_A4;
+
+    const SAMPLE_RATE: u32 = 8000;
+
+    #[test]
+    fn test_note_length_and_gap() {
+        // tempo=120: whole note = 2000ms, quarter = 500ms: 450ms sound + 50ms silence
+        let song = Song::new(120);
+        let samples: Vec<i16> = render(&song, &[(NOTE_A4, 4)], SAMPLE_RATE).collect();
+        assert_eq!(samples.len(), 4000);
+
+        let (sound, gap) = samples.split_at(3600);
+        assert!(sound.iter().all(|&s| s == AMPLITUDE || s == -AMPLITUDE));
+        assert!(gap.iter().all(|&s| s == 0));
+
+        // 50% duty, 440 Hz: 440 * 0.45 = 198 periods in 450ms.
+        // The first period starts at t=0: it's not an edge.
+        let rising = sound.windows(2).filter(|w| w[0] < 0 && w[1] > 0).count();
+        assert_eq!(rising, 198 - 1);
+        let high = sound.iter().filter(|&&s| s > 0).count();
+        assert!(high.abs_diff(sound.len() / 2) < 10);
+    }
+
+    #[test]
+    fn test_rest_is_silent() {
+        let song = Song::new(120);
+        let samples: Vec<i16> = render(&song, &[(REST, 4), (NOTE_A4, -4)], SAMPLE_RATE).collect();
+        // Rest: 500ms. Dotted quarter: 750ms
+        assert_eq!(samples.len(), 4000 + 6000);
+        assert!(samples[..4000].iter().all(|&s| s == 0));
+        assert_eq!(samples[4000], AMPLITUDE);
+    }
+
+    #[test]
+    fn test_wav_header() {
+        let header = wav_header(SAMPLE_RATE, 100);
+        assert_eq!(&header[0..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(header[4..8].try_into().unwrap()), 36 + 200);
+        assert_eq!(&header[8..16], b"WAVEfmt ");
+        assert_eq!(u32::from_le_bytes(header[24..28].try_into().unwrap()), SAMPLE_RATE);
+        assert_eq!(u32::from_le_bytes(header[28..32].try_into().unwrap()), SAMPLE_RATE * 2);
+        assert_eq!(&header[36..40], b"data");
+        assert_eq!(u32::from_le_bytes(header[40..44].try_into().unwrap()), 200);
+    }
+}
@@ -0,0 +1,55 @@
+// Song library: a registry of songs, compiled from `songs/*.txt` by `build.rs`.
+// Pick a song by name or by index: e.g. a button cycles through them, or a web UI lists them.
+//
+// Add a song: drop a text file into `songs/`. See the format in `build.rs`.
+// Malformed songs fail the build.
+use crate::music::*;
+
+// A song from the library
+pub struct SongInfo {
+    pub name: &'static str,
+    pub tempo: u16,
+    pub melody: &'static [(f64, i16)],
+}
+
+impl SongInfo {
+    // Get a `Song`: to calculate note durations
+    pub fn song(&self) -> Song {
+        Song::new(self.tempo)
+    }
+}
+
+// Generated: `pub static SONGS: &[SongInfo]`, sorted by name
+include!(concat!(env!("OUT_DIR"), "/songs.rs"));
+
+// Find a song by name
+pub fn by_name(name: &str) -> Option<&'static SongInfo> {
+    SONGS.iter().find(|s| s.name == name)
+}
+
+// Get a song by index: 0..SONGS.len()
+pub fn by_index(index: usize) -> Option<&'static SongInfo> {
+    SONGS.get(index)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry() {
+        // Sorted by file name
+        let names: Vec<_> = SONGS.iter().map(|s| s.name).collect();
+        assert_eq!(names, ["happy-birthday", "ode-to-joy", "twinkle"]);
+
+        let song = by_name("twinkle").unwrap();
+        assert_eq!(song.tempo, 120);
+        assert_eq!(song.melody[..3], [(NOTE_C4, 4), (NOTE_C4, 4), (NOTE_G4, 4)]);
+        assert_eq!(song.melody[6], (NOTE_G4, 2));
+
+        assert_eq!(by_index(1).unwrap().name, "ode-to-joy");
+        assert!(by_index(SONGS.len()).is_none());
+        assert!(by_name("nope").is_none());
+    }
+}
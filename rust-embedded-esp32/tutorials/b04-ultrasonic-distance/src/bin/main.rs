@@ -5,11 +5,12 @@ use esp_backtrace as _;
 esp_bootloader_esp_idf::esp_app_desc!();
 
 use log;
+use b04_ultrasonic_distance::{filter, hcsr04, mapping};
 use esp_hal::{
     delay::Delay,
     rtc_cntl::Rtc,
     clock::CpuClock, gpio, ledc,
-    time::{Duration, Rate},
+    time::{Duration, Instant, Rate},
     main,
 };
 
@@ -30,7 +31,7 @@ fn main() -> ! {
     // - ECHO pin: will respond with a continuous pulse. It's length is proportional to the distance.
     //   The initial state of this pin will be set to Pull Down to ensure it starts in the low state.
     let ultrasonic_trig_pin = peripherals.GPIO0;
-    let mut ultrasonic_trig = gpio::Output::new(ultrasonic_trig_pin, gpio::Level::Low, gpio::OutputConfig::default());
+    let ultrasonic_trig = gpio::Output::new(ultrasonic_trig_pin, gpio::Level::Low, gpio::OutputConfig::default());
     let ultrasonic_echo_pin = peripherals.GPIO1;
     let ultrasonic_echo = gpio::Input::new(ultrasonic_echo_pin, gpio::InputConfig::default()
         .with_pull(gpio::Pull::Down));
@@ -39,42 +40,65 @@ fn main() -> ! {
     let delay = Delay::new();
     let rtc = Rtc::new(peripherals.LPWR);
 
-    // Keep measuring
-    loop {
-        // Send a TRIG to the ultrasonic module.
-        // The datasheet requires it to be at least 10us long.
-        ultrasonic_trig.set_low();
-        delay.delay_micros(2);
-        ultrasonic_trig.set_high();
-        delay.delay_micros(10);
-        ultrasonic_trig.set_low();
+    // The driver: sends the TRIG, measures the ECHO pulse width, with timeouts.
+    // The pulse width tells us how long it took for the ultrasonic waves to travel
+    // to an obstacle and return: the driver converts it to cm.
+    let mut sensor = hcsr04::HcSr04::new(
+        ultrasonic_trig, ultrasonic_echo, delay,
+        || rtc.current_time_us(),
+        hcsr04::Config::default(),
+    );
 
-        // Now measure the response pulse width:
-        // 1. Wait while it's low. Then record the time.
-        // 2. Wait while it's high. Then record the time.
-        while ultrasonic_echo.is_low() {}
-        let time1 = rtc.current_time_us();
-        while ultrasonic_echo.is_high() {}
-        let time2 = rtc.current_time_us();
-        let pulse_width = Duration::from_micros(time2 - time1);
+    // Filters: median drops stray echoes, EMA smooths the jitter
+    let mut median = filter::Median::<5>::new();
+    let mut ema = filter::Ema::new(0.5);
 
-        // The pulse width tells us how long it took for the ultrasonic waves to travel
-        // to an obstacle and return. Since the pulse represents the round-trip time,
-        // we divide it by 2 to account for the journey to the obstacle and back.
-        let distance_cm = (pulse_width.as_micros() as f64 * 0.0343) / 2.0;  // speed of sound / 2
+    // Distance to sound: tone frequency, and beep interval, like a parking sensor.
+    // Try other curves: `Linear`, `Logarithmic`, `Stepped`. See `mapping.rs`
+    const MIN_DISTANCE: f32 = 10.0;
+    const MAX_DISTANCE: f32 = 200.0;
+    let tone = mapping::Mapping {
+        min_cm: MIN_DISTANCE, max_cm: MAX_DISTANCE,
+        near: 700.0, far: 7000.0, // Hz
+        curve: mapping::Linear,
+    };
+    let beep_interval = mapping::Mapping {
+        min_cm: MIN_DISTANCE, max_cm: MAX_DISTANCE,
+        near: 60.0, far: 1000.0, // ms
+        curve: mapping::Stepped::<5>,
+    };
+    let mut beep_on = false;
+    let mut beep_toggled_at = Instant::now();
+
+    // Keep measuring
+    loop {
+        let distance_cm = match sensor.measure_cm() {
+            Ok(d) => ema.push(median.push(d)),
+            // Nothing in range: silence
+            Err(hcsr04::Error::OutOfRange(_) | hcsr04::Error::EchoTimeout) => f32::INFINITY,
+            // Sensor not responding: silence, and complain
+            Err(e) => {
+                log::warn!("Ultrasonic sensor error: {e:?}");
+                f32::INFINITY
+            },
+        };
+        if distance_cm.is_infinite() {
+            median.reset();
+            ema.reset();
+        }
 
         // Convert distance to frequency
-        const MIN_DISTANCE: f64 = 10.0;
-        const MAX_DISTANCE: f64 = 200.0;
-        const MIN_FREQ: u32 = 700;
-        const MAX_FREQ: u32 = 7000;
-        let freq = Rate::from_hz(match distance_cm {
-            n if n < MIN_DISTANCE => MIN_FREQ,
-            n if n > MAX_DISTANCE => MAX_FREQ,
-            n => MIN_FREQ + (MAX_FREQ as f64 * n/MAX_DISTANCE) as u32,
-        });
+        let freq = Rate::from_hz(tone.map(distance_cm) as u32);
+
+        // Beep: toggle the sound when the interval is over. Silence when nothing's in range.
+        let interval = Duration::from_millis(beep_interval.map(distance_cm) as u64);
+        if beep_toggled_at.elapsed() >= interval {
+            beep_on = !beep_on;
+            beep_toggled_at = Instant::now();
+        }
         let duty = match distance_cm {
             n if n > MAX_DISTANCE => 0,
+            _ if !beep_on => 0,
             _ => 10,
         };
         log::info!("distance={distance_cm}cm freq={freq} duty={duty}");
@@ -0,0 +1,113 @@
+// Filters for noisy sensor readings.
+//
+// Ultrasonic readings jump around: a stray echo gives a reading that's way off.
+// * `Median`: the median of the last N readings. Throws away outliers completely.
+// * `Ema`: exponential moving average. Smooths the jitter, but an outlier still leaks through a bit.
+// Use both: median first, then EMA.
+
+// Median of the last N values
+pub struct Median<const N: usize> {
+    window: [f32; N],
+    // Number of values in the window: grows until N
+    len: usize,
+    // Where the next value goes
+    next: usize,
+}
+
+impl<const N: usize> Median<N> {
+    // NOTE: `Median::<0>` won't compile: there's no median of nothing. `Default` goes through here, too.
+    pub const fn new() -> Self {
+        const { assert!(N > 0, "Median: the window can't be empty") };
+        Self { window: [0.0; N], len: 0, next: 0 }
+    }
+
+    // Add a value, get the current median
+    pub fn push(&mut self, value: f32) -> f32 {
+        self.window[self.next] = value;
+        self.next = (self.next + 1) % N;
+        self.len = (self.len + 1).min(N);
+
+        let mut sorted = self.window;
+        let sorted = &mut sorted[..self.len];
+        sorted.sort_unstable_by(f32::total_cmp);
+        let mid = self.len / 2;
+        if self.len % 2 == 1 {
+            sorted[mid]
+        } else {
+            (sorted[mid - 1] + sorted[mid]) / 2.0
+        }
+    }
+
+    // Forget all values: e.g. after the sensor has been out of range
+    pub fn reset(&mut self) {
+        self.len = 0;
+        self.next = 0;
+    }
+}
+
+impl<const N: usize> Default for Median<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Exponential moving average: value = alpha * new + (1 - alpha) * value.
+// alpha=1: no smoothing. alpha closer to 0: smoother, but slower to react.
+pub struct Ema {
+    alpha: f32,
+    value: Option<f32>,
+}
+
+impl Ema {
+    pub const fn new(alpha: f32) -> Self {
+        Self { alpha, value: None }
+    }
+
+    // Add a value, get the current average. The first value is taken as is.
+    pub fn push(&mut self, value: f32) -> f32 {
+        let avg = match self.value {
+            Some(avg) => self.alpha * value + (1.0 - self.alpha) * avg,
+            None => value,
+        };
+        self.value = Some(avg);
+        avg
+    }
+
+    pub fn value(&self) -> Option<f32> {
+        self.value
+    }
+
+    pub fn reset(&mut self) {
+        self.value = None;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_median() {
+        let mut m = Median::<5>::new();
+        assert_eq!(m.push(10.0), 10.0);
+        assert_eq!(m.push(12.0), 11.0); // even: average of the middle two
+        assert_eq!(m.push(300.0), 12.0); // outlier: ignored
+        assert_eq!(m.push(11.0), 11.5);
+        assert_eq!(m.push(13.0), 12.0);
+        // Window is full: 10.0 is gone
+        assert_eq!(m.push(14.0), 13.0);
+
+        m.reset();
+        assert_eq!(m.push(1.0), 1.0);
+    }
+
+    #[test]
+    fn test_ema() {
+        let mut ema = Ema::new(0.25);
+        assert_eq!(ema.value(), None);
+        assert_eq!(ema.push(100.0), 100.0);
+        assert_eq!(ema.push(200.0), 125.0);
+        assert_eq!(ema.push(125.0), 125.0);
+    }
+}
@@ -0,0 +1,218 @@
+// HC-SR04 ultrasonic distance sensor driver.
+//
+// How it works:
+// 1. TRIG: send a 10us pulse. The sensor emits 8 ultrasonic bursts.
+// 2. ECHO: the sensor raises it and keeps it high until the echo returns.
+//    The pulse width is the round-trip time of the sound: distance = width * speed_of_sound / 2
+//
+// It works with any embedded-hal pins, delay, and a microsecond clock:
+// on the host, we test it with fake pins and a fake clock.
+// Unlike `while echo.is_low() {}`, every wait has a timeout: a disconnected sensor won't hang the board.
+use embedded_hal::{
+    delay::DelayNs,
+    digital::{InputPin, OutputPin},
+};
+
+// A microsecond clock: anything that tells the current time.
+// E.g. `|| rtc.current_time_us()`
+pub trait Clock {
+    fn now_us(&mut self) -> u64;
+}
+
+impl<F: FnMut() -> u64> Clock for F {
+    fn now_us(&mut self) -> u64 {
+        self()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error<E> {
+    // GPIO error
+    Pin(E),
+    // ECHO never went high: the sensor is not connected? Not powered?
+    NoEcho,
+    // ECHO stayed high for too long: nothing in range, or a broken sensor
+    EchoTimeout,
+    // Measured distance (cm) is outside of [min_cm; max_cm]: not reliable
+    OutOfRange(f32),
+}
+
+// Sensor config
+#[derive(Debug, Clone, Copy)]
+pub struct Config {
+    // Reliable range, cm. The datasheet says 2cm .. 400cm
+    pub min_cm: f32,
+    pub max_cm: f32,
+    // Air temperature, Celsius: the speed of sound depends on it
+    pub temperature_c: f32,
+    // How long to wait for ECHO to go high after the TRIG, us.
+    pub echo_start_timeout_us: u64,
+    // Max ECHO pulse width, us. The sensor gives up at ~38ms when there's no obstacle.
+    pub echo_timeout_us: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            min_cm: 2.0,
+            max_cm: 400.0,
+            temperature_c: 20.0,
+            echo_start_timeout_us: 10_000,
+            echo_timeout_us: 30_000,
+        }
+    }
+}
+
+// Speed of sound in the air at the given temperature, in cm/us.
+// 331.3 m/s at 0°C, +0.606 m/s for every °C
+pub fn speed_of_sound_cm_per_us(temperature_c: f32) -> f32 {
+    (331.3 + 0.606 * temperature_c) / 10_000.0
+}
+
+// Pulse width to distance. The sound travels there and back: divide by 2
+pub fn pulse_to_cm(pulse_us: u64, temperature_c: f32) -> f32 {
+    pulse_us as f32 * speed_of_sound_cm_per_us(temperature_c) / 2.0
+}
+
+
+pub struct HcSr04<Trig, Echo, Delay, C> {
+    trig: Trig,
+    echo: Echo,
+    delay: Delay,
+    clock: C,
+    config: Config,
+}
+
+impl<Trig, Echo, Delay, C, E> HcSr04<Trig, Echo, Delay, C>
+where
+    Trig: OutputPin<Error = E>,
+    Echo: InputPin<Error = E>,
+    Delay: DelayNs,
+    C: Clock,
+{
+    pub fn new(trig: Trig, echo: Echo, delay: Delay, clock: C, config: Config) -> Self {
+        Self { trig, echo, delay, clock, config }
+    }
+
+    // Update the air temperature: e.g. from a thermistor
+    pub fn set_temperature(&mut self, temperature_c: f32) {
+        self.config.temperature_c = temperature_c;
+    }
+
+    // Measure the distance, cm.
+    // NOTE: the datasheet wants >=60ms between measurements: otherwise, you'll get echoes of the previous one.
+    pub fn measure_cm(&mut self) -> Result<f32, Error<E>> {
+        let pulse_us = self.measure_pulse_us()?;
+        let distance = pulse_to_cm(pulse_us, self.config.temperature_c);
+        if distance < self.config.min_cm || distance > self.config.max_cm {
+            return Err(Error::OutOfRange(distance));
+        }
+        Ok(distance)
+    }
+
+    // Trigger the sensor, measure the ECHO pulse width, us.
+    pub fn measure_pulse_us(&mut self) -> Result<u64, Error<E>> {
+        // TRIG: at least 10us.
+        self.trig.set_low().map_err(Error::Pin)?;
+        self.delay.delay_us(2);
+        self.trig.set_high().map_err(Error::Pin)?;
+        self.delay.delay_us(10);
+        self.trig.set_low().map_err(Error::Pin)?;
+
+        // Wait for the ECHO to start, then wait for it to end
+        let started = self.wait_for(true, self.config.echo_start_timeout_us, Error::NoEcho)?;
+        let ended = self.wait_for(false, self.config.echo_timeout_us, Error::EchoTimeout)?;
+        Ok(ended - started)
+    }
+
+    // Wait until ECHO has the `level`. Returns the time when it happened, or `timeout_error`.
+    fn wait_for(&mut self, level: bool, timeout_us: u64, timeout_error: Error<E>) -> Result<u64, Error<E>> {
+        let start = self.clock.now_us();
+        while self.echo.is_high().map_err(Error::Pin)? != level {
+            if self.clock.now_us() - start > timeout_us {
+                return Err(timeout_error);
+            }
+        }
+        Ok(self.clock.now_us())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::{cell::Cell, convert::Infallible};
+    use embedded_hal::digital::ErrorType;
+
+    // Fake clock: time only moves when someone looks at it, or waits
+    struct FakeTime(Cell<u64>);
+    impl FakeTime {
+        fn tick(&self, us: u64) -> u64 {
+            self.0.set(self.0.get() + us);
+            self.0.get()
+        }
+    }
+
+    // Fake sensor: ECHO is high during [rise_us; fall_us) since the TRIG
+    struct FakeEcho<'a> {
+        time: &'a FakeTime,
+        echo: Option<(u64, u64)>,
+    }
+    impl ErrorType for FakeEcho<'_> {
+        type Error = Infallible;
+    }
+    impl InputPin for FakeEcho<'_> {
+        fn is_high(&mut self) -> Result<bool, Infallible> {
+            let now = self.time.0.get();
+            Ok(self.echo.is_some_and(|(rise, fall)| now >= rise && now < fall))
+        }
+        fn is_low(&mut self) -> Result<bool, Infallible> {
+            self.is_high().map(|h| !h)
+        }
+    }
+
+    struct FakeTrig;
+    impl ErrorType for FakeTrig {
+        type Error = Infallible;
+    }
+    impl OutputPin for FakeTrig {
+        fn set_low(&mut self) -> Result<(), Infallible> { Ok(()) }
+        fn set_high(&mut self) -> Result<(), Infallible> { Ok(()) }
+    }
+
+    struct FakeDelay<'a>(&'a FakeTime);
+    impl DelayNs for FakeDelay<'_> {
+        fn delay_ns(&mut self, ns: u32) {
+            self.0.tick(ns as u64 / 1000);
+        }
+    }
+
+    // Measure with a sensor that responds with ECHO at [rise; fall), us since start
+    fn measure(echo: Option<(u64, u64)>, config: Config) -> Result<f32, Error<Infallible>> {
+        let time = FakeTime(Cell::new(0));
+        let clock = || time.tick(1);
+        let mut sensor = HcSr04::new(FakeTrig, FakeEcho { time: &time, echo }, FakeDelay(&time), clock, config);
+        sensor.measure_cm()
+    }
+
+    #[test]
+    fn test_distance() {
+        // 20°C: 343.4 m/s. 10cm: 582us round trip
+        let d = measure(Some((100, 100 + 582)), Config::default()).unwrap();
+        assert!((d - 10.0).abs() < 0.1, "{d}");
+
+        // Colder air: slower sound, same pulse means shorter distance
+        let cold = measure(Some((100, 100 + 582)), Config { temperature_c: -20.0, ..Default::default() }).unwrap();
+        assert!((cold - 9.3).abs() < 0.1, "{cold}");
+    }
+
+    #[test]
+    fn test_errors() {
+        // Disconnected sensor: returns, doesn't hang
+        assert_eq!(measure(None, Config::default()), Err(Error::NoEcho));
+        // ECHO stuck high
+        assert_eq!(measure(Some((100, u64::MAX)), Config::default()), Err(Error::EchoTimeout));
+        // Too close: 1cm
+        assert!(matches!(measure(Some((100, 158)), Config::default()), Err(Error::OutOfRange(d)) if d < 2.0));
+    }
+}
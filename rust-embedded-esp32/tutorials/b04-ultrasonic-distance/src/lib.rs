@@ -1 +1,4 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
+pub mod filter;
+pub mod hcsr04;
+pub mod mapping;
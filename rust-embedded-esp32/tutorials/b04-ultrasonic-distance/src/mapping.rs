@@ -0,0 +1,152 @@
+// Map distance to an output: tone frequency, beep interval, anything.
+//
+// `Mapping` maps [min_cm; max_cm] to [near; far]: `near` at min_cm, `far` at max_cm.
+// The output never leaves [near; far]: out of range distances are clamped, and so are NaNs and infinities.
+//
+// The `Curve` decides how the output changes in between:
+// * `Linear`: proportionally
+// * `Logarithmic`: changes fast when close, slowly when far: more resolution where it matters
+// * `Stepped`: a few discrete zones, like a parking sensor: beep... beep.. beep. beepbeepbeep
+// Implement `Curve` to plug in your own.
+
+// Curve: distance to a fraction in [0; 1]. 0 = near, 1 = far.
+// The result is clamped anyway: a buggy curve can't make the output go out of bounds.
+pub trait Curve {
+    fn fraction(&self, distance_cm: f32, min_cm: f32, max_cm: f32) -> f32;
+}
+
+pub struct Linear;
+
+impl Curve for Linear {
+    fn fraction(&self, distance_cm: f32, min_cm: f32, max_cm: f32) -> f32 {
+        (distance_cm - min_cm) / (max_cm - min_cm)
+    }
+}
+
+// Log scale: every doubling of the distance moves the output by the same amount.
+// Needs min_cm > 0. Otherwise, falls back to linear.
+pub struct Logarithmic;
+
+impl Curve for Logarithmic {
+    fn fraction(&self, distance_cm: f32, min_cm: f32, max_cm: f32) -> f32 {
+        if min_cm <= 0.0 {
+            return Linear.fraction(distance_cm, min_cm, max_cm);
+        }
+        if distance_cm <= min_cm {
+            return 0.0; // no log of negatives, please
+        }
+        libm::logf(distance_cm / min_cm) / libm::logf(max_cm / min_cm)
+    }
+}
+
+// N discrete zones, evenly spread over the range: the output only takes N values.
+// The first zone gives `near`, the last one gives `far`.
+// NOTE: N < 2 won't compile: one zone would be `near` and `far` at the same time.
+pub struct Stepped<const N: u8>;
+
+impl<const N: u8> Curve for Stepped<N> {
+    fn fraction(&self, distance_cm: f32, min_cm: f32, max_cm: f32) -> f32 {
+        const { assert!(N >= 2, "Stepped: needs at least 2 zones") };
+        let zones = N as f32;
+        let t = clamp_fraction(Linear.fraction(distance_cm, min_cm, max_cm));
+        let zone = (t * zones).min(zones - 1.0) as u8; // as u8: rounds down
+        zone as f32 / (zones - 1.0)
+    }
+}
+
+
+pub struct Mapping<C: Curve> {
+    pub min_cm: f32,
+    pub max_cm: f32,
+    // Output at min_cm, and at max_cm. `near` can be larger than `far`.
+    pub near: f32,
+    pub far: f32,
+    pub curve: C,
+}
+
+impl<C: Curve> Mapping<C> {
+    pub fn map(&self, distance_cm: f32) -> f32 {
+        let t = clamp_fraction(self.curve.fraction(distance_cm, self.min_cm, self.max_cm));
+        let value = self.near + (self.far - self.near) * t;
+
+        // Float rounding may overshoot by a tiny bit: clamp
+        value.clamp(self.near.min(self.far), self.near.max(self.far))
+    }
+}
+
+// Clamp to [0; 1]. NaN: let's say it's far away
+fn clamp_fraction(t: f32) -> f32 {
+    if t.is_nan() { 1.0 } else { t.clamp(0.0, 1.0) }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const fn freq<C: Curve>(curve: C) -> Mapping<C> {
+        Mapping { min_cm: 10.0, max_cm: 200.0, near: 700.0, far: 7000.0, curve }
+    }
+
+    #[test]
+    fn test_curves() {
+        // Linear. The old `MIN_FREQ + MAX_FREQ * n/MAX` gave 700 + 7000 = 7700 at 200cm
+        assert_eq!(freq(Linear).map(10.0), 700.0);
+        assert_eq!(freq(Linear).map(105.0), 3850.0);
+        assert_eq!(freq(Linear).map(200.0), 7000.0);
+
+        // Log: 20cm is one "doubling" out of log2(20) = 4.32
+        let log = freq(Logarithmic).map(20.0);
+        assert!((log - (700.0 + 6300.0 / 4.3219)).abs() < 1.0, "{log}");
+
+        // Parking sensor: 4 zones of 47.5cm
+        let beep = Mapping { min_cm: 10.0, max_cm: 200.0, near: 50.0, far: 1000.0, curve: Stepped::<4> };
+        assert_eq!(beep.map(20.0), 50.0);
+        assert_eq!(beep.map(60.0).round(), 367.0);
+        assert_eq!(beep.map(150.0).round(), 683.0);
+        assert_eq!(beep.map(199.0), 1000.0);
+    }
+
+    #[test]
+    fn test_edge_cases() {
+        assert_eq!(freq(Linear).map(0.0), 700.0);
+        assert_eq!(freq(Linear).map(f32::INFINITY), 7000.0);
+        assert_eq!(freq(Linear).map(f32::NAN), 7000.0);
+        assert_eq!(freq(Logarithmic).map(-5.0), 700.0);
+    }
+
+    // Any curve, any distance: the output stays within [near; far], and moves monotonically.
+    fn check_bounds<C: Curve>(mapping: &Mapping<C>, a: f32, b: f32) -> Result<(), TestCaseError> {
+        let (lo, hi) = (mapping.near.min(mapping.far), mapping.near.max(mapping.far));
+        let (va, vb) = (mapping.map(a), mapping.map(b));
+        prop_assert!(va >= lo && va <= hi, "map({a}) = {va} is out of [{lo}; {hi}]");
+        prop_assert!(vb >= lo && vb <= hi, "map({b}) = {vb} is out of [{lo}; {hi}]");
+
+        // Monotonic: further away = closer to `far`
+        if a <= b {
+            let (da, db) = ((va - mapping.far).abs(), (vb - mapping.far).abs());
+            prop_assert!(db <= da + 1e-3, "map({a}) = {va}, map({b}) = {vb}: not monotonic");
+        }
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn prop_bounds(
+            a in prop::num::f32::ANY,
+            b in -100.0f32..1000.0,
+            min_cm in 0.0f32..100.0,
+            span in 1.0f32..500.0,
+            near in 0.0f32..10_000.0,
+            far in 0.0f32..10_000.0,
+        ) {
+            let max_cm = min_cm + span;
+            check_bounds(&Mapping { min_cm, max_cm, near, far, curve: Linear }, a, b)?;
+            check_bounds(&Mapping { min_cm, max_cm, near, far, curve: Logarithmic }, a, b)?;
+            check_bounds(&Mapping { min_cm, max_cm, near, far, curve: Stepped::<2> }, a, b)?;
+            check_bounds(&Mapping { min_cm, max_cm, near, far, curve: Stepped::<3> }, a, b)?;
+            check_bounds(&Mapping { min_cm, max_cm, near, far, curve: Stepped::<10> }, a, b)?;
+        }
+    }
+}
@@ -7,28 +7,47 @@ use {esp_backtrace as _, esp_println as _};
 // This time we'll use defmt
 use defmt;
 use esp_hal::{
-    delay,
     gpio,
-    main,
+    timer::timg::TimerGroup,
 };
+use embassy_executor::Spawner;
+use embassy_time::{Delay, Instant};
 
-#[main]
-fn main() -> ! {
+// Motion events: ../../libs/pir
+use pir::{Event, Pir};
+
+#[esp_rtos::main]
+async fn main(_spawner: Spawner) -> ! {
     // Peripherals
     let peripherals = esp_hal::init(esp_hal::Config::default());
 
+    // RTOS
+    let timg0 = TimerGroup::new(peripherals.TIMG0);
+    let sw_interrupt = esp_hal::interrupt::software::SoftwareInterruptControl::new(peripherals.SW_INTERRUPT);
+    esp_rtos::start(timg0.timer0, sw_interrupt.software_interrupt0);
+
     // PIR Sensor.
     // With an initial state of "pull down": it goes HIGH when motion is detected.
     let pir_sensor = gpio::Input::new(peripherals.GPIO0, gpio::InputConfig::default()
         .with_pull(gpio::Pull::Down));
 
-    let delay = delay::Delay::new();
+    // Raw HIGH/LOW -> events: motion, occupied, vacant.
+    // Re-triggers within 5s are the same motion. No motion for a minute: the room is empty.
+    let mut pir = Pir::new(pir_sensor, Delay, || Instant::now().as_millis(), pir::Config {
+        retrigger_ms: 5_000,
+        vacancy_timeout_ms: 60_000,
+    });
+
+    // Wait for someone to come in, then wait for everyone to leave:
+    //   pir.wait_for_motion().await.unwrap();
+    //   pir.wait_for_vacancy().await.unwrap();
+    // Or, handle every event:
     loop {
-        if pir_sensor.is_high() {
-            defmt::info!("Motion detected");
-            // TODO: You can trigger the buzzer here.
-            delay.delay_millis(100);
+        match pir.next_event().await.unwrap() {
+            Event::Occupied => defmt::info!("Someone came in"),
+            Event::Motion => defmt::info!("Motion detected"),
+            Event::Vacant => defmt::info!("The room is empty"),
         }
-        delay.delay_millis(100);
+        // TODO: You can trigger the buzzer on Motion, and turn the LED on while occupied.
     }
 }
@@ -0,0 +1,143 @@
+#![no_std]
+#![no_main]
+#![deny(clippy::mem_forget)]
+use esp_backtrace as _;
+esp_bootloader_esp_idf::esp_app_desc!();
+
+// Robot arm: 4 servos, playing choreography from ../../choreography/*.txt
+// $ cargo run --bin arm
+
+use esp_hal::{
+    clock::CpuClock, gpio, ledc,
+    timer::timg::TimerGroup,
+    time::Rate,
+};
+use embassy_executor::Spawner;
+use embassy_time::{Duration, Ticker, Timer};
+use embassy_sync::{
+    blocking_mutex::raw::CriticalSectionRawMutex,
+    channel::Channel,
+};
+use static_cell::StaticCell;
+use b06_servo::{
+    sequence::{Player, Sequence},
+    servo::{self, Servo},
+};
+
+// Joints: base, shoulder, elbow, gripper
+const N_SERVOS: usize = 4;
+type ArmServo = Servo<ledc::channel::Channel<'static, ledc::LowSpeed>>;
+type ArmSequence = Sequence<N_SERVOS>;
+
+// Update the servos once per PWM period: 50Hz
+const TICK_MS: u32 = 20;
+
+// Where the arm starts
+const HOME: [f32; N_SERVOS] = [90.0, 90.0, 90.0, 90.0];
+
+// Calibrate every servo separately: they're all a bit different.
+// Speed limits, °/s: the base is heavy, go easy on it.
+const CONFIGS: [servo::Config; N_SERVOS] = [joint(120.0), joint(180.0), joint(240.0), joint(360.0)];
+
+const fn joint(max_speed: f32) -> servo::Config {
+    servo::Config { min_pulse_us: 600, max_pulse_us: 2575, range_deg: 180.0, period_us: 20_000, max_speed }
+}
+
+// Sequences to play: main() sends, the player task plays them one after another.
+static QUEUE: Channel<CriticalSectionRawMutex, ArmSequence, 2> = Channel::new();
+
+
+#[esp_rtos::main]
+async fn main(spawner: Spawner) -> ! {
+    esp_println::logger::init_logger_from_env();
+    let peripherals = esp_hal::init(esp_hal::Config::default().with_cpu_clock(CpuClock::max()));
+
+    // RTOS
+    let timg0 = TimerGroup::new(peripherals.TIMG0);
+    let sw_interrupt = esp_hal::interrupt::software::SoftwareInterruptControl::new(peripherals.SW_INTERRUPT);
+    esp_rtos::start(timg0.timer0, sw_interrupt.software_interrupt0);
+
+    // PWM: one timer, 4 channels
+    let mut ledc = ledc::Ledc::new(peripherals.LEDC);
+    ledc.set_global_slow_clock(ledc::LSGlobalClkSource::APBClk); // nothing works without this line!
+    use ledc::timer::TimerIFace;  // brings: .configure()
+    use ledc::channel::ChannelIFace;  // brings: .configure()
+
+    // The channels keep a reference to the timer, and the task needs them 'static: make the timer static.
+    static PWM_TIMER: StaticCell<ledc::timer::Timer<'static, ledc::LowSpeed>> = StaticCell::new();
+    let pwm_timer = PWM_TIMER.init(ledc.timer::<ledc::LowSpeed>(ledc::timer::Number::Timer0));
+    pwm_timer.configure(ledc::timer::config::Config {
+        clock_source: ledc::timer::LSClockSource::APBClk,
+        duty: ledc::timer::config::Duty::Duty12Bit,
+        frequency: Rate::from_hz(50),
+    }).unwrap();
+    let pwm_timer = &*pwm_timer;
+
+    let channels = [
+        ledc.channel(ledc::channel::Number::Channel0, peripherals.GPIO0),
+        ledc.channel(ledc::channel::Number::Channel1, peripherals.GPIO1),
+        ledc.channel(ledc::channel::Number::Channel2, peripherals.GPIO3),
+        ledc.channel(ledc::channel::Number::Channel3, peripherals.GPIO4),
+    ];
+    let mut configs = CONFIGS.into_iter();
+    let servos = channels.map(|mut channel| {
+        channel.configure(ledc::channel::config::Config {
+            timer: pwm_timer,
+            duty_pct: 0,
+            drive_mode: gpio::DriveMode::PushPull,
+        }).unwrap();
+        Servo::new(channel, configs.next().unwrap())
+    });
+
+    spawner.spawn(task_player(servos)).unwrap();
+
+    // Choreography: compiled in
+    const CHOREOGRAPHY: [(&str, &str); 2] = [
+        ("wave", include_str!("../../choreography/wave.txt")),
+        ("pick-and-place", include_str!("../../choreography/pick-and-place.txt")),
+    ];
+
+    loop {
+        for (name, text) in CHOREOGRAPHY {
+            match ArmSequence::parse(text) {
+                Ok(sequence) => {
+                    let duration = Player::duration_ms(&sequence, HOME, CONFIGS.map(|config| config.max_speed));
+                    log::info!("Queued: {name} ({duration}ms from home)");
+                    // Waits while the queue is full
+                    QUEUE.send(sequence).await;
+                }
+                Err(err) => log::error!("{name}.txt: {err:?}"),
+            }
+        }
+        Timer::after(Duration::from_secs(2)).await;
+    }
+}
+
+
+// Task: play sequences from the queue, one after another
+#[embassy_executor::task]
+async fn task_player(mut servos: [ArmServo; N_SERVOS]) -> ! {
+    // Go home first: instantly. We don't know where the servos are.
+    for (servo, angle) in servos.iter_mut().zip(HOME) {
+        servo.set_angle(angle).unwrap();
+    }
+
+    // Speed limits: from every servo's config
+    let max_speed = servos.each_ref().map(|servo| servo.config().max_speed);
+
+    let mut ticker = Ticker::every(Duration::from_millis(TICK_MS as u64));
+    loop {
+        let sequence = QUEUE.receive().await;
+
+        // Continue from wherever the last sequence has left the arm
+        let start = core::array::from_fn(|i| servos[i].angle().unwrap_or(HOME[i]));
+        ticker.reset();
+        for pose in Player::new(&sequence, start, max_speed, TICK_MS) {
+            for (servo, angle) in servos.iter_mut().zip(pose) {
+                servo.set_angle(angle).unwrap();
+            }
+            ticker.next().await;
+        }
+        log::info!("Done: {} keyframes", sequence.keyframes.len());
+    }
+}
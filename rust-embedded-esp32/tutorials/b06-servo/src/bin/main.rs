@@ -8,10 +8,14 @@ use log;
 use esp_hal::{
     delay::Delay,
     clock::CpuClock, gpio, ledc,
-    time::{Duration, Rate},
+    time::{Duration, Instant, Rate},
     rng::Rng,
     main,
 };
+use b06_servo::{
+    motion::Easing,
+    servo::{self, Servo},
+};
 
 #[main]
 fn main() -> ! {
@@ -43,40 +47,34 @@ fn main() -> ! {
 
     // We need to control the pulse width using duty cycle.
     // Max duty cycle depends on the duty resolution bits we've configured.
-    use embedded_hal::pwm::SetDutyCycle;
-    let max_duty_cycle = pwm_channel.max_duty_cycle();
-
-    // Convert our servo's extreme values into duty cycle range
+    // The servo converts our servo's extreme values into the duty cycle range: see servo::Config::duty()
     const SERVO_MIN: Duration = Duration::from_micros(600);
     const SERVO_MAX: Duration = Duration::from_micros(2575);
     const PWM_FREQ: Rate = Rate::from_hz(50);
-    let min_duty: u16 = (max_duty_cycle as f64 * (SERVO_MIN.as_micros() as f64) / (PWM_FREQ.as_duration().as_micros() as f64)) as u16;
-    let max_duty: u16 = (max_duty_cycle as f64 * (SERVO_MAX.as_micros() as f64) / (PWM_FREQ.as_duration().as_micros() as f64)) as u16;
-
-    log::info!("max_duty_cycle={max_duty_cycle}");
-    log::info!("min_duty={min_duty}");
-    log::info!("max_duty={max_duty}");
+    let mut servo = Servo::new(pwm_channel, servo::Config {
+        min_pulse_us: SERVO_MIN.as_micros() as u32,
+        max_pulse_us: SERVO_MAX.as_micros() as u32,
+        range_deg: 180.0,
+        period_us: PWM_FREQ.as_duration().as_micros() as u32,
+        max_speed: 240.0, // °/s
+    });
 
     // Go
     let delay = Delay::new();
     let rng = Rng::new();
+    let now_ms = || Instant::now().duration_since_epoch().as_millis();
     loop {
-        // Gen random angle
-        let angle = (rng.random() % 180) as u8;
+        // Gen random angle: fractional
+        let angle = (rng.random() % 1800) as f32 / 10.0;
+        log::info!("angle={angle}");
 
-        // Go to angle
-        let duty = duty_from_angle(angle, min_duty, max_duty);
-        pwm_channel.set_duty_cycle(duty).unwrap();
+        // Go to angle: smoothly. Update once per PWM period: the servo won't notice more often anyway.
+        servo.move_to(angle, Easing::EaseInOut, now_ms());
+        while servo.tick(now_ms()).unwrap() {
+            delay.delay_millis(20);
+        }
 
         // Sleep
         delay.delay_millis(500);
     }
 }
-
-
-// Angle => Duty perc
-// 0 .. 180
-fn duty_from_angle(deg: u8, min_duty: u16, max_duty: u16) -> u16 {
-    let k = deg as f32 / 180.0;  // 0..100
-    min_duty + ((max_duty-min_duty) as f32 * k) as u16
-}
@@ -1 +1,4 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
+pub mod motion;
+pub mod sequence;
+pub mod servo;
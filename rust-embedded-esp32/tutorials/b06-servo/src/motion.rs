@@ -0,0 +1,129 @@
+// Motion over time: from one angle to another, smoothly.
+//
+// Jumping to an angle instantly makes the servo slam at full speed: it jerks, overshoots, and draws a current spike.
+// Instead, we move it gradually: a `Motion` tells where the servo should be at any moment.
+//
+// * Max speed: the motion takes as long as needed so that the servo never exceeds it.
+// * Easing: how the speed changes along the way.
+//   `Linear`: constant speed: starts and stops abruptly.
+//   `EaseInOut`: accelerates, then decelerates: smooth. Peaks at 1.5x the average speed.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    EaseInOut,
+}
+
+impl Easing {
+    // Progress in time [0; 1] -> progress in distance [0; 1]
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Self::Linear => t,
+            // Smoothstep: 3t² - 2t³. Zero speed at both ends.
+            Self::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+
+    // Peak speed, relative to the average: the derivative at its max.
+    // Smoothstep: 6t - 6t² peaks at t=0.5 with 1.5
+    pub fn peak_speed(self) -> f32 {
+        match self {
+            Self::Linear => 1.0,
+            Self::EaseInOut => 1.5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Motion {
+    pub from: f32,
+    pub to: f32,
+    pub duration_ms: u32,
+    pub easing: Easing,
+}
+
+impl Motion {
+    // Move as fast as possible, but never faster than `max_speed` (degrees per second)
+    pub fn new(from: f32, to: f32, max_speed: f32, easing: Easing) -> Self {
+        let distance = (to - from).abs();
+        let duration_ms = if max_speed > 0.0 {
+            (distance / max_speed * easing.peak_speed() * 1000.0) as u32
+        } else {
+            0 // no limit
+        };
+        Self { from, to, duration_ms, easing }
+    }
+
+    // Move in exactly this time
+    pub const fn timed(from: f32, to: f32, duration_ms: u32, easing: Easing) -> Self {
+        Self { from, to, duration_ms, easing }
+    }
+
+    // Where the servo should be `elapsed_ms` after the start
+    pub fn angle_at(&self, elapsed_ms: u32) -> f32 {
+        if elapsed_ms >= self.duration_ms {
+            return self.to;
+        }
+        let t = elapsed_ms as f32 / self.duration_ms as f32;
+        self.from + (self.to - self.from) * self.easing.apply(t)
+    }
+
+    pub fn is_done(&self, elapsed_ms: u32) -> bool {
+        elapsed_ms >= self.duration_ms
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_easing() {
+        for easing in [Easing::Linear, Easing::EaseInOut] {
+            assert_eq!(easing.apply(0.0), 0.0);
+            assert_eq!(easing.apply(0.5), 0.5);
+            assert_eq!(easing.apply(1.0), 1.0);
+            assert_eq!(easing.apply(2.0), 1.0);
+        }
+        // Slow start
+        assert!(Easing::EaseInOut.apply(0.1) < 0.1 / 2.0);
+    }
+
+    #[test]
+    fn test_duration() {
+        // 90° at 180°/s: half a second
+        assert_eq!(Motion::new(0.0, 90.0, 180.0, Easing::Linear).duration_ms, 500);
+        // Backwards, just the same
+        assert_eq!(Motion::new(90.0, 0.0, 180.0, Easing::Linear).duration_ms, 500);
+        // Ease-in-out is slower on average: the peak is limited
+        assert_eq!(Motion::new(0.0, 90.0, 180.0, Easing::EaseInOut).duration_ms, 750);
+        // No limit: instant
+        let m = Motion::new(0.0, 90.0, 0.0, Easing::Linear);
+        assert_eq!(m.duration_ms, 0);
+        assert_eq!(m.angle_at(0), 90.0);
+    }
+
+    // Walk the trajectory with a fixed tick: the speed limit holds at every step
+    #[test]
+    fn test_trajectory() {
+        const TICK_MS: u32 = 20;
+        for easing in [Easing::Linear, Easing::EaseInOut] {
+            let m = Motion::new(10.0, 170.0, 200.0, easing);
+            let mut prev = m.angle_at(0);
+            assert_eq!(prev, 10.0);
+
+            let mut t = 0;
+            while !m.is_done(t) {
+                t += TICK_MS;
+                let angle = m.angle_at(t);
+                let speed = (angle - prev) / TICK_MS as f32 * 1000.0;
+                assert!(speed >= 0.0, "{easing:?}: going backwards at {t}ms");
+                assert!(speed <= 200.0 + 1.0, "{easing:?}: {speed}°/s at {t}ms");
+                prev = angle;
+            }
+            assert_eq!(prev, 170.0);
+        }
+    }
+}
@@ -0,0 +1,279 @@
+// Choreography for N servos: a sequence of keyframes.
+//
+// A keyframe is a pose (an angle for every servo) and how long it takes to get there.
+// All servos move together: they start at the same time and arrive at the same time.
+// If some servo can't make it in time (speed limit), the whole keyframe takes longer: they still arrive together.
+//
+// Text format: one keyframe per line.
+//
+//   # Wave hello. Joints: base, shoulder, elbow, gripper
+//   # <duration ms> [linear|ease]: <angle> <angle> ...
+//   500: 90 60 120 0        # ease-in-out by default
+//   300 linear: _ _ 150 _   # `_`: stay where you are
+//   1000 hold               # pause
+//
+// `Player` then plays it at a fixed tick: every `tick_ms`, it gives the next pose.
+
+use crate::motion::{Easing, Motion};
+
+// Enough for a little dance, and small enough to pass around
+pub const MAX_KEYFRAMES: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Keyframe<const N: usize> {
+    pub duration_ms: u32,
+    pub easing: Easing,
+    // Target angles. `None`: keep the previous one.
+    pub pose: [Option<f32>; N],
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sequence<const N: usize> {
+    pub keyframes: heapless::Vec<Keyframe<N>, MAX_KEYFRAMES>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    // Not `<duration> [easing]: <angles>`
+    Syntax,
+    // Not a number
+    Number,
+    // Wrong number of angles
+    Joints { expected: usize, got: usize },
+    // More than MAX_KEYFRAMES
+    TooLong,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Error {
+    // 1-based
+    pub line: usize,
+    pub kind: ErrorKind,
+}
+
+impl<const N: usize> Sequence<N> {
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let mut keyframes = heapless::Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let error = |kind| Error { line: i + 1, kind };
+
+            // Strip comments and blanks
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let keyframe = Self::parse_keyframe(line).map_err(error)?;
+            keyframes.push(keyframe).map_err(|_| error(ErrorKind::TooLong))?;
+        }
+        Ok(Self { keyframes })
+    }
+
+    fn parse_keyframe(line: &str) -> Result<Keyframe<N>, ErrorKind> {
+        // "1000 hold"
+        let mut words = line.split_whitespace();
+        if let (Some(duration), Some("hold"), None) = (words.next(), words.next(), words.next()) {
+            let duration_ms = duration.parse().map_err(|_| ErrorKind::Number)?;
+            return Ok(Keyframe { duration_ms, easing: Easing::Linear, pose: [None; N] });
+        }
+
+        // "500 linear: 90 60 _ 0"
+        let (head, angles) = line.split_once(':').ok_or(ErrorKind::Syntax)?;
+        let mut head = head.split_whitespace();
+        let duration_ms = head.next().ok_or(ErrorKind::Syntax)?
+            .parse().map_err(|_| ErrorKind::Number)?;
+        let easing = match head.next() {
+            None | Some("ease") => Easing::EaseInOut,
+            Some("linear") => Easing::Linear,
+            Some(_) => return Err(ErrorKind::Syntax),
+        };
+        if head.next().is_some() {
+            return Err(ErrorKind::Syntax);
+        }
+
+        let mut pose = [None; N];
+        let mut got = 0;
+        for word in angles.split_whitespace() {
+            if got < N {
+                pose[got] = match word {
+                    "_" => None,
+                    _ => Some(word.parse().map_err(|_| ErrorKind::Number)?),
+                };
+            }
+            got += 1;
+        }
+        if got != N {
+            return Err(ErrorKind::Joints { expected: N, got });
+        }
+        Ok(Keyframe { duration_ms, easing, pose })
+    }
+}
+
+
+// Plays a sequence: an iterator of poses, one per tick.
+pub struct Player<'a, const N: usize> {
+    sequence: &'a Sequence<N>,
+    // Max speed of every servo, °/s. 0 = no limit.
+    max_speed: [f32; N],
+    tick_ms: u32,
+
+    // Current keyframe
+    index: usize,
+    elapsed_ms: u32,
+    // Where the current keyframe started, and where it goes
+    from: [f32; N],
+    to: [f32; N],
+    duration_ms: u32,
+}
+
+impl<'a, const N: usize> Player<'a, N> {
+    // `start`: where the servos are now
+    pub fn new(sequence: &'a Sequence<N>, start: [f32; N], max_speed: [f32; N], tick_ms: u32) -> Self {
+        let mut player = Self {
+            sequence, max_speed, tick_ms: tick_ms.max(1),
+            index: 0, elapsed_ms: 0,
+            from: start, to: start, duration_ms: 0,
+        };
+        player.enter(0);
+        player
+    }
+
+    // Total play time, ms. Includes the slow-downs due to the speed limits.
+    pub fn duration_ms(sequence: &Sequence<N>, start: [f32; N], max_speed: [f32; N]) -> u32 {
+        let mut player = Player::new(sequence, start, max_speed, 1);
+        let mut total = 0;
+        while player.index < sequence.keyframes.len() {
+            total += player.duration_ms;
+            player.enter(player.index + 1);
+        }
+        total
+    }
+
+    // Start keyframe #index: compute the target and the synchronized duration
+    fn enter(&mut self, index: usize) {
+        self.from = self.to;
+        self.index = index;
+        self.elapsed_ms = 0;
+        let Some(keyframe) = self.sequence.keyframes.get(index) else {
+            return;
+        };
+
+        // The slowest servo sets the pace
+        self.duration_ms = keyframe.duration_ms;
+        for i in 0..N {
+            self.to[i] = keyframe.pose[i].unwrap_or(self.from[i]);
+            let fastest = Motion::new(self.from[i], self.to[i], self.max_speed[i], keyframe.easing);
+            self.duration_ms = self.duration_ms.max(fastest.duration_ms);
+        }
+    }
+}
+
+impl<const N: usize> Iterator for Player<'_, N> {
+    type Item = [f32; N];
+
+    fn next(&mut self) -> Option<[f32; N]> {
+        // Skip to the keyframe that's in progress
+        while self.elapsed_ms >= self.duration_ms && self.elapsed_ms > 0
+            && self.index < self.sequence.keyframes.len() {
+            let overshoot = self.elapsed_ms - self.duration_ms;
+            self.enter(self.index + 1);
+            self.elapsed_ms = overshoot;
+        }
+        let keyframe = self.sequence.keyframes.get(self.index)?;
+
+        self.elapsed_ms += self.tick_ms;
+        let elapsed = self.elapsed_ms.min(self.duration_ms);
+        Some(core::array::from_fn(|i| {
+            Motion::timed(self.from[i], self.to[i], self.duration_ms, keyframe.easing).angle_at(elapsed)
+        }))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WAVE: &str = "
+        # Wave hello
+        500: 90 60 120 0
+        300 linear: _ _ 150 _   # elbow only
+        1000 hold
+    ";
+
+    #[test]
+    fn test_parse() {
+        let seq = Sequence::<4>::parse(WAVE).unwrap();
+        assert_eq!(seq.keyframes.len(), 3);
+        assert_eq!(seq.keyframes[0], Keyframe {
+            duration_ms: 500, easing: Easing::EaseInOut,
+            pose: [Some(90.0), Some(60.0), Some(120.0), Some(0.0)],
+        });
+        assert_eq!(seq.keyframes[1].easing, Easing::Linear);
+        assert_eq!(seq.keyframes[1].pose, [None, None, Some(150.0), None]);
+        assert_eq!(seq.keyframes[2].pose, [None; 4]);
+        assert_eq!(seq.keyframes[2].duration_ms, 1000);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = |text: &str| Sequence::<2>::parse(text).unwrap_err();
+        assert_eq!(err("100: 1 2\n\n100: 1 2 3"), Error { line: 3, kind: ErrorKind::Joints { expected: 2, got: 3 } });
+        assert_eq!(err("100: 1"), Error { line: 1, kind: ErrorKind::Joints { expected: 2, got: 1 } });
+        assert_eq!(err("100 fast: 1 2"), Error { line: 1, kind: ErrorKind::Syntax });
+        assert_eq!(err("100 1 2"), Error { line: 1, kind: ErrorKind::Syntax });
+        assert_eq!(err("1s: 1 2"), Error { line: 1, kind: ErrorKind::Number });
+        assert_eq!(err("100: 1 x"), Error { line: 1, kind: ErrorKind::Number });
+
+        let long = "100: 1 2\n".repeat(MAX_KEYFRAMES + 1);
+        assert_eq!(err(&long), Error { line: MAX_KEYFRAMES + 1, kind: ErrorKind::TooLong });
+    }
+
+    #[test]
+    fn test_play() {
+        let seq = Sequence::<2>::parse("100 linear: 10 100\n100 hold\n40 linear: 0 _").unwrap();
+        let frames: Vec<_> = Player::new(&seq, [0.0, 0.0], [0.0, 0.0], 20).collect();
+
+        // 100ms + 100ms + 40ms, 20ms ticks
+        assert_eq!(frames.len(), 5 + 5 + 2);
+        assert_eq!(frames[0], [2.0, 20.0]);
+        assert_eq!(frames[4], [10.0, 100.0]);
+        assert_eq!(frames[9], [10.0, 100.0]); // hold
+        assert_eq!(frames[10], [5.0, 100.0]);
+        assert_eq!(frames[11], [0.0, 100.0]);
+    }
+
+    #[test]
+    fn test_synchronized() {
+        // Joint 1 moves 90° in 100ms, but can only do 180°/s: the keyframe takes 500ms.
+        // Joint 0 slows down too: both arrive together.
+        let seq = Sequence::<2>::parse("100 linear: 10 90").unwrap();
+        let max_speed = [1000.0, 180.0];
+        assert_eq!(Player::duration_ms(&seq, [0.0, 0.0], max_speed), 500);
+
+        let frames: Vec<_> = Player::new(&seq, [0.0, 0.0], max_speed, 50).collect();
+        assert_eq!(frames.len(), 10);
+        assert_eq!(frames[4], [5.0, 45.0]); // halfway, both
+        assert_eq!(frames[9], [10.0, 90.0]);
+
+        // The speed limit holds on every tick
+        let mut prev = [0.0, 0.0];
+        for frame in &frames {
+            for i in 0..2 {
+                let speed = (frame[i] - prev[i]).abs() / 0.050;
+                assert!(speed <= max_speed[i] + 0.1, "joint {i}: {speed}°/s");
+            }
+            prev = *frame;
+        }
+    }
+
+    #[test]
+    fn test_empty() {
+        let seq = Sequence::<3>::parse("# nothing").unwrap();
+        assert_eq!(Player::new(&seq, [0.0; 3], [0.0; 3], 20).next(), None);
+        // Zero-length keyframe: a single frame, right at the target
+        let seq = Sequence::<1>::parse("0: 45").unwrap();
+        let frames: Vec<_> = Player::new(&seq, [0.0], [0.0], 20).collect();
+        assert_eq!(frames, vec![[45.0]]);
+    }
+}
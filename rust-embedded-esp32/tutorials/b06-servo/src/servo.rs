@@ -0,0 +1,232 @@
+// Servo: angle -> pulse width -> PWM duty cycle.
+//
+// A hobby servo wants a pulse every 20ms (50Hz). The pulse width sets the angle:
+// typically 0.5ms = 0°, 2.5ms = 180°. Every servo is a bit different, so calibrate:
+// find the pulse widths where it stops turning at both ends.
+//
+// Works with anything that implements `embedded_hal::pwm::SetDutyCycle`: e.g. an LEDC channel.
+//
+// Usage:
+//   let mut servo = Servo::new(pwm_channel, Config::default());
+//   servo.move_to(90.0, Easing::EaseInOut, now_ms);
+//   while servo.tick(now_ms)? { /* sleep 20ms */ }
+
+use embedded_hal::pwm::SetDutyCycle;
+use crate::motion::{Easing, Motion};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Config {
+    // Pulse width at 0°, and at `range_deg`.
+    // Swap them to mirror the servo: `max_pulse_us < min_pulse_us` is fine.
+    pub min_pulse_us: u32,
+    pub max_pulse_us: u32,
+    // Angle range: 180° for most servos. Zero (or less): it stays at 0°.
+    pub range_deg: f32,
+    // PWM period: 20ms at 50Hz
+    pub period_us: u32,
+    // Max angular speed, °/s. 0 = no limit.
+    pub max_speed: f32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self { min_pulse_us: 500, max_pulse_us: 2500, range_deg: 180.0, period_us: 20_000, max_speed: 300.0 }
+    }
+}
+
+impl Config {
+    // Angle -> pulse width. Out of range angles are clamped.
+    // NOTE: f32 math: the pulse range may go backwards
+    pub fn pulse_us(&self, angle: f32) -> f32 {
+        let k = if self.range_deg > 0.0 { (angle / self.range_deg).clamp(0.0, 1.0) } else { 0.0 };
+        let (min, max) = (self.min_pulse_us as f32, self.max_pulse_us as f32);
+        min + (max - min) * k
+    }
+
+    // Clamp the angle to 0..range_deg
+    pub fn clamp(&self, angle: f32) -> f32 {
+        // `max()`: a negative or NaN range becomes 0
+        angle.clamp(0.0, self.range_deg.max(0.0))
+    }
+
+    // Angle -> duty cycle, given the PWM's max duty cycle
+    pub fn duty(&self, angle: f32, max_duty: u16) -> u16 {
+        (max_duty as f32 * self.pulse_us(angle) / self.period_us as f32) as u16
+    }
+}
+
+pub struct Servo<P: SetDutyCycle> {
+    pwm: P,
+    config: Config,
+    // Where we've told it to be. `None`: unknown, until the first move.
+    angle: Option<f32>,
+    // Current motion, and when it started
+    motion: Option<(Motion, u64)>,
+}
+
+impl<P: SetDutyCycle> Servo<P> {
+    pub fn new(pwm: P, config: Config) -> Self {
+        Self { pwm, config, angle: None, motion: None }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn angle(&self) -> Option<f32> {
+        self.angle
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.motion.is_some()
+    }
+
+    // Go to the angle immediately, at full speed. Cancels the current motion.
+    pub fn set_angle(&mut self, angle: f32) -> Result<(), P::Error> {
+        self.motion = None;
+        self.write(angle)
+    }
+
+    // Start moving towards the angle, within the speed limit. Call tick() to actually move.
+    // The very first move is instant: we don't know where the servo is.
+    pub fn move_to(&mut self, angle: f32, easing: Easing, now_ms: u64) {
+        let target = self.config.clamp(angle);
+        let from = self.angle.unwrap_or(target);
+        self.start(Motion::new(from, target, self.config.max_speed, easing), now_ms);
+    }
+
+    // Start a motion. Starts from wherever it says, not from the current angle.
+    pub fn start(&mut self, motion: Motion, now_ms: u64) {
+        self.motion = Some((motion, now_ms));
+    }
+
+    // Move along: set the angle for this moment. Returns `true` while still moving.
+    pub fn tick(&mut self, now_ms: u64) -> Result<bool, P::Error> {
+        let Some((motion, started)) = self.motion else {
+            return Ok(false);
+        };
+        let elapsed = now_ms.saturating_sub(started).min(u32::MAX as u64) as u32;
+        self.write(motion.angle_at(elapsed))?;
+        if motion.is_done(elapsed) {
+            self.motion = None;
+        }
+        Ok(self.motion.is_some())
+    }
+
+    // Stop, and release the PWM channel
+    pub fn release(self) -> P {
+        self.pwm
+    }
+
+    fn write(&mut self, angle: f32) -> Result<(), P::Error> {
+        let angle = self.config.clamp(angle);
+        let duty = self.config.duty(angle, self.pwm.max_duty_cycle());
+        self.pwm.set_duty_cycle(duty)?;
+        self.angle = Some(angle);
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::convert::Infallible;
+
+    // Remembers every duty cycle
+    struct FakePwm(Vec<u16>);
+
+    impl embedded_hal::pwm::ErrorType for FakePwm {
+        type Error = Infallible;
+    }
+    impl SetDutyCycle for FakePwm {
+        fn max_duty_cycle(&self) -> u16 {
+            4095 // 12 bit
+        }
+        fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Infallible> {
+            self.0.push(duty);
+            Ok(())
+        }
+    }
+
+    // The tutorial's servo: 600..2575us
+    const CONFIG: Config = Config { min_pulse_us: 600, max_pulse_us: 2575, range_deg: 180.0, period_us: 20_000, max_speed: 180.0 };
+
+    #[test]
+    fn test_calibration() {
+        assert_eq!(CONFIG.pulse_us(0.0), 600.0);
+        assert_eq!(CONFIG.pulse_us(180.0), 2575.0);
+        assert_eq!(CONFIG.pulse_us(90.0), 1587.5);
+        // Fractional
+        assert!(CONFIG.pulse_us(45.5) > CONFIG.pulse_us(45.0));
+        // Clamped
+        assert_eq!(CONFIG.pulse_us(-10.0), 600.0);
+        assert_eq!(CONFIG.pulse_us(200.0), 2575.0);
+
+        // 600us of 20ms = 3% of 4095
+        assert_eq!(CONFIG.duty(0.0, 4095), 122);
+        assert_eq!(CONFIG.duty(180.0, 4095), 527);
+    }
+
+    #[test]
+    fn test_mirrored() {
+        // Swapped pulse widths: turns the other way
+        let mirrored = Config { min_pulse_us: 2575, max_pulse_us: 600, ..CONFIG };
+        assert_eq!(mirrored.pulse_us(0.0), 2575.0);
+        assert_eq!(mirrored.pulse_us(180.0), 600.0);
+        assert_eq!(mirrored.pulse_us(90.0), 1587.5);
+        assert_eq!(mirrored.duty(0.0, 4095), CONFIG.duty(180.0, 4095));
+    }
+
+    #[test]
+    fn test_zero_range() {
+        // No range: always at 0°, no NaN
+        for range_deg in [0.0, -90.0, f32::NAN] {
+            let config = Config { range_deg, ..CONFIG };
+            assert_eq!(config.pulse_us(90.0), 600.0);
+            assert_eq!(config.clamp(90.0), 0.0);
+        }
+
+        let mut servo = Servo::new(FakePwm(vec![]), Config { range_deg: 0.0, ..CONFIG });
+        servo.move_to(90.0, Easing::Linear, 0);
+        assert_eq!(servo.tick(0), Ok(false));
+        assert_eq!(servo.angle(), Some(0.0));
+    }
+
+    #[test]
+    fn test_servo() {
+        let mut servo = Servo::new(FakePwm(vec![]), CONFIG);
+        assert_eq!(servo.angle(), None);
+
+        // First move: instant
+        servo.move_to(0.0, Easing::Linear, 0);
+        assert_eq!(servo.tick(0), Ok(false));
+        assert_eq!(servo.angle(), Some(0.0));
+
+        // 90° at 180°/s: 500ms, in 20ms ticks
+        servo.move_to(90.0, Easing::Linear, 1000);
+        let mut now = 1000;
+        while servo.tick(now).unwrap() {
+            now += 20;
+        }
+        assert_eq!(now, 1500);
+        assert_eq!(servo.angle(), Some(90.0));
+
+        let duties = servo.release().0;
+        assert_eq!(duties.len(), 1 + 26);
+        assert!(duties.windows(2).all(|w| w[0] <= w[1]), "{duties:?}");
+        assert_eq!(duties.last(), Some(&CONFIG.duty(90.0, 4095)));
+    }
+
+    #[test]
+    fn test_set_angle_cancels() {
+        let mut servo = Servo::new(FakePwm(vec![]), CONFIG);
+        servo.set_angle(0.0).unwrap();
+        servo.move_to(180.0, Easing::EaseInOut, 0);
+        assert!(servo.is_moving());
+        servo.set_angle(45.0).unwrap();
+        assert!(!servo.is_moving());
+        assert_eq!(servo.tick(100), Ok(false));
+        assert_eq!(servo.angle(), Some(45.0));
+    }
+}
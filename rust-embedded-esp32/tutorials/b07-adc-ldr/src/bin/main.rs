@@ -7,14 +7,24 @@ esp_bootloader_esp_idf::esp_app_desc!();
 
 use log;
 use esp_hal::{
-    time::{Instant, Duration},
+    time::Instant,
     clock::CpuClock,
     gpio, analog::adc,
     delay::Delay,
+    peripherals::ADC1,
     main,
 };
 use nb;
 
+// ADC counts -> millivolts: ../../libs/adc-cal
+use adc_cal::{Calibration, esp::efuse_line};
+
+// Millivolts -> lux -> day/night
+use b07_adc_ldr::{
+    daylight::{self, Classifier, Daylight},
+    ldr::Ldr,
+};
+
 #[main]
 fn main() -> ! {
     esp_println::logger::init_logger_from_env();
@@ -27,11 +37,21 @@ fn main() -> ! {
 
     // ADC Input
     // Attenuation: -11dB
+    // `AdcCalBasic`: the ADC removes its own offset (eFuse), so that 0V reads as 0.
     let ldr_pin = peripherals.GPIO0;
     let mut adc_config = adc::AdcConfig::new();
-    let mut pin = adc_config.enable_pin(ldr_pin, adc::Attenuation::_11dB);
+    let mut pin = adc_config.enable_pin_with_cal::<_, adc::AdcCalBasic<ADC1>>(ldr_pin, ATTENUATION);
     let mut adc1 = adc::Adc::new(peripherals.ADC1, adc_config);
 
+    // Counts -> millivolts: use the factory calibration
+    let attenuation = ATTENUATION.into();
+    let calibration = Calibration::nominal()
+        .with(attenuation, efuse_line(ATTENUATION));
+
+    // Day/night. Short dwell time: for the demo. Real life: minutes.
+    let ldr = Ldr::GL5528;
+    let mut daylight = Classifier::new(daylight::Config { min_dwell_ms: 3_000, ..Default::default() });
+
     let delay = Delay::new();
     loop {
         // Read
@@ -44,22 +64,36 @@ fn main() -> ! {
 
         // Instead, we can use nb::block!() to wait until the value becomes available.
         // Internally, it loop{}s until WouldBlock goes away and a value becomes available.
-        let luminosity: u16 = match nb::block!(adc1.read_oneshot(&mut pin)) {
-            Ok(result) => {
-                (result.max(1900)-1900)/20
-            }
+        // Oversample: read 16 times and average. Less noise.
+        let samples = match adc_cal::oversample(16, || nb::block!(adc1.read_oneshot(&mut pin))) {
+            Ok(samples) => samples,
             Err(err) => {
                 log::error!("err={err:?}");
                 continue;
             }
         };
-        log::info!("luminosity={luminosity}");
+        let reading = calibration.read(attenuation, &samples);
+        log::info!("voltage={:.0}±{:.0}mV{}", reading.mv, reading.uncertainty_mv,
+            if reading.out_of_range { " (out of range)" } else { "" });
 
-        // Blink LED
-        let now = Instant::now();
-        while now.elapsed() < Duration::from_millis(300) {
-            onboard_led.toggle();
-            delay.delay_millis(luminosity as u32);
+        let lux = ldr.lux(reading.mv);
+        log::info!("lux={lux:.1}");
+
+        // Day/night changed: drive the outputs
+        let now_ms = Instant::now().duration_since_epoch().as_millis();
+        if let Some(state) = daylight.update(lux, now_ms) {
+            log::info!("daylight={state:?}");
+        }
+
+        // LED: a night light. Blinks at dusk, on at night, off during the day.
+        match daylight.state() {
+            Some(Daylight::Night) => onboard_led.set_high(),
+            Some(Daylight::Dusk) => onboard_led.toggle(),
+            _ => onboard_led.set_low(),
         }
+        delay.delay_millis(300);
     }
 }
+
+
+const ATTENUATION: adc::Attenuation = adc::Attenuation::_11dB;
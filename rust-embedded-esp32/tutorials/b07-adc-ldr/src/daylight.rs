@@ -0,0 +1,170 @@
+// Day / dusk / night classifier.
+//
+// A naive `if lux < 10 { night }` flickers: at dusk, the light hovers right around 10 lux,
+// and a passing car or a cloud flips it back and forth. Two things fix that:
+// * Hysteresis: to leave the current state, the light has to cross the threshold *by a margin*.
+//   Lux is logarithmic to the eye, so the margin is a ratio: ±25% of the threshold.
+// * Dwell time: the new state must hold for a while before we believe it.
+//
+// `update()` returns the new state when it changes: use it to drive outputs.
+// Time is passed in (milliseconds): no clock dependencies, easy to test.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Daylight {
+    Night,
+    Dusk,
+    Day,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Config {
+    // Below this: night. Civil twilight ends at ~3 lux; a street light gives ~10.
+    pub night_lux: f32,
+    // Above this: day. Overcast daylight is ~1000 lux, sunrise ~400.
+    pub day_lux: f32,
+    // Hysteresis margin, a ratio: 0.25 = ±25% around the thresholds
+    pub hysteresis: f32,
+    // The new state must hold this long before we switch
+    pub min_dwell_ms: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self { night_lux: 10.0, day_lux: 400.0, hysteresis: 0.25, min_dwell_ms: 30_000 }
+    }
+}
+
+pub struct Classifier {
+    config: Config,
+    state: Option<Daylight>,
+    // A different state we're seeing, and since when
+    candidate: Option<(Daylight, u64)>,
+}
+
+impl Classifier {
+    pub const fn new(config: Config) -> Self {
+        Self { config, state: None, candidate: None }
+    }
+
+    pub fn state(&self) -> Option<Daylight> {
+        self.state
+    }
+
+    // Feed a reading. Returns the new state when it changes.
+    // The very first reading sets the state right away: nothing to debounce against.
+    pub fn update(&mut self, lux: f32, now_ms: u64) -> Option<Daylight> {
+        let Some(state) = self.state else {
+            let initial = self.classify(lux, None);
+            self.state = Some(initial);
+            return Some(initial);
+        };
+
+        let seen = self.classify(lux, Some(state));
+        if seen == state {
+            // False alarm: back to normal
+            self.candidate = None;
+            return None;
+        }
+
+        match self.candidate {
+            // Still seeing the same new state: long enough?
+            Some((candidate, since)) if candidate == seen => {
+                if now_ms.saturating_sub(since) >= self.config.min_dwell_ms {
+                    self.state = Some(seen);
+                    self.candidate = None;
+                    return Some(seen);
+                }
+            }
+            // Something new: start the clock
+            _ => self.candidate = Some((seen, now_ms)),
+        }
+        None
+    }
+
+    // Which state this lux level belongs to.
+    // Each threshold is moved away from the current state: harder to leave it.
+    fn classify(&self, lux: f32, current: Option<Daylight>) -> Daylight {
+        let h = self.config.hysteresis;
+        let threshold = |limit: f32, above: Daylight| match current {
+            Some(current) if current >= above => limit * (1.0 - h),
+            Some(_) => limit * (1.0 + h),
+            None => limit,
+        };
+
+        if lux >= threshold(self.config.day_lux, Daylight::Day) {
+            Daylight::Day
+        } else if lux >= threshold(self.config.night_lux, Daylight::Dusk) {
+            Daylight::Dusk
+        } else {
+            Daylight::Night
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: Config = Config { night_lux: 10.0, day_lux: 400.0, hysteresis: 0.25, min_dwell_ms: 1000 };
+
+    // Feed (lux, time) readings, collect the changes
+    fn run(c: &mut Classifier, readings: &[(f32, u64)]) -> Vec<(u64, Daylight)> {
+        readings.iter()
+            .filter_map(|&(lux, t)| c.update(lux, t).map(|s| (t, s)))
+            .collect()
+    }
+
+    #[test]
+    fn test_initial() {
+        assert_eq!(Classifier::new(CONFIG).update(1.0, 0), Some(Daylight::Night));
+        assert_eq!(Classifier::new(CONFIG).update(10.0, 0), Some(Daylight::Dusk));
+        assert_eq!(Classifier::new(CONFIG).update(400.0, 0), Some(Daylight::Day));
+        assert_eq!(Classifier::new(CONFIG).update(f32::INFINITY, 0), Some(Daylight::Day));
+    }
+
+    #[test]
+    fn test_dwell() {
+        let mut c = Classifier::new(CONFIG);
+        let changes = run(&mut c, &[
+            (1000.0, 0),
+            // Cloud: dark for 500ms only. Ignored.
+            (50.0, 1000), (50.0, 1500), (1000.0, 2000),
+            // Sunset: dusk for good
+            (50.0, 3000), (50.0, 3500), (50.0, 4000), (50.0, 5000),
+        ]);
+        assert_eq!(changes, vec![(0, Daylight::Day), (4000, Daylight::Dusk)]);
+        assert_eq!(c.state(), Some(Daylight::Dusk));
+    }
+
+    #[test]
+    fn test_hysteresis() {
+        let mut c = Classifier::new(CONFIG);
+        c.update(20.0, 0);
+        assert_eq!(c.state(), Some(Daylight::Dusk));
+
+        // Hovering around 10 lux: 9 lux isn't dark enough to leave Dusk (needs < 7.5)
+        let changes = run(&mut c, &[(9.0, 1000), (11.0, 1500), (9.0, 2000), (9.0, 3000), (9.0, 5000)]);
+        assert_eq!(changes, vec![]);
+
+        // Properly dark
+        let changes = run(&mut c, &[(7.0, 6000), (7.0, 7000)]);
+        assert_eq!(changes, vec![(7000, Daylight::Night)]);
+
+        // Now 11 lux isn't enough to leave Night (needs > 12.5)
+        let changes = run(&mut c, &[(11.0, 8000), (11.0, 10_000), (13.0, 11_000), (13.0, 12_000)]);
+        assert_eq!(changes, vec![(12_000, Daylight::Dusk)]);
+    }
+
+    #[test]
+    fn test_jump() {
+        // Lights on at night: straight to Day, skipping Dusk
+        let mut c = Classifier::new(CONFIG);
+        let changes = run(&mut c, &[(1.0, 0), (5000.0, 100), (5000.0, 1100)]);
+        assert_eq!(changes, vec![(0, Daylight::Night), (1100, Daylight::Day)]);
+
+        // Flip-flopping between two new states: the clock restarts
+        let changes = run(&mut c, &[(1.0, 2000), (50.0, 2500), (1.0, 3000), (1.0, 3900), (1.0, 4000)]);
+        assert_eq!(changes, vec![(4000, Daylight::Night)]);
+    }
+}
@@ -0,0 +1,86 @@
+// LDR: voltage -> resistance -> lux (approximately).
+//
+// Our circuit: the LDR is on the low side of the divider.
+//                         ┌──────────── GPIO0
+//   3.3V output ───[ 10k ]─┴──[ LDR ]─── GND
+//   $R_{LDR} = R_1 * V / (V_{DD} - V)$
+//
+// LDR datasheets give two numbers:
+// * R10: resistance at 10 lux
+// * γ (gamma): the slope on the log-log plot: $log(R) = log(R10) - γ * log(lux / 10)$
+// So: $lux = 10 * (R10 / R)^{1/γ}$
+//
+// It's a rough estimate: LDRs vary a lot from piece to piece, and respond differently to different colors.
+// Good enough to tell day from night.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ldr {
+    // Resistance at 10 lux, Ohm
+    pub r10: f32,
+    pub gamma: f32,
+    // The fixed resistor in the divider, Ohm
+    pub r_fixed: f32,
+    // Divider supply voltage, mV
+    pub vdd_mv: f32,
+}
+
+impl Ldr {
+    // GL5528, the most common one. Datasheet: R10 = 8..20k, γ = 0.7
+    pub const GL5528: Self = Self { r10: 15_000.0, gamma: 0.7, r_fixed: 10_000.0, vdd_mv: 3300.0 };
+    // Wokwi's simulated photoresistor: RL10 = 50k, γ = 0.7
+    pub const WOKWI: Self = Self { r10: 50_000.0, gamma: 0.7, ..Self::GL5528 };
+
+    // LDR resistance, Ohm. At V_DD: infinite (pitch black, or disconnected)
+    pub fn resistance(&self, mv: f32) -> f32 {
+        if mv >= self.vdd_mv {
+            return f32::INFINITY;
+        }
+        self.r_fixed * mv.max(0.0) / (self.vdd_mv - mv)
+    }
+
+    // Approximate illuminance, lux
+    pub fn lux(&self, mv: f32) -> f32 {
+        self.lux_from_resistance(self.resistance(mv))
+    }
+
+    pub fn lux_from_resistance(&self, r: f32) -> f32 {
+        // R=0 gives infinity, R=∞ gives 0: fine
+        10.0 * libm::powf(self.r10 / r, 1.0 / self.gamma)
+    }
+
+    // The inverse: what voltage to expect at the given lux. Handy for tests and thresholds.
+    pub fn mv_at(&self, lux: f32) -> f32 {
+        let r = self.r10 * libm::powf(lux / 10.0, -self.gamma);
+        self.vdd_mv * r / (self.r_fixed + r)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lux() {
+        let ldr = Ldr::GL5528;
+
+        // At R10, it's 10 lux
+        let mv = ldr.mv_at(10.0);
+        assert!((ldr.resistance(mv) - 15_000.0).abs() < 1.0);
+        assert!((ldr.lux(mv) - 10.0).abs() < 0.01);
+
+        // Brighter: lower resistance, lower voltage
+        assert!(ldr.mv_at(1000.0) < ldr.mv_at(10.0));
+        // 100x brighter: R drops by 100^0.7 = 25x
+        assert!((ldr.resistance(ldr.mv_at(1000.0)) - 15_000.0 / 25.12).abs() < 5.0);
+        for lux in [0.1, 1.0, 50.0, 1000.0, 30_000.0] {
+            let back = ldr.lux(ldr.mv_at(lux));
+            assert!((back - lux).abs() / lux < 1e-3, "{lux} -> {back}");
+        }
+
+        // Rails
+        assert_eq!(ldr.lux(3300.0), 0.0);
+        assert_eq!(ldr.lux(3500.0), 0.0);
+        assert_eq!(ldr.lux(0.0), f32::INFINITY);
+    }
+}
@@ -53,7 +53,8 @@ async fn main(spawner: Spawner) -> ! {
     esp_rtos::start(timg0.timer0, sw_int.software_interrupt0);
 
     // Init WiFi & network stack
-    let stack = lib::wifi::start_wifi(&spawner, peripherals.WIFI).await;
+    // No Wi-Fi settings yet? Starts the setup AP. GPIO9: the BOOT button, hold it to start over.
+    let stack = lib::wifi::start_wifi(&spawner, peripherals.WIFI, peripherals.FLASH, peripherals.GPIO9).await.unwrap();
     let rng = Rng::new();
     let tls_seed = rng.random() as u64 | ((rng.random() as u64) << 32);
 
@@ -0,0 +1,298 @@
+// A tiny DHCP server: hands out addresses to the phones that join the setup AP.
+//
+//   phone: DISCOVER (broadcast)   ->  we: OFFER 192.168.4.2
+//   phone: REQUEST 192.168.4.2    ->  we: ACK: it's yours. Router and DNS: us.
+//
+// One address per MAC, from a small pool; leases never expire: the AP only lives until the setup is done.
+// Replies are broadcast: the phone has no address yet.
+// Docs: RFC 2131; options: RFC 2132
+
+use core::net::Ipv4Addr;
+
+pub const SERVER_PORT: u16 = 67;
+pub const CLIENT_PORT: u16 = 68;
+
+// Fixed part: op, htype, hlen, hops, xid, secs, flags, ciaddr, yiaddr, siaddr, giaddr, chaddr, sname, file
+const FIXED_LEN: usize = 236;
+const MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
+const OPTIONS_START: usize = FIXED_LEN + 4;
+// BOOTP: some clients drop shorter replies
+pub const MIN_REPLY_LEN: usize = 300;
+
+const BOOTREQUEST: u8 = 1;
+const BOOTREPLY: u8 = 2;
+
+// Option codes
+const OPT_PAD: u8 = 0;
+const OPT_SUBNET_MASK: u8 = 1;
+const OPT_ROUTER: u8 = 3;
+const OPT_DNS: u8 = 6;
+const OPT_REQUESTED_IP: u8 = 50;
+const OPT_LEASE_TIME: u8 = 51;
+const OPT_MESSAGE_TYPE: u8 = 53;
+const OPT_SERVER_ID: u8 = 54;
+const OPT_END: u8 = 255;
+
+// Message types
+const DISCOVER: u8 = 1;
+const OFFER: u8 = 2;
+const REQUEST: u8 = 3;
+const ACK: u8 = 5;
+const NAK: u8 = 6;
+const RELEASE: u8 = 7;
+
+// Seconds. Clients renew at half of it: not that we care.
+const LEASE_SECS: u32 = 3600;
+
+// `N` addresses, starting at `pool_start`
+pub struct Server<const N: usize> {
+    ip: Ipv4Addr,
+    pool_start: Ipv4Addr,
+    // MAC of the client that has the address
+    leases: [Option<[u8; 6]>; N],
+}
+
+impl<const N: usize> Server<N> {
+    // `ip`: ours, the subnet is /24. `pool_start`: in the same subnet.
+    pub const fn new(ip: Ipv4Addr, pool_start: Ipv4Addr) -> Self {
+        Self { ip, pool_start, leases: [None; N] }
+    }
+
+    // Handle a request: write the reply into `out`, return its length.
+    // `None`: nothing to reply: not for us, garbage, or the pool is full.
+    pub fn handle(&mut self, request: &[u8], out: &mut [u8]) -> Option<usize> {
+        if request.len() < OPTIONS_START || request[0] != BOOTREQUEST || request[FIXED_LEN..OPTIONS_START] != MAGIC_COOKIE {
+            return None;
+        }
+        // Ethernet MACs only
+        let (htype, hlen) = (request[1], request[2]);
+        if htype != 1 || hlen != 6 {
+            return None;
+        }
+        let mac: [u8; 6] = request[28..34].try_into().unwrap();
+        let options = &request[OPTIONS_START..];
+
+        match option(options, OPT_MESSAGE_TYPE)? {
+            [DISCOVER] => {
+                let ip = self.lease(mac)?;
+                self.reply(request, OFFER, ip, out)
+            },
+            [REQUEST] => {
+                // Talking to another server? Not our business.
+                if option(options, OPT_SERVER_ID).is_some_and(|id| id != self.ip.octets()) {
+                    return None;
+                }
+                // SELECTING / INIT-REBOOT: requested IP option. RENEWING: ciaddr.
+                let requested = match option(options, OPT_REQUESTED_IP) {
+                    Some(&[a, b, c, d]) => Ipv4Addr::new(a, b, c, d),
+                    _ => Ipv4Addr::new(request[12], request[13], request[14], request[15]),
+                };
+                match self.lease(mac) {
+                    Some(ip) if ip == requested => self.reply(request, ACK, ip, out),
+                    // Wrong address: the client starts over, with a DISCOVER
+                    _ => self.reply(request, NAK, Ipv4Addr::UNSPECIFIED, out),
+                }
+            },
+            [RELEASE] => {
+                self.leases.iter_mut().filter(|lease| **lease == Some(mac)).for_each(|lease| *lease = None);
+                None
+            },
+            // DECLINE, INFORM: ignore
+            _ => None,
+        }
+    }
+
+    // The address of this MAC: the same one as before, or a free one
+    fn lease(&mut self, mac: [u8; 6]) -> Option<Ipv4Addr> {
+        let i = match self.leases.iter().position(|lease| *lease == Some(mac)) {
+            Some(i) => i,
+            None => {
+                let i = self.leases.iter().position(Option::is_none)?;
+                self.leases[i] = Some(mac);
+                i
+            },
+        };
+        Some(Ipv4Addr::from(u32::from(self.pool_start) + i as u32))
+    }
+
+    fn reply(&self, request: &[u8], message_type: u8, ip: Ipv4Addr, out: &mut [u8]) -> Option<usize> {
+        let out = out.get_mut(..MIN_REPLY_LEN)?;
+        out.fill(0);
+        out[0] = BOOTREPLY;
+        // htype, hlen, hops; xid, secs, flags (the "broadcast" bit): as in the request
+        out[1..12].copy_from_slice(&request[1..12]);
+        out[16..20].copy_from_slice(&ip.octets()); // yiaddr: "your" address
+        out[20..24].copy_from_slice(&self.ip.octets()); // siaddr: the server
+        out[28..44].copy_from_slice(&request[28..44]); // chaddr: client MAC
+        out[FIXED_LEN..OPTIONS_START].copy_from_slice(&MAGIC_COOKIE);
+
+        let mut options = Options { buf: &mut out[OPTIONS_START..], pos: 0 };
+        options.put(OPT_MESSAGE_TYPE, &[message_type]);
+        options.put(OPT_SERVER_ID, &self.ip.octets());
+        if message_type != NAK {
+            options.put(OPT_LEASE_TIME, &LEASE_SECS.to_be_bytes());
+            options.put(OPT_SUBNET_MASK, &[255, 255, 255, 0]);
+            // We're the router, and the DNS: see dns.rs
+            options.put(OPT_ROUTER, &self.ip.octets());
+            options.put(OPT_DNS, &self.ip.octets());
+        }
+        options.buf[options.pos] = OPT_END;
+        Some(MIN_REPLY_LEN)
+    }
+}
+
+// Find an option: code, length, value
+fn option(mut options: &[u8], code: u8) -> Option<&[u8]> {
+    loop {
+        match *options.first()? {
+            OPT_END => return None,
+            OPT_PAD => options = &options[1..],
+            found => {
+                let len = *options.get(1)? as usize;
+                let value = options.get(2..2 + len)?;
+                if found == code {
+                    return Some(value);
+                }
+                options = &options[2 + len..];
+            },
+        }
+    }
+}
+
+struct Options<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl Options<'_> {
+    fn put(&mut self, code: u8, value: &[u8]) {
+        self.buf[self.pos] = code;
+        self.buf[self.pos + 1] = value.len() as u8;
+        self.buf[self.pos + 2..self.pos + 2 + value.len()].copy_from_slice(value);
+        self.pos += 2 + value.len();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IP: Ipv4Addr = Ipv4Addr::new(192, 168, 4, 1);
+    const PHONE: [u8; 6] = [0xAA, 0xBB, 0xCC, 0, 0, 1];
+    const LAPTOP: [u8; 6] = [0xAA, 0xBB, 0xCC, 0, 0, 2];
+
+    fn request(mac: [u8; 6], options: &[(u8, &[u8])]) -> Vec<u8> {
+        let mut request = vec![0; FIXED_LEN];
+        request[..4].copy_from_slice(&[BOOTREQUEST, 1, 6, 0]);
+        request[4..8].copy_from_slice(&[0xDE, 0xAD, 0xBE, 0xEF]); // xid
+        request[10] = 0x80; // broadcast flag
+        request[28..34].copy_from_slice(&mac);
+        request.extend_from_slice(&MAGIC_COOKIE);
+        for (code, value) in options {
+            request.extend_from_slice(&[*code, value.len() as u8]);
+            request.extend_from_slice(value);
+        }
+        request.push(OPT_PAD);
+        request.push(OPT_END);
+        request
+    }
+
+    fn handle<const N: usize>(server: &mut Server<N>, request: &[u8]) -> Option<Vec<u8>> {
+        let mut out = [0; 512];
+        server.handle(request, &mut out).map(|len| out[..len].to_vec())
+    }
+
+    fn yiaddr(reply: &[u8]) -> Ipv4Addr {
+        Ipv4Addr::new(reply[16], reply[17], reply[18], reply[19])
+    }
+
+    #[test]
+    fn test_discover_request() {
+        let mut server = Server::<4>::new(IP, Ipv4Addr::new(192, 168, 4, 2));
+
+        let offer = handle(&mut server, &request(PHONE, &[(OPT_MESSAGE_TYPE, &[DISCOVER])])).unwrap();
+        assert_eq!(offer.len(), MIN_REPLY_LEN);
+        assert_eq!(offer[0], BOOTREPLY);
+        assert_eq!(&offer[4..8], [0xDE, 0xAD, 0xBE, 0xEF]);
+        assert_eq!(offer[10], 0x80);
+        assert_eq!(&offer[28..34], PHONE);
+        assert_eq!(yiaddr(&offer), Ipv4Addr::new(192, 168, 4, 2));
+        let options = &offer[OPTIONS_START..];
+        assert_eq!(option(options, OPT_MESSAGE_TYPE), Some(&[OFFER][..]));
+        assert_eq!(option(options, OPT_SERVER_ID), Some(&[192, 168, 4, 1][..]));
+        assert_eq!(option(options, OPT_SUBNET_MASK), Some(&[255, 255, 255, 0][..]));
+        assert_eq!(option(options, OPT_ROUTER), Some(&[192, 168, 4, 1][..]));
+        assert_eq!(option(options, OPT_DNS), Some(&[192, 168, 4, 1][..]));
+        assert_eq!(option(options, OPT_LEASE_TIME), Some(&3600u32.to_be_bytes()[..]));
+
+        // Take it
+        let ack = handle(&mut server, &request(PHONE, &[
+            (OPT_MESSAGE_TYPE, &[REQUEST]),
+            (OPT_REQUESTED_IP, &[192, 168, 4, 2]),
+            (OPT_SERVER_ID, &[192, 168, 4, 1]),
+        ])).unwrap();
+        assert_eq!(option(&ack[OPTIONS_START..], OPT_MESSAGE_TYPE), Some(&[ACK][..]));
+        assert_eq!(yiaddr(&ack), Ipv4Addr::new(192, 168, 4, 2));
+
+        // Another client: another address. The same client: the same address.
+        let offer = handle(&mut server, &request(LAPTOP, &[(OPT_MESSAGE_TYPE, &[DISCOVER])])).unwrap();
+        assert_eq!(yiaddr(&offer), Ipv4Addr::new(192, 168, 4, 3));
+        let offer = handle(&mut server, &request(PHONE, &[(OPT_MESSAGE_TYPE, &[DISCOVER])])).unwrap();
+        assert_eq!(yiaddr(&offer), Ipv4Addr::new(192, 168, 4, 2));
+    }
+
+    #[test]
+    fn test_nak() {
+        let mut server = Server::<4>::new(IP, Ipv4Addr::new(192, 168, 4, 2));
+        // Remembers an address from another network
+        let nak = handle(&mut server, &request(PHONE, &[
+            (OPT_MESSAGE_TYPE, &[REQUEST]),
+            (OPT_REQUESTED_IP, &[10, 0, 0, 5]),
+        ])).unwrap();
+        assert_eq!(option(&nak[OPTIONS_START..], OPT_MESSAGE_TYPE), Some(&[NAK][..]));
+        assert_eq!(option(&nak[OPTIONS_START..], OPT_ROUTER), None);
+        assert_eq!(yiaddr(&nak), Ipv4Addr::UNSPECIFIED);
+
+        // Talks to another server: ignore
+        let other = request(PHONE, &[(OPT_MESSAGE_TYPE, &[REQUEST]), (OPT_SERVER_ID, &[10, 0, 0, 1])]);
+        assert_eq!(handle(&mut server, &other), None);
+    }
+
+    #[test]
+    fn test_pool() {
+        let mut server = Server::<1>::new(IP, Ipv4Addr::new(192, 168, 4, 2));
+        let discover = |mac| request(mac, &[(OPT_MESSAGE_TYPE, &[DISCOVER])]);
+        assert!(handle(&mut server, &discover(PHONE)).is_some());
+        // Full
+        assert_eq!(handle(&mut server, &discover(LAPTOP)), None);
+
+        // Released: free again
+        assert_eq!(handle(&mut server, &request(PHONE, &[(OPT_MESSAGE_TYPE, &[RELEASE])])), None);
+        let offer = handle(&mut server, &discover(LAPTOP)).unwrap();
+        assert_eq!(yiaddr(&offer), Ipv4Addr::new(192, 168, 4, 2));
+    }
+
+    #[test]
+    fn test_garbage() {
+        let mut server = Server::<4>::new(IP, Ipv4Addr::new(192, 168, 4, 2));
+        let valid = request(PHONE, &[(OPT_MESSAGE_TYPE, &[DISCOVER])]);
+        assert_eq!(handle(&mut server, &valid[..100]), None);
+
+        let mut reply = valid.clone();
+        reply[0] = BOOTREPLY;
+        assert_eq!(handle(&mut server, &reply), None);
+
+        let mut no_cookie = valid.clone();
+        no_cookie[FIXED_LEN] = 0;
+        assert_eq!(handle(&mut server, &no_cookie), None);
+
+        // No message type
+        assert_eq!(handle(&mut server, &request(PHONE, &[])), None);
+        // An option longer than the packet
+        let mut truncated = request(PHONE, &[]);
+        truncated.truncate(OPTIONS_START);
+        truncated.extend_from_slice(&[OPT_MESSAGE_TYPE, 10, DISCOVER]);
+        assert_eq!(handle(&mut server, &truncated), None);
+    }
+}
@@ -0,0 +1,154 @@
+// Captive DNS: every name resolves to us.
+//
+// A phone joins the setup AP and checks whether it's online: fetches a well-known URL.
+// Android: http://connectivitycheck.gstatic.com/generate_204, Apple: http://captive.apple.com/hotspot-detect.html
+// The name resolves to us, we redirect it to the setup page, and the phone pops up "Sign in to network".
+//
+// Answers A queries only; anything else gets an empty answer: "no such record".
+// Docs: RFC 1035, section 4.1
+
+use core::net::Ipv4Addr;
+
+pub const PORT: u16 = 53;
+
+// Header: id, flags, 4 counters: 16 bits each
+const HEADER_LEN: usize = 12;
+
+const TYPE_A: u16 = 1;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+
+// Seconds. Short: once the setup is done, the phone should forget us soon.
+const TTL: u32 = 60;
+
+// Answer a query with `ip`: write the response into `out`, return its length.
+// `None`: not a query we understand. Ignore it.
+pub fn answer(query: &[u8], ip: Ipv4Addr, out: &mut [u8]) -> Option<usize> {
+    let header = query.get(..HEADER_LEN)?;
+    let flags = u16::from_be_bytes([header[2], header[3]]);
+    let is_response = flags & 0x8000 != 0;
+    let opcode = (flags >> 11) & 0xF;
+    let questions = u16::from_be_bytes([header[4], header[5]]);
+    // A standard query, with one question: that's what resolvers send
+    if is_response || opcode != 0 || questions != 1 {
+        return None;
+    }
+
+    // The question: labels ("\x07example\x03com\x00"), type, class
+    let mut pos = HEADER_LEN;
+    loop {
+        let len = *query.get(pos)? as usize;
+        // 0xC0: a compression pointer. Not in a question.
+        if len & 0xC0 != 0 {
+            return None;
+        }
+        pos += 1 + len;
+        if len == 0 {
+            break;
+        }
+    }
+    let qtype = u16::from_be_bytes(query.get(pos..pos + 2)?.try_into().ok()?);
+    let qclass = u16::from_be_bytes(query.get(pos + 2..pos + 4)?.try_into().ok()?);
+    let question = &query[HEADER_LEN..pos + 4];
+    let has_answer = matches!(qtype, TYPE_A | TYPE_ANY) && qclass == CLASS_IN;
+
+    // Answer: points at the name in the question (offset 12), type A, class IN, TTL, 4 bytes of IP
+    const ANSWER_LEN: usize = 16;
+    let len = HEADER_LEN + question.len() + if has_answer { ANSWER_LEN } else { 0 };
+    let out = out.get_mut(..len)?;
+
+    // Same id. Flags: a response, authoritative, "recursion desired" copied.
+    out[..2].copy_from_slice(&header[..2]);
+    let flags = 0x8000 | 0x0400 | (flags & 0x0100);
+    out[2..4].copy_from_slice(&flags.to_be_bytes());
+    out[4..6].copy_from_slice(&1u16.to_be_bytes());
+    out[6..8].copy_from_slice(&(has_answer as u16).to_be_bytes());
+    out[8..12].fill(0);
+    out[HEADER_LEN..HEADER_LEN + question.len()].copy_from_slice(question);
+
+    if has_answer {
+        let answer = &mut out[HEADER_LEN + question.len()..];
+        answer[..2].copy_from_slice(&[0xC0, HEADER_LEN as u8]);
+        answer[2..4].copy_from_slice(&TYPE_A.to_be_bytes());
+        answer[4..6].copy_from_slice(&CLASS_IN.to_be_bytes());
+        answer[6..10].copy_from_slice(&TTL.to_be_bytes());
+        answer[10..12].copy_from_slice(&4u16.to_be_bytes());
+        answer[12..16].copy_from_slice(&ip.octets());
+    }
+    Some(len)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IP: Ipv4Addr = Ipv4Addr::new(192, 168, 4, 1);
+
+    // A query, as `dig` sends it: id, "recursion desired", one question, one additional record (EDNS)
+    fn query(name: &str, qtype: u16) -> Vec<u8> {
+        let mut query = vec![0xAB, 0xCD, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 1];
+        for label in name.split('.') {
+            query.push(label.len() as u8);
+            query.extend_from_slice(label.as_bytes());
+        }
+        query.push(0);
+        query.extend_from_slice(&qtype.to_be_bytes());
+        query.extend_from_slice(&CLASS_IN.to_be_bytes());
+        // EDNS OPT: ignored
+        query.extend_from_slice(&[0, 0, 41, 0x10, 0, 0, 0, 0, 0, 0, 0]);
+        query
+    }
+
+    #[test]
+    fn test_answer() {
+        let query = query("captive.apple.com", TYPE_A);
+        let mut out = [0; 512];
+        let len = answer(&query, IP, &mut out).unwrap();
+
+        let question = &query[HEADER_LEN..HEADER_LEN + 19 + 4];
+        let expected = [
+            // id; flags: response, authoritative, recursion desired; 1 question, 1 answer
+            &[0xAB, 0xCD, 0x85, 0x00, 0, 1, 0, 1, 0, 0, 0, 0][..],
+            question,
+            // name -> offset 12; A; IN; TTL 60; 4 bytes
+            &[0xC0, 12, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 192, 168, 4, 1],
+        ].concat();
+        assert_eq!(&out[..len], expected);
+    }
+
+    #[test]
+    fn test_no_answer() {
+        // AAAA: a valid response, with no answers. The phone will fall back to IPv4.
+        let query = query("example.com", 28);
+        let mut out = [0; 512];
+        let len = answer(&query, IP, &mut out).unwrap();
+        assert_eq!(&out[..12], [0xAB, 0xCD, 0x85, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(len, 12 + 13 + 4);
+    }
+
+    #[test]
+    fn test_ignored() {
+        let mut out = [0; 512];
+        let valid = query("example.com", TYPE_A);
+        assert!(answer(&valid, IP, &mut out).is_some());
+
+        // Truncated
+        assert_eq!(answer(&valid[..10], IP, &mut out), None);
+        assert_eq!(answer(&valid[..20], IP, &mut out), None);
+        // A response
+        let mut response = valid.clone();
+        response[2] |= 0x80;
+        assert_eq!(answer(&response, IP, &mut out), None);
+        // Two questions
+        let mut two = valid.clone();
+        two[5] = 2;
+        assert_eq!(answer(&two, IP, &mut out), None);
+        // A pointer in the question
+        let mut pointer = valid.clone();
+        pointer[12] = 0xC0;
+        assert_eq!(answer(&pointer, IP, &mut out), None);
+        // The response won't fit
+        assert_eq!(answer(&valid, IP, &mut out[..20]), None);
+    }
+}
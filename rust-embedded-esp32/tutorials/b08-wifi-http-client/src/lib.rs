@@ -1,7 +1,19 @@
-#![no_std]
-#![feature(impl_trait_in_assoc_type)]
+#![cfg_attr(not(test), no_std)]
+#![cfg_attr(target_arch = "riscv32", feature(impl_trait_in_assoc_type))]
 
+// Pure logic: runs on the host too
+pub mod settings;
+pub mod dns;
+pub mod dhcp;
+pub mod roaming;
+
+// ESP-only
+#[cfg(target_arch = "riscv32")]
 pub mod wifi;
+#[cfg(target_arch = "riscv32")]
+pub mod provisioning;
+#[cfg(target_arch = "riscv32")]
+pub mod portal;
 
 // Use mk_static!() macro to do esp_radio::init() with a static lifetime.
 // The StaticCell crate is useful when you need to initialize a variable at runtime
@@ -18,7 +30,7 @@ macro_rules! make_static {
     ($t:ty, $val:expr) => {{
         static STATIC_CELL: static_cell::StaticCell<$t> = static_cell::StaticCell::new();
         #[deny(unused_attributes)]
-        let x = STATIC_CELL.uninit().write(($val));
+        let x = STATIC_CELL.uninit().write($val);
         x
     }};
 }
@@ -0,0 +1,260 @@
+// Captive portal: the setup page for a device that doesn't know any Wi-Fi yet.
+//
+// 1. The device starts an open AP: "esp32-setup"
+// 2. A phone joins it. Our DHCP server gives it an address, and says: "DNS? Ask me". See dhcp.rs
+// 3. Our DNS server resolves every name to us, and the web server redirects every page to the setup page. See dns.rs
+//    The phone notices: "Sign in to network", and opens the setup page.
+// 4. The user enters the Wi-Fi name and password. We save them to flash, and reboot: into station mode.
+//
+// No pop-up? Open http://192.168.4.1/ manually.
+
+use core::{fmt, net::Ipv4Addr};
+use defmt;
+use embassy_executor::Spawner;
+use embassy_net::{
+    udp::{PacketMetadata, UdpSocket},
+    IpEndpoint, Ipv4Cidr, Stack, StaticConfigV4,
+};
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
+use embassy_time::Duration;
+use picoserve::{
+    AppRouter, AppWithStateBuilder,
+    extract::{Form, State},
+    io::Read,
+    request::{Path, Request},
+    response::{File, IntoResponse, Redirect, ResponseWriter, StatusCode},
+    routing::{self, PathRouterService, Router},
+    ResponseSent,
+};
+use serde::Deserialize;
+
+use crate::{
+    dhcp, dns,
+    provisioning::{self, SharedStorage},
+    settings::{Network, SettingsError, WifiSettings, MAX_NETWORKS},
+};
+
+// The setup AP: open, so that anyone can join it.
+// NOTE: anyone nearby can configure the device while it's in setup mode. Only lasts until the first save.
+pub const AP_SSID: &str = "esp32-setup";
+pub const AP_IP: Ipv4Addr = Ipv4Addr::new(192, 168, 4, 1);
+const AP_PREFIX_LEN: u8 = 24;
+// Everything that isn't the setup page, redirects here
+const SETUP_URL: &str = "http://192.168.4.1/";
+
+// DHCP: hand out .2 .. .9
+const DHCP_LEASES: usize = 8;
+const DHCP_POOL_START: Ipv4Addr = Ipv4Addr::new(192, 168, 4, 2);
+
+// Phones open several connections at once
+const WEB_TASK_POOL_SIZE: usize = 3;
+
+// Saved: reboot once the response is out
+static REBOOT: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+// Net config for the AP: a static IP. We're the gateway.
+pub fn net_config() -> embassy_net::Config {
+    embassy_net::Config::ipv4_static(StaticConfigV4 {
+        address: Ipv4Cidr::new(AP_IP, AP_PREFIX_LEN),
+        gateway: Some(AP_IP),
+        dns_servers: Default::default(),
+    })
+}
+
+// Spawn the DHCP, DNS and web servers. Return immediately.
+pub fn start(spawner: &Spawner, stack: Stack<'static>, storage: &'static SharedStorage) {
+    spawner.must_spawn(task_dhcp(stack));
+    spawner.must_spawn(task_dns(stack));
+
+    let router = picoserve::make_static!(AppRouter<Portal>, Portal.build_app());
+    let config = picoserve::make_static!(
+        picoserve::Config<Duration>,
+        picoserve::Config::new(picoserve::Timeouts {
+            start_read_request: Some(Duration::from_secs(5)),
+            read_request: Some(Duration::from_secs(1)),
+            write: Some(Duration::from_secs(1)),
+            persistent_start_read_request: Some(Duration::from_secs(1)),
+        })
+    );
+    for id in 0..WEB_TASK_POOL_SIZE {
+        spawner.must_spawn(task_web(id, stack, router, config, storage));
+    }
+    spawner.must_spawn(task_reboot());
+
+    defmt::info!("Portal: join \"{}\", open {}", AP_SSID, SETUP_URL);
+}
+
+
+// The web app. State: the flash storage, to save the settings
+struct Portal;
+
+impl AppWithStateBuilder for Portal {
+    type State = &'static SharedStorage;
+    type PathRouter = impl routing::PathRouter<&'static SharedStorage>;
+
+    fn build_app(self) -> Router<Self::PathRouter, &'static SharedStorage> {
+        // Unknown pages: redirect to the setup page
+        Router::from_service(RedirectToSetup)
+            .route("/", routing::get_service(File::html(include_str!("setup.html"))).post(post_setup))
+    }
+}
+
+// What the setup page posts. Empty fields may be missing.
+// Up to 3 networks: the first one is required, the other two are optional. See roaming.rs
+// NOTE: the fields are larger than the limits: too long should get a helpful message, not a rejection
+#[derive(Deserialize)]
+struct SetupForm {
+    ssid: heapless::String<64>,
+    #[serde(default)]
+    password: heapless::String<128>,
+    #[serde(default)]
+    auth: heapless::String<16>,
+    #[serde(default)]
+    ssid2: heapless::String<64>,
+    #[serde(default)]
+    password2: heapless::String<128>,
+    #[serde(default)]
+    auth2: heapless::String<16>,
+    #[serde(default)]
+    ssid3: heapless::String<64>,
+    #[serde(default)]
+    password3: heapless::String<128>,
+    #[serde(default)]
+    auth3: heapless::String<16>,
+    #[serde(default)]
+    static_ip: heapless::String<32>,
+    #[serde(default)]
+    gateway: heapless::String<32>,
+}
+
+impl SetupForm {
+    fn settings(&self) -> Result<WifiSettings, SettingsError> {
+        let mut networks = heapless::Vec::<Network, MAX_NETWORKS>::new();
+        networks.push(Network::new(&self.ssid, &self.password, &self.auth)?).ok();
+        // Optional: skip the empty ones
+        for (ssid, password, auth) in [
+            (&self.ssid2, &self.password2, &self.auth2),
+            (&self.ssid3, &self.password3, &self.auth3),
+        ] {
+            if !ssid.is_empty() {
+                networks.push(Network::new(ssid, password, auth)?).ok();
+            }
+        }
+        WifiSettings::new(&networks, &self.static_ip, &self.gateway)
+    }
+}
+
+// POST /: validate, save, reboot
+async fn post_setup(State(storage): State<&'static SharedStorage>, Form(form): Form<SetupForm>) -> impl IntoResponse {
+    let settings = match form.settings() {
+        Ok(settings) => settings,
+        Err(e) => return (StatusCode::BAD_REQUEST, message(format_args!("{}. Go back and try again.\n", e))),
+    };
+    if let Err(e) = storage.lock().await.save(&settings) {
+        defmt::error!("Portal: {}", defmt::Display2Format(&e));
+        return (StatusCode::INTERNAL_SERVER_ERROR, message(format_args!("Failed to save the settings\n")));
+    }
+
+    let ssid = settings.networks[0].ssid.as_str();
+    defmt::info!("Portal: saved settings for {} network(s)", settings.networks.len());
+    REBOOT.signal(());
+    (StatusCode::OK, message(format_args!("Saved. Rebooting: the device will join \"{}\"\n", ssid)))
+}
+
+// A short text response. Too long? Cut.
+fn message(args: fmt::Arguments) -> heapless::String<128> {
+    let mut s = heapless::String::new();
+    fmt::write(&mut s, args).ok();
+    s
+}
+
+// Any other page: "303 See Other" -> the setup page. This is what makes the phone show the pop-up.
+struct RedirectToSetup;
+
+impl<State, PathParameters> PathRouterService<State, PathParameters> for RedirectToSetup {
+    async fn call_request_handler_service<R: Read, W: ResponseWriter<Error = R::Error>>(
+        &self,
+        _state: &State,
+        _path_parameters: PathParameters,
+        _path: Path<'_>,
+        request: Request<'_, R>,
+        response_writer: W,
+    ) -> Result<ResponseSent, W::Error> {
+        Redirect::to(SETUP_URL)
+            .write_to(request.body_connection.finalize().await?, response_writer)
+            .await
+    }
+}
+
+
+// A pool of http server workers
+#[embassy_executor::task(pool_size = WEB_TASK_POOL_SIZE)]
+async fn task_web(
+    id: usize,
+    stack: Stack<'static>,
+    router: &'static AppRouter<Portal>,
+    config: &'static picoserve::Config<Duration>,
+    storage: &'static SharedStorage,
+) -> ! {
+    let mut tcp_rx_buffer = [0; 1024];
+    let mut tcp_tx_buffer = [0; 1024];
+    let mut http_buffer = [0; 2048];
+
+    picoserve::listen_and_serve_with_state(
+        id,
+        router,
+        config,
+        stack, 80,
+        &mut tcp_rx_buffer,
+        &mut tcp_tx_buffer,
+        &mut http_buffer,
+        &storage,
+    )
+    .await
+}
+
+// Task: wait for the settings to be saved, then reboot into station mode
+#[embassy_executor::task]
+async fn task_reboot() {
+    REBOOT.wait().await;
+    provisioning::reboot().await;
+}
+
+// Task: DNS server. Every name -> us.
+#[embassy_executor::task]
+async fn task_dns(stack: Stack<'static>) {
+    let (mut rx_meta, mut tx_meta) = ([PacketMetadata::EMPTY; 4], [PacketMetadata::EMPTY; 4]);
+    let (mut rx_buffer, mut tx_buffer) = ([0; 1024], [0; 1024]);
+    let mut socket = UdpSocket::new(stack, &mut rx_meta, &mut rx_buffer, &mut tx_meta, &mut tx_buffer);
+    socket.bind(dns::PORT).unwrap();
+
+    let mut query = [0; 512];
+    let mut response = [0; 512];
+    loop {
+        let Ok((len, from)) = socket.recv_from(&mut query).await else { continue };
+        if let Some(len) = dns::answer(&query[..len], AP_IP, &mut response) {
+            socket.send_to(&response[..len], from).await.ok();
+        }
+    }
+}
+
+// Task: DHCP server. Hands out addresses, and tells the clients to use our DNS.
+#[embassy_executor::task]
+async fn task_dhcp(stack: Stack<'static>) {
+    let (mut rx_meta, mut tx_meta) = ([PacketMetadata::EMPTY; 4], [PacketMetadata::EMPTY; 4]);
+    let (mut rx_buffer, mut tx_buffer) = ([0; 1024], [0; 1024]);
+    let mut socket = UdpSocket::new(stack, &mut rx_meta, &mut rx_buffer, &mut tx_meta, &mut tx_buffer);
+    socket.bind(dhcp::SERVER_PORT).unwrap();
+
+    let mut server = dhcp::Server::<DHCP_LEASES>::new(AP_IP, DHCP_POOL_START);
+    let mut request = [0; 576];
+    let mut reply = [0; 576];
+    // The client has no address yet: broadcast the reply
+    let to = IpEndpoint::new(Ipv4Addr::BROADCAST.into(), dhcp::CLIENT_PORT);
+    loop {
+        let Ok((len, _)) = socket.recv_from(&mut request).await else { continue };
+        if let Some(len) = server.handle(&request[..len], &mut reply) {
+            socket.send_to(&reply[..len], to).await.ok();
+        }
+    }
+}
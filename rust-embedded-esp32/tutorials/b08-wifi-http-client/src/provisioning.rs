@@ -0,0 +1,95 @@
+// Wi-Fi settings in flash, and the button that forgets them.
+//
+// No settings in flash: the device starts the setup AP, see portal.rs. Saved: it reboots and connects.
+// Moved? Hold the BOOT button for 3 seconds: the settings are erased, and the device reboots into the setup AP.
+//
+// Where: the "nvs" partition of the default partition table. Without ESP-IDF, nobody else uses it:
+// we write our own record there, see settings.rs
+//   $ espflash partition-table --info   # see the partitions
+
+use anyhow::{anyhow, Result};
+use defmt;
+use embassy_futures::select::{select, Either};
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, mutex::Mutex};
+use embassy_time::{Duration, Timer};
+use embedded_storage::{ReadStorage, Storage as _};
+use esp_bootloader_esp_idf::partitions::{self, DataPartitionSubType, PartitionType};
+use esp_hal::gpio::Input;
+use esp_storage::FlashStorage;
+
+use crate::settings::{WifiSettings, RECORD_LEN};
+
+// Hold the button this long to reset the Wi-Fi settings
+const LONG_PRESS: Duration = Duration::from_secs(3);
+
+// Shared between tasks: the portal saves, the button erases
+pub type SharedStorage = Mutex<CriticalSectionRawMutex, Storage>;
+
+pub struct Storage {
+    flash: FlashStorage<'static>,
+    // Where the "nvs" partition starts
+    offset: u32,
+}
+
+impl Storage {
+    // Find the partition
+    pub fn new(flash: esp_hal::peripherals::FLASH<'static>) -> Result<Self> {
+        let mut flash = FlashStorage::new(flash);
+        let mut buffer = [0; partitions::PARTITION_TABLE_MAX_LEN];
+        let table = partitions::read_partition_table(&mut flash, &mut buffer)
+            .map_err(|e| anyhow!("Failed to read the partition table: {:?}", e))?;
+        let nvs = table.find_partition(PartitionType::Data(DataPartitionSubType::Nvs))
+            .map_err(|e| anyhow!("Bad partition table: {:?}", e))?
+            .ok_or(anyhow!("No NVS partition"))?;
+        let offset = nvs.offset();
+        Ok(Self { flash, offset })
+    }
+
+    // `None`: nothing saved yet, or erased
+    pub fn load(&mut self) -> Option<WifiSettings> {
+        let mut record = [0; RECORD_LEN];
+        self.flash.read(self.offset, &mut record).ok()?;
+        WifiSettings::from_record(&record)
+    }
+
+    // NOTE: `FlashStorage` erases the sector before writing: no need to do it ourselves.
+    pub fn save(&mut self, settings: &WifiSettings) -> Result<()> {
+        self.flash.write(self.offset, &settings.to_record())
+            .map_err(|e| anyhow!("Failed to write the flash: {:?}", e))
+    }
+
+    // Erased flash is all 0xFF
+    pub fn erase(&mut self) -> Result<()> {
+        self.flash.write(self.offset, &[0xFF; RECORD_LEN])
+            .map_err(|e| anyhow!("Failed to erase the flash: {:?}", e))
+    }
+}
+
+
+// Task: long press on the button -> forget the Wi-Fi settings, reboot into the setup AP.
+// The button: active LOW, with a pull-up. The BOOT button on most boards: GPIO9.
+// NOTE: GPIO9 is a strapping pin: held on power-up, it enters the download mode. Press it after the boot.
+#[embassy_executor::task]
+pub async fn task_reset_button(mut button: Input<'static>, storage: &'static SharedStorage) {
+    loop {
+        button.wait_for_low().await;
+        // Released early: a short press. Ignore it.
+        if let Either::First(_) = select(button.wait_for_high(), Timer::after(LONG_PRESS)).await {
+            continue;
+        }
+
+        defmt::warn!("Provisioning: long press, forgetting Wi-Fi settings");
+        if let Err(e) = storage.lock().await.erase() {
+            defmt::error!("Provisioning: {}", defmt::Display2Format(&e));
+            continue;
+        }
+        reboot().await;
+    }
+}
+
+// Give the logs (and the last HTTP response) a moment to get out
+pub async fn reboot() -> ! {
+    defmt::info!("Rebooting...");
+    Timer::after(Duration::from_secs(1)).await;
+    esp_hal::system::software_reset()
+}
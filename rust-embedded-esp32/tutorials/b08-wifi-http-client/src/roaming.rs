@@ -0,0 +1,284 @@
+// Roaming: which of the known networks to join, when to switch, how long to wait after a failure.
+//
+// The policy only: no radio here, so it runs on the host too. The Wi-Fi task scans and connects: see wifi.rs
+//
+// Which network? Every known network that the scan has seen gets a score:
+//     RSSI, dBm
+//   + 10 dB for every step up the list: the first one is preferred, unless another one is much stronger
+//   - 15 dB for every recent failure: a network with a wrong password won't block the others
+// Weaker than -85 dBm: not worth trying.
+//
+// Connected, but the signal is weak? Scan once in a while, and switch if another network scores a lot better.
+// Nothing to join, or failed to connect? Wait, longer every time: 1s, 2s, 4s, ... 1 min.
+
+use core::{fmt, time::Duration};
+use heapless::String;
+
+use crate::settings::{Auth, Network, MAX_NETWORKS};
+
+// dBm. Too weak to connect.
+const MIN_RSSI: i8 = -85;
+// dBm. Connected, but weaker than this: look for a better network
+pub const WEAK_RSSI: i8 = -75;
+
+// dB per step in the list
+const PRIORITY_BONUS: i16 = 10;
+// dB per failure; and the failures that count
+const FAILURE_PENALTY: i16 = 15;
+const MAX_FAILURES: u8 = 3;
+// Switch only to a network that scores this much better: no flip-flopping between two similar ones
+const ROAM_MARGIN: i16 = 10;
+
+const BACKOFF_MIN: Duration = Duration::from_secs(1);
+const BACKOFF_MAX: Duration = Duration::from_secs(60);
+
+// An access point, as the scan has seen it
+#[derive(Debug, Clone, Copy)]
+pub struct Seen<'a> {
+    pub ssid: &'a str,
+    pub rssi: i8,
+    // What it announces. `None`: something we can't do: WEP, enterprise
+    pub auth: Option<Auth>,
+}
+
+// The network to join
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Choice {
+    // Index in the list of networks
+    pub index: usize,
+    pub rssi: i8,
+    // Never `Auth::Auto`: resolved with the scan results
+    pub auth: Auth,
+}
+
+// What the Wi-Fi task is up to. Published: see `wifi::events()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionEvent {
+    Scanning,
+    Connecting { ssid: String<32> },
+    Connected { ssid: String<32>, rssi: i8 },
+    Disconnected { ssid: String<32> },
+    // Nothing to join, or failed to connect: try again later
+    Waiting { retry_in: Duration },
+}
+
+impl fmt::Display for ConnectionEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Scanning => write!(f, "scanning"),
+            Self::Connecting { ssid } => write!(f, "connecting to \"{ssid}\""),
+            Self::Connected { ssid, rssi } => write!(f, "connected to \"{ssid}\", rssi={rssi}"),
+            Self::Disconnected { ssid } => write!(f, "disconnected from \"{ssid}\""),
+            Self::Waiting { retry_in } => write!(f, "retry in {}s", retry_in.as_secs()),
+        }
+    }
+}
+
+
+// Remembers the failures, and decides
+pub struct Supervisor<'a> {
+    networks: &'a [Network],
+    failures: [u8; MAX_NETWORKS],
+    backoff: Backoff,
+}
+
+impl<'a> Supervisor<'a> {
+    // `networks`: the first one is preferred
+    pub fn new(networks: &'a [Network]) -> Self {
+        Self { networks, failures: [0; MAX_NETWORKS], backoff: Backoff::new() }
+    }
+
+    pub fn network(&self, choice: &Choice) -> &'a Network {
+        &self.networks[choice.index]
+    }
+
+    // The best network to join. `None`: none of ours is around.
+    pub fn pick(&self, seen: &[Seen]) -> Option<Choice> {
+        self.networks.iter().enumerate()
+            .filter_map(|(index, network)| {
+                // The strongest access point of this network
+                let ap = seen.iter()
+                    .filter(|ap| ap.ssid == network.ssid.as_str() && ap.rssi >= MIN_RSSI)
+                    .max_by_key(|ap| ap.rssi)?;
+                let auth = match network.auth {
+                    Auth::Auto => ap.auth?,
+                    auth => auth,
+                };
+                Some(Choice { index, rssi: ap.rssi, auth })
+            })
+            .max_by_key(|choice| self.score(choice.index, choice.rssi))
+    }
+
+    // Connected to `current`, with this signal: switch to a better network?
+    pub fn roam(&self, current: usize, rssi: i8, seen: &[Seen]) -> Option<Choice> {
+        if rssi >= WEAK_RSSI {
+            return None;
+        }
+        let best = self.pick(seen)?;
+        let better = best.index != current && self.score(best.index, best.rssi) >= self.score(current, rssi) + ROAM_MARGIN;
+        better.then_some(best)
+    }
+
+    // Connected: forget the failures
+    pub fn connected(&mut self, index: usize) {
+        self.failures[index] = 0;
+        self.backoff.reset();
+    }
+
+    // Failed to connect: how long to wait before the next attempt
+    pub fn failed(&mut self, index: usize) -> Duration {
+        self.failures[index] = (self.failures[index] + 1).min(MAX_FAILURES);
+        self.backoff.next_delay()
+    }
+
+    // None of our networks is around: how long to wait before the next scan
+    pub fn not_found(&mut self) -> Duration {
+        self.backoff.next_delay()
+    }
+
+    fn score(&self, index: usize, rssi: i8) -> i16 {
+        let steps_up = (self.networks.len() - 1 - index) as i16;
+        rssi as i16 + PRIORITY_BONUS * steps_up - FAILURE_PENALTY * self.failures[index] as i16
+    }
+}
+
+
+// Exponential backoff: 1s, 2s, 4s, ... up to 1 min
+pub struct Backoff {
+    attempt: u32,
+}
+
+impl Backoff {
+    pub const fn new() -> Self {
+        Self { attempt: 0 }
+    }
+
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = BACKOFF_MIN.saturating_mul(1 << self.attempt.min(16)).min(BACKOFF_MAX);
+        self.attempt = self.attempt.saturating_add(1);
+        delay
+    }
+
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn networks() -> [Network; 3] {
+        [
+            Network::new("home", "12345678", "").unwrap(),
+            Network::new("phone", "12345678", "wpa2").unwrap(),
+            Network::new("cafe", "", "open").unwrap(),
+        ]
+    }
+
+    fn seen(ssid: &str, rssi: i8) -> Seen<'_> {
+        Seen { ssid, rssi, auth: Some(Auth::Wpa2Personal) }
+    }
+
+    fn picked(supervisor: &Supervisor, seen: &[Seen]) -> Option<(usize, i8)> {
+        supervisor.pick(seen).map(|choice| (choice.index, choice.rssi))
+    }
+
+    #[test]
+    fn test_pick() {
+        let networks = networks();
+        let supervisor = Supervisor::new(&networks);
+
+        // Nothing of ours around
+        assert_eq!(supervisor.pick(&[]), None);
+        assert_eq!(picked(&supervisor, &[seen("neighbour", -40)]), None);
+        // Too weak
+        assert_eq!(picked(&supervisor, &[seen("home", -90)]), None);
+        // The strongest access point of a network
+        assert_eq!(picked(&supervisor, &[seen("home", -80), seen("home", -60)]), Some((0, -60)));
+
+        // The first one is preferred ...
+        assert_eq!(picked(&supervisor, &[seen("home", -70), seen("phone", -65)]), Some((0, -70)));
+        // ... unless another one is much stronger
+        assert_eq!(picked(&supervisor, &[seen("home", -80), seen("phone", -65)]), Some((1, -65)));
+        assert_eq!(picked(&supervisor, &[seen("home", -80), seen("cafe", -55)]), Some((2, -55)));
+    }
+
+    #[test]
+    fn test_pick_auth() {
+        let networks = networks();
+        let supervisor = Supervisor::new(&networks);
+
+        // Auto: what the access point announces
+        let wpa3 = Seen { ssid: "home", rssi: -50, auth: Some(Auth::Wpa3Personal) };
+        assert_eq!(supervisor.pick(&[wpa3]).unwrap().auth, Auth::Wpa3Personal);
+        // ... nothing we can do: skip it
+        let wep = Seen { ssid: "home", rssi: -50, auth: None };
+        assert_eq!(supervisor.pick(&[wep]), None);
+        // Set explicitly: use it
+        let phone = Seen { ssid: "phone", rssi: -50, auth: None };
+        assert_eq!(supervisor.pick(&[wep, phone]).unwrap().auth, Auth::Wpa2Personal);
+    }
+
+    #[test]
+    fn test_failures() {
+        let networks = networks();
+        let mut supervisor = Supervisor::new(&networks);
+        let around = [seen("home", -60), seen("phone", -60)];
+        assert_eq!(picked(&supervisor, &around), Some((0, -60)));
+
+        // A wrong password at home: try the phone
+        supervisor.failed(0);
+        assert_eq!(picked(&supervisor, &around), Some((1, -60)));
+        // The phone fails, too: back home
+        supervisor.failed(1);
+        assert_eq!(picked(&supervisor, &around), Some((0, -60)));
+
+        // Connected: the failures are forgotten
+        supervisor.failed(0);
+        supervisor.connected(0);
+        assert_eq!(picked(&supervisor, &around), Some((0, -60)));
+    }
+
+    #[test]
+    fn test_roam() {
+        let networks = networks();
+        let supervisor = Supervisor::new(&networks);
+
+        // Strong enough: stay
+        assert_eq!(supervisor.roam(1, -60, &[seen("home", -40), seen("phone", -60)]), None);
+        // Weak, and home is much better
+        let choice = supervisor.roam(1, -80, &[seen("home", -65), seen("phone", -80)]).unwrap();
+        assert_eq!(choice.index, 0);
+        // Weak, but home is only a bit better
+        assert_eq!(supervisor.roam(1, -80, &[seen("home", -82), seen("phone", -80)]), None);
+        // The best one is where we are
+        assert_eq!(supervisor.roam(0, -80, &[seen("home", -80), seen("phone", -85)]), None);
+    }
+
+    #[test]
+    fn test_backoff() {
+        let networks = networks();
+        let mut supervisor = Supervisor::new(&networks);
+        let secs: Vec<u64> = (0..8).map(|_| supervisor.not_found().as_secs()).collect();
+        assert_eq!(secs, [1, 2, 4, 8, 16, 32, 60, 60]);
+
+        // Connected: start over
+        supervisor.connected(0);
+        assert_eq!(supervisor.failed(0), Duration::from_secs(1));
+        assert_eq!(supervisor.failed(0), Duration::from_secs(2));
+
+        let mut backoff = Backoff::new();
+        for _ in 0..100 {
+            backoff.next_delay();
+        }
+        assert_eq!(backoff.next_delay(), BACKOFF_MAX);
+    }
+}
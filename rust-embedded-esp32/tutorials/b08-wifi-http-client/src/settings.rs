@@ -0,0 +1,357 @@
+// Wi-Fi settings: entered on the setup page, kept in flash. See portal.rs and provisioning.rs
+//
+// Up to 3 networks, in the order of preference: home, the phone's hotspot, the office. See roaming.rs
+//
+// Stored as a fixed-size record:
+//   "WiFi" | version | count | 3x (SSID | password | auth) | static IP (optional) | checksum
+// Erased flash is all 0xFF: no magic, no settings. A half-written record fails the checksum.
+// Either way, the device boots into the setup AP: better than connecting with garbage.
+
+use core::{fmt, net::Ipv4Addr, str::FromStr};
+use heapless::{String, Vec};
+
+// Bytes in flash
+pub const RECORD_LEN: usize = 320;
+
+// How many networks to remember
+pub const MAX_NETWORKS: usize = 3;
+
+const MAGIC: &[u8; 4] = b"WiFi";
+// Change the layout? Bump it: old records will be ignored
+const VERSION: u8 = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WifiSettings {
+    // The first one is preferred. Never empty.
+    pub networks: Vec<Network, MAX_NETWORKS>,
+    // `None`: DHCP. Applies to every network.
+    pub static_ip: Option<StaticIp>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Network {
+    pub ssid: String<32>,
+    // Empty: an open network
+    pub password: String<64>,
+    pub auth: Auth,
+}
+
+// How to authenticate with the network
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Auth {
+    // Whatever the access point announces: see the scan results
+    Auto,
+    Open,
+    Wpa2Personal,
+    Wpa3Personal,
+    // WPA2/WPA3 transition mode
+    Wpa2Wpa3Personal,
+}
+
+// "192.168.0.199/24", gateway "192.168.0.1"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StaticIp {
+    pub address: Ipv4Addr,
+    pub prefix_len: u8,
+    pub gateway: Ipv4Addr,
+}
+
+// What's wrong with the input. Shown on the setup page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingsError {
+    EmptySsid,
+    SsidTooLong,
+    TooManyNetworks,
+    InvalidAuth,
+    PasswordLength,
+    InvalidIp,
+    InvalidGateway,
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::EmptySsid => "Network name is required",
+            Self::SsidTooLong => "Network name is longer than 32 bytes",
+            Self::TooManyNetworks => "Too many networks",
+            Self::InvalidAuth => "Security: expected auto, open, wpa2, wpa3 or wpa2/wpa3",
+            Self::PasswordLength => "Password: 8 to 64 characters, or none for an open network",
+            Self::InvalidIp => "Static IP: expected an address with a prefix, like 192.168.0.199/24",
+            Self::InvalidGateway => "Gateway: expected an address, like 192.168.0.1",
+        })
+    }
+}
+
+impl WifiSettings {
+    // Validate what the user has entered.
+    // `networks`: the first one is preferred. At least one.
+    // `static_ip`: "192.168.0.199/24", or empty for DHCP. Then `gateway` is ignored.
+    pub fn new(networks: &[Network], static_ip: &str, gateway: &str) -> Result<Self, SettingsError> {
+        if networks.is_empty() {
+            return Err(SettingsError::EmptySsid);
+        }
+        let static_ip = match static_ip.trim() {
+            "" => None,
+            cidr => Some(StaticIp::parse(cidr, gateway.trim())?),
+        };
+        Ok(Self {
+            networks: Vec::from_slice(networks).map_err(|_| SettingsError::TooManyNetworks)?,
+            static_ip,
+        })
+    }
+
+    pub fn to_record(&self) -> [u8; RECORD_LEN] {
+        let mut record = [0; RECORD_LEN];
+        let mut writer = Writer { buf: &mut record, pos: 0 };
+        writer.put(MAGIC);
+        writer.put(&[VERSION]);
+        writer.put(&[self.networks.len() as u8]);
+        for i in 0..MAX_NETWORKS {
+            match self.networks.get(i) {
+                Some(network) => {
+                    writer.put_str::<32>(&network.ssid);
+                    writer.put_str::<64>(&network.password);
+                    writer.put(&[network.auth as u8]);
+                },
+                None => writer.put(&[0; NETWORK_LEN]),
+            }
+        }
+        match self.static_ip {
+            Some(ip) => {
+                writer.put(&[1]);
+                writer.put(&ip.address.octets());
+                writer.put(&[ip.prefix_len]);
+                writer.put(&ip.gateway.octets());
+            },
+            None => writer.put(&[0; 10]),
+        }
+        // The checksum goes at the very end
+        let checksum = fnv1a(&record[..RECORD_LEN - 4]);
+        record[RECORD_LEN - 4..].copy_from_slice(&checksum.to_le_bytes());
+        record
+    }
+
+    // `None`: erased, corrupted, or an old version
+    pub fn from_record(record: &[u8; RECORD_LEN]) -> Option<Self> {
+        let (data, checksum) = record.split_at(RECORD_LEN - 4);
+        if fnv1a(data).to_le_bytes() != checksum {
+            return None;
+        }
+
+        let mut reader = Reader { buf: data, pos: 0 };
+        if reader.take(4)? != MAGIC || reader.take(1)? != [VERSION] {
+            return None;
+        }
+        let count = reader.take(1)?[0] as usize;
+        if !(1..=MAX_NETWORKS).contains(&count) {
+            return None;
+        }
+        let mut networks = Vec::new();
+        for i in 0..MAX_NETWORKS {
+            if i >= count {
+                reader.take(NETWORK_LEN)?;
+                continue;
+            }
+            let ssid = reader.take_str::<32>()?;
+            let password = reader.take_str::<64>()?;
+            let auth = Auth::from_u8(reader.take(1)?[0])?;
+            networks.push(Network { ssid, password, auth }).ok()?;
+        }
+        let static_ip = match reader.take(1)? {
+            [0] => None,
+            _ => {
+                let address = reader.take_ip()?;
+                let prefix_len = reader.take(1)?[0];
+                let gateway = reader.take_ip()?;
+                Some(StaticIp { address, prefix_len, gateway })
+            },
+        };
+        Some(Self { networks, static_ip })
+    }
+}
+
+// In the record: SSID, password, auth
+const NETWORK_LEN: usize = (1 + 32) + (1 + 64) + 1;
+
+impl Network {
+    // `auth`: as on the setup page. Empty: "auto".
+    pub fn new(ssid: &str, password: &str, auth: &str) -> Result<Self, SettingsError> {
+        if ssid.is_empty() {
+            return Err(SettingsError::EmptySsid);
+        }
+        // WPA2: a passphrase is 8..63 characters; 64 is a raw hex key
+        if !password.is_empty() && !(8..=64).contains(&password.len()) {
+            return Err(SettingsError::PasswordLength);
+        }
+        Ok(Self {
+            ssid: ssid.try_into().map_err(|_| SettingsError::SsidTooLong)?,
+            password: password.try_into().map_err(|_| SettingsError::PasswordLength)?,
+            auth: Auth::from_str(auth)?,
+        })
+    }
+}
+
+impl Auth {
+    fn from_u8(value: u8) -> Option<Self> {
+        [Self::Auto, Self::Open, Self::Wpa2Personal, Self::Wpa3Personal, Self::Wpa2Wpa3Personal]
+            .into_iter()
+            .find(|&auth| auth as u8 == value)
+    }
+}
+
+// The values of the <select> on the setup page
+impl FromStr for Auth {
+    type Err = SettingsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "" | "auto" => Ok(Self::Auto),
+            "open" => Ok(Self::Open),
+            "wpa2" => Ok(Self::Wpa2Personal),
+            "wpa3" => Ok(Self::Wpa3Personal),
+            "wpa2/wpa3" => Ok(Self::Wpa2Wpa3Personal),
+            _ => Err(SettingsError::InvalidAuth),
+        }
+    }
+}
+
+impl StaticIp {
+    pub fn parse(cidr: &str, gateway: &str) -> Result<Self, SettingsError> {
+        let (address, prefix_len) = cidr.split_once('/').ok_or(SettingsError::InvalidIp)?;
+        let address = Ipv4Addr::from_str(address).map_err(|_| SettingsError::InvalidIp)?;
+        let prefix_len = prefix_len.parse().ok().filter(|&len| (1..=32).contains(&len)).ok_or(SettingsError::InvalidIp)?;
+        let gateway = Ipv4Addr::from_str(gateway).map_err(|_| SettingsError::InvalidGateway)?;
+        Ok(Self { address, prefix_len, gateway })
+    }
+}
+
+// Strings: length byte + fixed-size field, zero-padded
+struct Writer<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl Writer<'_> {
+    fn put(&mut self, bytes: &[u8]) {
+        self.buf[self.pos..self.pos + bytes.len()].copy_from_slice(bytes);
+        self.pos += bytes.len();
+    }
+
+    fn put_str<const N: usize>(&mut self, s: &str) {
+        self.put(&[s.len() as u8]);
+        self.put(s.as_bytes());
+        self.pos += N - s.len();
+    }
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let bytes = self.buf.get(self.pos..self.pos + n)?;
+        self.pos += n;
+        Some(bytes)
+    }
+
+    fn take_str<const N: usize>(&mut self) -> Option<String<N>> {
+        let len = self.take(1)?[0] as usize;
+        let field = self.take(N)?;
+        let s = core::str::from_utf8(field.get(..len)?).ok()?;
+        s.try_into().ok()
+    }
+
+    fn take_ip(&mut self) -> Option<Ipv4Addr> {
+        let octets: [u8; 4] = self.take(4)?.try_into().ok()?;
+        Some(Ipv4Addr::from(octets))
+    }
+}
+
+// FNV-1a: a tiny hash. Catches corruption; not meant to stop anyone.
+fn fnv1a(data: &[u8]) -> u32 {
+    data.iter().fold(0x811c9dc5, |hash, &byte| (hash ^ byte as u32).wrapping_mul(0x01000193))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn home() -> Network {
+        Network::new("home", "12345678", "").unwrap()
+    }
+
+    #[test]
+    fn test_new() {
+        let settings = WifiSettings::new(&[home()], "", "ignored").unwrap();
+        assert_eq!(settings.networks[0], Network {
+            ssid: "home".try_into().unwrap(),
+            password: "12345678".try_into().unwrap(),
+            auth: Auth::Auto,
+        });
+        assert_eq!(settings.static_ip, None);
+
+        let settings = WifiSettings::new(&[home()], " 192.168.0.199/24 ", "192.168.0.1").unwrap();
+        assert_eq!(settings.static_ip, Some(StaticIp {
+            address: Ipv4Addr::new(192, 168, 0, 199),
+            prefix_len: 24,
+            gateway: Ipv4Addr::new(192, 168, 0, 1),
+        }));
+
+        let error = |ip: &str, gateway: &str| WifiSettings::new(&[home()], ip, gateway).unwrap_err();
+        assert_eq!(error("192.168.0.199", "192.168.0.1"), SettingsError::InvalidIp);
+        assert_eq!(error("192.168.0.199/33", "192.168.0.1"), SettingsError::InvalidIp);
+        assert_eq!(error("192.168.0.299/24", "192.168.0.1"), SettingsError::InvalidIp);
+        assert_eq!(error("192.168.0.199/24", ""), SettingsError::InvalidGateway);
+        assert_eq!(WifiSettings::new(&[], "", "").unwrap_err(), SettingsError::EmptySsid);
+        assert_eq!(WifiSettings::new(&[home(), home(), home(), home()], "", "").unwrap_err(), SettingsError::TooManyNetworks);
+    }
+
+    #[test]
+    fn test_network() {
+        assert_eq!(Network::new("cafe", "", "open").unwrap().auth, Auth::Open);
+        assert_eq!(Network::new("office", "12345678", "wpa2/wpa3").unwrap().auth, Auth::Wpa2Wpa3Personal);
+
+        let error = |ssid: &str, password: &str, auth: &str| Network::new(ssid, password, auth).unwrap_err();
+        assert_eq!(error("", "12345678", ""), SettingsError::EmptySsid);
+        assert_eq!(error(&"x".repeat(33), "", ""), SettingsError::SsidTooLong);
+        assert_eq!(error("home", "1234567", ""), SettingsError::PasswordLength);
+        assert_eq!(error("home", &"x".repeat(65), ""), SettingsError::PasswordLength);
+        assert_eq!(error("home", "12345678", "wep"), SettingsError::InvalidAuth);
+    }
+
+    #[test]
+    fn test_record() {
+        for settings in [
+            WifiSettings::new(&[Network::new("home", "", "open").unwrap()], "", "").unwrap(),
+            WifiSettings::new(&[
+                Network::new("Квартира 42", &"p".repeat(64), "wpa3").unwrap(),
+                Network::new(&"s".repeat(32), "12345678", "").unwrap(),
+                Network::new("phone", "12345678", "wpa2").unwrap(),
+            ], "10.0.0.5/8", "10.0.0.1").unwrap(),
+        ] {
+            assert_eq!(WifiSettings::from_record(&settings.to_record()), Some(settings));
+        }
+    }
+
+    #[test]
+    fn test_bad_record() {
+        // Erased flash
+        assert_eq!(WifiSettings::from_record(&[0xFF; RECORD_LEN]), None);
+        assert_eq!(WifiSettings::from_record(&[0; RECORD_LEN]), None);
+
+        // A flipped bit
+        let mut record = WifiSettings::new(&[home()], "", "").unwrap().to_record();
+        record[10] ^= 1;
+        assert_eq!(WifiSettings::from_record(&record), None);
+
+        // Another version, with a valid checksum
+        let mut record = WifiSettings::new(&[home()], "", "").unwrap().to_record();
+        record[4] = VERSION + 1;
+        let checksum = fnv1a(&record[..RECORD_LEN - 4]);
+        record[RECORD_LEN - 4..].copy_from_slice(&checksum.to_le_bytes());
+        assert_eq!(WifiSettings::from_record(&record), None);
+    }
+}
@@ -1,41 +1,84 @@
 use defmt;
 use esp_hal::{
+    gpio::{self, InputPin},
     rng::Rng,
 };
 use crate::make_static;
 
 use embassy_executor::Spawner;
 use esp_radio::wifi;
-use embassy_time::{Duration, Timer};
-use core::{net::Ipv4Addr, str::FromStr};
+use embassy_time::{Duration, Instant, Timer};
+use core::sync::atomic::{AtomicI32, Ordering};
+use embassy_futures::select::{select, Either};
 use embassy_net::{DhcpConfig, Ipv4Cidr, Stack, StaticConfigV4};
+use embassy_sync::{
+    blocking_mutex::raw::CriticalSectionRawMutex,
+    mutex::Mutex,
+    pubsub::{PubSubChannel, Subscriber},
+};
 
 
 // anyhow: return errors
-use anyhow::{Context, Result, anyhow};
-
-// Run me:
-// $ SSID="name" PASSWORD="secret" cargo run
-// $ SSID="name" PASSWORD="secret" STATIC_IP=192.168.0.199/24 GATEWAY=192.168.0.1 cargo run
-// $ AP_MODE=1 SSID="esp32" PASSWORD="12345678" STATIC_IP=192.168.12.1/24 GATEWAY=192.168.12.1 cargo run
+use anyhow::{Context, Result};
 
+use crate::{
+    portal,
+    provisioning::{self, SharedStorage, Storage},
+    roaming::{self, ConnectionEvent, Seen, Supervisor},
+    settings::{Auth, WifiSettings},
+};
 
-// Load WiFi credential from environment variables
-// Variables will be read *at compile time*
-const AP_MODE: Option<&str> = option_env!("AP_MODE");  // run in AP mode
-const SSID: &str = env!("SSID");
-const PASSWORD: &str = env!("PASSWORD");
-const STATIC_IP: Option<&str> = option_env!("STATIC_IP"); // optional
-const GATEWAY_IP: Option<&str> = option_env!("GATEWAY"); // optional
+// Run me:
+// $ cargo run
+// First boot: no Wi-Fi settings yet. Join the "esp32-setup" AP, and enter them on the setup page. See portal.rs
+// Then the device reboots, and connects. To start over: hold the BOOT button for 3 seconds. See provisioning.rs
 
 // The number of sockets to allocate enough space for.
+// NOTE: the setup AP needs 3 web workers + DNS + DHCP
 const N_SOCKETS: usize = 7;
 
-// Start WiFi, spawn net tasks, return net stack
+// Signal strength of the current connection, dBm. Refreshed every few seconds while connected.
+static RSSI: AtomicI32 = AtomicI32::new(NO_RSSI);
+const NO_RSSI: i32 = i32::MIN;
+const RSSI_INTERVAL: Duration = Duration::from_secs(5);
+
+// Signal strength, dBm. `None`: not connected (or in AP mode).
+pub fn rssi() -> Option<i32> {
+    Some(RSSI.load(Ordering::Relaxed)).filter(|&rssi| rssi != NO_RSSI)
+}
+
+// How many access points to look at. Crowded air? The strongest ones come first.
+const MAX_SCAN_RESULTS: usize = 20;
+// Connected with a weak signal: look for a better network this often
+const ROAM_INTERVAL: Duration = Duration::from_secs(60);
+
+// Connection events: scanning, connected, disconnected, ... See roaming.rs
+// Every subscriber gets every event. Too slow? It loses the oldest ones.
+pub const MAX_EVENT_SUBSCRIBERS: usize = 2;
+const EVENT_CAPACITY: usize = 4;
+pub type Events = Subscriber<'static, CriticalSectionRawMutex, ConnectionEvent, EVENT_CAPACITY, MAX_EVENT_SUBSCRIBERS, 0>;
+static EVENTS: PubSubChannel<CriticalSectionRawMutex, ConnectionEvent, EVENT_CAPACITY, MAX_EVENT_SUBSCRIBERS, 0> = PubSubChannel::new();
+
+// Subscribe to connection events. `None`: too many subscribers.
+pub fn events() -> Option<Events> {
+    EVENTS.subscriber().ok()
+}
+
+// Start WiFi, spawn net tasks, return net stack.
+// No Wi-Fi settings in flash: start the setup AP and never return. Reboots once the settings are saved.
+// `reset_button`: hold it to forget the settings. The BOOT button: GPIO9.
 pub async fn start_wifi(
     spawner: &Spawner,
     wifi_peripheral: esp_hal::peripherals::WIFI<'static>,
+    flash: esp_hal::peripherals::FLASH<'static>,
+    reset_button: impl InputPin + 'static,
 ) -> Result<Stack<'static>> {
+    // Wi-Fi settings: in flash
+    let storage = &*make_static!(SharedStorage, Mutex::new(Storage::new(flash)?));
+    let reset_button = gpio::Input::new(reset_button, gpio::InputConfig::default().with_pull(gpio::Pull::Up));
+    spawner.must_spawn(provisioning::task_reset_button(reset_button, storage));
+    let settings = storage.lock().await.load();
+
     // Init Wifi.
     // Make a static variable: it's globally available.
     let radio_init = &*make_static!(
@@ -48,12 +91,11 @@ pub async fn start_wifi(
 
     // Init controller
     let (wifi_controller, interfaces) =
-        wifi::new(&radio_init, wifi_peripheral, Default::default())
+        wifi::new(radio_init, wifi_peripheral, Default::default())
             .context("Failed to initialize Wi-Fi controller")?;
-    let wifi_interface = if AP_MODE.is_none() {
-        interfaces.sta // WiFi station: the client
-    } else {
-        interfaces.ap
+    let wifi_interface = match settings {
+        Some(_) => interfaces.sta, // WiFi station: the client
+        None => interfaces.ap,
     };
 
     // Network stack needs a random number: for TLS and networking.
@@ -62,22 +104,25 @@ pub async fn start_wifi(
     let net_seed = rng.random() as u64 | ((rng.random() as u64) << 32);
 
     // Init network stack
-    let net_config = {
+    let net_config = match &settings {
+        // Setup AP: we're the router
+        None => portal::net_config(),
         // Static ip
-        if let Some(ip) = STATIC_IP && let Some(gw) = GATEWAY_IP {
+        Some(WifiSettings { static_ip: Some(ip), .. }) => {
             embassy_net::Config::ipv4_static(StaticConfigV4{
-                address: Ipv4Cidr::from_str(ip).unwrap(),
-                gateway: Ipv4Addr::from_str(gw).ok(),
+                address: Ipv4Cidr::new(ip.address, ip.prefix_len),
+                gateway: Some(ip.gateway),
                 dns_servers: Default::default(), // TODO: use Google DNS by default?
             })
+        },
         // DHCP
-        } else {
+        Some(_) => {
             embassy_net::Config::dhcpv4({
                 let mut c = DhcpConfig::default();
                 c.hostname = Some(heapless::String::new());
                 c
             })
-        }
+        },
     };
     let (stack, runner) = embassy_net::new(
         wifi_interface, net_config,
@@ -85,16 +130,19 @@ pub async fn start_wifi(
         make_static!(embassy_net::StackResources<N_SOCKETS>, embassy_net::StackResources::<N_SOCKETS>::new()),
         net_seed,
     );
+    spawner.spawn(task_network(runner)).ok();
 
-    // Start two background tasks:
-    // - the connection_task will maintain the Wi-Fi connection
-    // - the net_task will run the network stack and handle network events.
-    if AP_MODE.is_none() {
-        spawner.spawn(task_keep_wifi_client_up(wifi_controller)).ok();
-    } else {
+    // Start the background task that maintains the Wi-Fi connection.
+    // No settings? Serve the setup page until they're saved: then we reboot.
+    let Some(settings) = settings else {
         spawner.spawn(task_keep_wifi_ap_up(wifi_controller)).ok();
-    }
-    spawner.spawn(task_network(runner)).ok();
+        portal::start(spawner, stack, storage);
+        loop {
+            Timer::after(Duration::from_secs(3600)).await;
+        }
+    };
+    let settings = &*make_static!(WifiSettings, settings);
+    spawner.spawn(task_keep_wifi_client_up(wifi_controller, settings)).ok();
 
     // Wait until the connection is up
     wait_for_connection(stack).await;
@@ -111,61 +159,147 @@ async fn task_network(mut runner: embassy_net::Runner<'static, wifi::WifiDevice<
 }
 
 
-// Task: manage WiFi connection by continuously checking the status, configuring the Wi-Fi controller,
-// and attempting to reconnect if the connection is lost or not started.
+// Task: the connection supervisor. Scans, joins the best known network, watches the signal, roams.
+// The policy, see roaming.rs: which network, when to switch, how long to wait.
 #[embassy_executor::task]
-async fn task_keep_wifi_client_up(mut controller: wifi::WifiController<'static>) {
+async fn task_keep_wifi_client_up(mut controller: wifi::WifiController<'static>, settings: &'static WifiSettings) {
     defmt::info!("WiFi: start client");
     defmt::info!("WiFi: Device capabilities: {:?}", controller.capabilities());
+    let mut supervisor = Supervisor::new(&settings.networks);
 
-    loop {
-        // 1. Check WiFi state
-        // If it is in StaConnected, we wait until it gets disconnected.
-        match wifi::sta_state() {
-            wifi::WifiStaState::Connected => {
-                // wait until we're no longer connected
-                controller.wait_for_event(wifi::WifiEvent::StaDisconnected).await;
-                Timer::after(Duration::from_millis(5000)).await;
-            }
-            _ => {},
-        }
-
-        // 2. Check if the WiFi controller is started.
-        // If not, we initialize the WiFi client configuration.
-        if !matches!(controller.is_started(), Ok(true)) {
-            // Init client. Use SSID.
-            let client_config = wifi::ModeConfig::Client(
-                wifi::ClientConfig::default()
-                    .with_ssid(SSID.into())
-                    .with_password(PASSWORD.into())
-                    .with_auth_method(wifi::AuthMethod::Wpa2Personal),  // TODO: configurable?
-            );
-            controller.set_config(&client_config).unwrap();
-            defmt::debug!("WiFi: starting...");
-
-            // Wifi start.
-            controller.start_async().await.unwrap();
-        }
+    // Start the station: it can't scan otherwise
+    controller.set_config(&wifi::ModeConfig::Client(Default::default())).unwrap();
+    controller.start_async().await.unwrap();
 
-        // Wait until connected
+    loop {
+        // 1. Scan: which of our networks are around?
+        publish(ConnectionEvent::Scanning);
+        let Some(choice) = scan(&mut controller, |seen| supervisor.pick(seen)).await.flatten() else {
+            wait(supervisor.not_found()).await;
+            continue;
+        };
+
+        // 2. Connect
+        let network = supervisor.network(&choice);
+        publish(ConnectionEvent::Connecting { ssid: network.ssid.clone() });
+        let client_config = wifi::ModeConfig::Client(
+            wifi::ClientConfig::default()
+                .with_ssid(network.ssid.as_str().into())
+                .with_password(network.password.as_str().into())
+                .with_auth_method(auth_method(choice.auth)),
+        );
+        controller.set_config(&client_config).unwrap();
         defmt::debug!("WiFi: connecting...");
         match controller.connect_async().await {
             // NOTE: This is only WiFi.
             // The network stack (smoltcp) will need to use its DHCP client now.
             Ok(_) => {
-                let rssi = controller.rssi().unwrap_or(-999);
-                defmt::info!("WiFi: connected! rssi={}", rssi);
+                supervisor.connected(choice.index);
+                let rssi = controller.rssi().map_or(choice.rssi, clamp_rssi);
+                publish(ConnectionEvent::Connected { ssid: network.ssid.clone(), rssi });
+
+                // 3. Stay connected: until the connection is lost, or there's a better network
+                stay_connected(&mut controller, &supervisor, choice.index).await;
+                publish(ConnectionEvent::Disconnected { ssid: network.ssid.clone() });
             }
             Err(e) => {
                 defmt::warn!("WiFi: failed to connect: {:?}", e);
+                wait(supervisor.failed(choice.index)).await;
+            }
+        }
+    }
+}
 
-                // Sleep 5s before trying again
-                Timer::after(Duration::from_millis(5000)).await
+// Connected: keep an eye on the signal strength. Returns when disconnected, or after leaving for a better network.
+async fn stay_connected(controller: &mut wifi::WifiController<'static>, supervisor: &Supervisor<'_>, index: usize) {
+    let mut last_roam_scan = Instant::now();
+    loop {
+        let rssi = controller.rssi().unwrap_or(NO_RSSI);
+        RSSI.store(rssi, Ordering::Relaxed);
+
+        // Weak signal? Look for a better network. Not too often: a scan interrupts the traffic.
+        if rssi != NO_RSSI && rssi < roaming::WEAK_RSSI as i32 && last_roam_scan.elapsed() >= ROAM_INTERVAL {
+            last_roam_scan = Instant::now();
+            let better = scan(controller, |seen| supervisor.roam(index, clamp_rssi(rssi), seen)).await.flatten();
+            if let Some(better) = better {
+                defmt::info!("WiFi: roaming to \"{}\", rssi={}", supervisor.network(&better).ssid.as_str(), better.rssi);
+                controller.disconnect_async().await.ok();
+                break;
             }
         }
+
+        // Lost while we weren't looking?
+        if wifi::sta_state() != wifi::WifiStaState::Connected {
+            break;
+        }
+        let disconnected = controller.wait_for_event(wifi::WifiEvent::StaDisconnected);
+        if let Either::First(_) = select(disconnected, Timer::after(RSSI_INTERVAL)).await {
+            break;
+        }
+    }
+    RSSI.store(NO_RSSI, Ordering::Relaxed);
+}
+
+// Scan, and decide: `f` gets the access points around. `None`: the scan has failed.
+async fn scan<R>(controller: &mut wifi::WifiController<'static>, f: impl FnOnce(&[Seen]) -> R) -> Option<R> {
+    let config = wifi::ScanConfig::default().with_max(MAX_SCAN_RESULTS);
+    let access_points = match controller.scan_with_config_async(config).await {
+        Ok(access_points) => access_points,
+        Err(e) => {
+            defmt::warn!("WiFi: scan failed: {:?}", e);
+            return None;
+        }
+    };
+    let seen: heapless::Vec<Seen, MAX_SCAN_RESULTS> = access_points.iter()
+        .map(|ap| Seen {
+            ssid: &ap.ssid,
+            rssi: ap.signal_strength,
+            auth: ap.auth_method.and_then(auth_from_scan),
+        })
+        .take(MAX_SCAN_RESULTS)
+        .collect();
+    Some(f(&seen))
+}
+
+// Sleep, and tell everyone why
+async fn wait(delay: core::time::Duration) {
+    publish(ConnectionEvent::Waiting { retry_in: delay });
+    Timer::after(Duration::from_millis(delay.as_millis() as u64)).await;
+}
+
+// What the access point announces -> what we can do. `None`: we can't.
+fn auth_from_scan(method: wifi::AuthMethod) -> Option<Auth> {
+    match method {
+        wifi::AuthMethod::None => Some(Auth::Open),
+        wifi::AuthMethod::Wpa2Personal | wifi::AuthMethod::WpaWpa2Personal => Some(Auth::Wpa2Personal),
+        wifi::AuthMethod::Wpa3Personal => Some(Auth::Wpa3Personal),
+        wifi::AuthMethod::Wpa2Wpa3Personal => Some(Auth::Wpa2Wpa3Personal),
+        // WEP, WPA, enterprise, WAPI
+        _ => None,
+    }
+}
+
+// NOTE: for a client, it's the weakest auth method to accept
+fn auth_method(auth: Auth) -> wifi::AuthMethod {
+    match auth {
+        Auth::Open => wifi::AuthMethod::None,
+        // `Auto` is resolved by the supervisor. Just in case: the default.
+        Auth::Auto | Auth::Wpa2Personal => wifi::AuthMethod::Wpa2Personal,
+        Auth::Wpa3Personal => wifi::AuthMethod::Wpa3Personal,
+        Auth::Wpa2Wpa3Personal => wifi::AuthMethod::Wpa2Wpa3Personal,
     }
 }
 
+fn clamp_rssi(rssi: i32) -> i8 {
+    rssi.clamp(i8::MIN as i32, i8::MAX as i32) as i8
+}
+
+// Publish an event: log it, and tell the subscribers
+fn publish(event: ConnectionEvent) {
+    defmt::info!("WiFi: {}", defmt::Display2Format(&event));
+    EVENTS.immediate_publisher().publish_immediate(event);
+}
+
 // Task: wait for the Wi-Fi link to be up, then obtain the IP address.
 async fn wait_for_connection(stack: embassy_net::Stack<'_>) {
     let mut print_once: bool = false;
@@ -198,25 +332,26 @@ async fn wait_for_connection(stack: embassy_net::Stack<'_>) {
 
 
 
+// Task: keep the setup AP up
 #[embassy_executor::task]
 async fn task_keep_wifi_ap_up(mut controller: wifi::WifiController<'static>) {
     defmt::info!("WiFi: start AP");
 
     loop {
-        // WiFi AP is up and running? Then do nothing.
+        // WiFi AP is up and running? Wait until it stops.
         if wifi::ap_state() == wifi::WifiApState::Started {
+            controller.wait_for_event(wifi::WifiEvent::ApStop).await;
+            defmt::warn!("WiFi: AP stopped");
             Timer::after(Duration::from_millis(1000)).await;
-            continue;
         }
 
         // Re-configure AP
         if !matches!(controller.is_started(), Ok(true)) {
-            // Init client. Use SSID.
+            // Open: no password. See portal.rs
             let ap_config = wifi::ModeConfig::AccessPoint(
                 wifi::AccessPointConfig::default()
-                    .with_ssid(SSID.into())
-                    .with_password(PASSWORD.into())
-                    .with_auth_method(wifi::AuthMethod::Wpa2Wpa3Personal),
+                    .with_ssid(portal::AP_SSID.into())
+                    .with_auth_method(wifi::AuthMethod::None),
             );
             controller.set_config(&ap_config).unwrap();
             defmt::info!("WiFi: Starting ...");
@@ -0,0 +1,60 @@
+// Talk to the REST API: see /api/openapi.json
+
+// Changing things needs credentials: see auth.rs
+// Basic auth: the browser asks for the password itself. Token: we ask, and remember it.
+async function api(method, path, body) {
+    const headers = { 'Content-Type': 'application/json' };
+    const token = localStorage.getItem('token');
+    if (token) {
+        headers['Authorization'] = 'Bearer ' + token;
+    }
+    const response = await fetch(path, {
+        method,
+        headers,
+        body: body && JSON.stringify(body),
+    });
+    if (response.status == 401 && response.headers.get('WWW-Authenticate')?.startsWith('Bearer')) {
+        const token = prompt('API token:');
+        if (token) {
+            localStorage.setItem('token', token);
+            return api(method, path, body);
+        }
+    }
+    const data = await response.json();
+    if (!response.ok) {
+        throw new Error(data.error);
+    }
+    return data;
+}
+
+function showLed(led) {
+    document.getElementById('led').textContent = led.is_on ? 'on' : 'off';
+}
+
+async function refresh() {
+    const status = await api('GET', '/api/status');
+    showLed(status.led);
+    document.getElementById('uptime').textContent = Math.round(status.uptime_ms / 1000);
+    document.getElementById('rssi').textContent = status.rssi ?? '-';
+}
+
+function setLed(is_on) {
+    api('PUT', '/api/led', { is_on })
+    .then(showLed)
+    .catch(error => {
+        console.error('Error:', error);
+        alert('Failed to send the request');
+    });
+}
+
+refresh();
+
+// Live updates: the server pushes changes. See events.rs
+const events = new EventSource('/events');
+events.addEventListener('led', e => showLed(JSON.parse(e.data)));
+events.addEventListener('uptime', e => {
+    document.getElementById('uptime').textContent = JSON.parse(e.data).secs;
+});
+events.addEventListener('temperature', e => {
+    document.getElementById('temperature').textContent = JSON.parse(e.data).celsius;
+});
@@ -1,4 +1,13 @@
 fn main() {
+    // Web assets: needed everywhere, unit-tests too
+    bundle_assets();
+
+    // ESP-only stuff. Host builds (unit-tests) don't need it.
+    // NOTE: build.rs runs on the host, so check the *target* arch via the env, not #[cfg]
+    if std::env::var("CARGO_CFG_TARGET_ARCH").as_deref() != Ok("riscv32") {
+        return;
+    }
+
     linker_be_nice();
     println!("cargo:rustc-link-arg=-Tdefmt.x");
     // make sure linkall.x is the last linker script (otherwise might cause problems with flip-link)
@@ -55,3 +64,95 @@ fn linker_be_nice() {
         std::env::current_exe().unwrap().display()
     );
 }
+
+
+// Asset pipeline: assets/* -> gzip -> $OUT_DIR, plus `assets.rs` that embeds them. See src/assets.rs
+//
+// * Every file is gzipped: less flash, less radio time. The browser unpacks it.
+// * CSS, JS, etc get a content hash in their name: "app.js" -> "app.0123abcd.js".
+//   The name changes whenever the contents do, so the browser can cache them forever.
+// * HTML files keep their names: they're the entry points. The links in them are rewritten to the hashed names.
+fn bundle_assets() {
+    use std::{fmt::Write as _, fs, io::Write as _, path::Path};
+
+    let src = Path::new("assets");
+    let out = Path::new(&std::env::var("OUT_DIR").unwrap()).join("assets");
+    fs::create_dir_all(&out).unwrap();
+    println!("cargo:rerun-if-changed=assets");
+
+    let mut files: Vec<_> = fs::read_dir(src).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_file())
+        .collect();
+    files.sort();
+
+    // Hashed names first: HTML files link to them
+    let mut renames = vec![];
+    for path in &files {
+        let name = path.file_name().unwrap().to_str().unwrap().to_string();
+        if is_html(&name) {
+            continue;
+        }
+        let (stem, ext) = name.rsplit_once('.').unwrap_or((&name, ""));
+        let hash = fnv1a(&fs::read(path).unwrap());
+        renames.push((name.clone(), format!("{stem}.{:08x}.{ext}", hash as u32)));
+    }
+
+    let mut code = String::from("// Generated by build.rs from assets/. Don't edit.\n\n");
+    let mut entries = String::new();
+    for path in &files {
+        let name = path.file_name().unwrap().to_str().unwrap().to_string();
+        let mut content = fs::read(path).unwrap();
+
+        let (served_name, headers) = if is_html(&name) {
+            let mut html = String::from_utf8(content).unwrap();
+            for (from, to) in &renames {
+                html = html.replace(&format!("/static/{from}"), &format!("/static/{to}"));
+            }
+            content = html.into_bytes();
+            (name.clone(), "REVALIDATE")
+        } else {
+            let (_, hashed) = renames.iter().find(|(from, _)| *from == name).unwrap();
+            (hashed.clone(), "IMMUTABLE")
+        };
+
+        let mut gz = flate2::write::GzEncoder::new(vec![], flate2::Compression::best());
+        gz.write_all(&content).unwrap();
+        let gz_path = out.join(format!("{name}.gz"));
+        fs::write(&gz_path, gz.finish().unwrap()).unwrap();
+
+        let file = format!(
+            "File::with_content_type_and_headers({:?}, include_bytes!({:?}), {headers})",
+            content_type(&name), gz_path.display(),
+        );
+        writeln!(entries, "        ({served_name:?}, {file}),").unwrap();
+        if name == "index.html" {
+            writeln!(code, "pub const INDEX: File = {file};\n").unwrap();
+        }
+    }
+    writeln!(code, "pub const STATIC: Directory = Directory {{\n    files: &[\n{entries}    ],\n    sub_directories: &[],\n}};").unwrap();
+
+    fs::write(out.join("assets.rs"), code).unwrap();
+}
+
+fn is_html(name: &str) -> bool {
+    name.ends_with(".html")
+}
+
+fn content_type(name: &str) -> &'static str {
+    match name.rsplit_once('.').map(|(_, ext)| ext) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("css") => "text/css",
+        Some("js") => "application/javascript; charset=utf-8",
+        Some("json") => "application/json",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("ico") => "image/x-icon",
+        _ => "application/octet-stream",
+    }
+}
+
+// FNV-1a: a tiny non-cryptographic hash. Good enough to tell versions of a file apart.
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, &b| (hash ^ b as u64).wrapping_mul(0x100000001b3))
+}
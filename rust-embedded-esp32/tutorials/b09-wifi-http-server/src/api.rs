@@ -0,0 +1,298 @@
+// REST API: device status and controls, JSON in, JSON out.
+//
+//   GET  /api/status        uptime, heap, Wi-Fi signal, LED
+//   GET  /api/led           {"is_on": true}
+//   PUT  /api/led           {"is_on": true}  -> the new state
+//   GET  /api/openapi.json  the description of this API: paste it into https://editor.swagger.io/
+//   GET  /events            changes, as they happen: Server-Sent Events. See events.rs
+//
+// Errors come as JSON too: {"error": "..."}, with a 4xx status code.
+// Changing things needs credentials: see auth.rs
+//
+// The handlers don't touch the hardware directly: they go through the `Device` trait,
+// which is the app state: picoserve passes it to every handler with the `State` extractor.
+// The firmware implements it with the real thing; the unit-tests use a fake, and run on the host:
+// $ cargo test --lib --target x86_64-unknown-linux-gnu -Zbuild-std=std,panic_unwind,test
+
+use core::fmt::Write;
+use crate::events::Hub;
+use picoserve::{
+    extract::{FromRequest, State},
+    request::{RequestBody, RequestParts},
+    response::{File, IntoResponse, Json, StatusCode},
+    routing::{self, PathRouter, Router},
+};
+
+// What the API needs from the device.
+// Cloned for every request: keep it cheap, e.g. a bunch of references.
+pub trait Device: Clone {
+    fn status(&self) -> Status;
+    fn led(&self) -> bool;
+    fn set_led(&self, is_on: bool);
+    // Tasks publish their changes here
+    fn events(&self) -> &'static Hub;
+}
+
+#[derive(serde::Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Status {
+    pub uptime_ms: u64,
+    pub heap: Heap,
+    // Wi-Fi signal, dBm. `null`: not connected
+    pub rssi: Option<i32>,
+    pub led: Led,
+}
+
+#[derive(serde::Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Heap {
+    pub used: usize,
+    pub free: usize,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Led {
+    pub is_on: bool,
+}
+
+// Routes. Mount them under "/api". The app state is the device.
+pub fn router<D: Device>() -> Router<impl PathRouter<D>, D> {
+    Router::new()
+        .route("/status", routing::get(get_status::<D>))
+        .route("/led", routing::get(get_led::<D>).put(put_led::<D>))
+        .route(
+            "/openapi.json",
+            routing::get_service(File::with_content_type("application/json", OPENAPI.as_bytes())),
+        )
+}
+
+// OpenAPI description. Keep it in sync with the routes!
+pub const OPENAPI: &str = include_str!("openapi.json");
+
+async fn get_status<D: Device>(State(device): State<D>) -> impl IntoResponse {
+    Json(device.status())
+}
+
+async fn get_led<D: Device>(State(device): State<D>) -> impl IntoResponse {
+    Json(Led { is_on: device.led() })
+}
+
+async fn put_led<D: Device>(State(device): State<D>, JsonBody(led): JsonBody<Led>) -> impl IntoResponse {
+    device.set_led(led.is_on);
+    Json(Led { is_on: device.led() })
+}
+
+
+// Extractor: JSON request body.
+// Like picoserve's `Json`, but rejects with a JSON error: picoserve responds with plain text.
+pub struct JsonBody<T>(pub T);
+
+impl<'r, State, T: serde::de::DeserializeOwned> FromRequest<'r, State> for JsonBody<T> {
+    type Rejection = ApiError;
+
+    async fn from_request<R: picoserve::io::Read>(
+        _state: &'r State,
+        _request_parts: RequestParts<'r>,
+        request_body: RequestBody<'r, R>,
+    ) -> Result<Self, ApiError> {
+        if !request_body.entire_body_fits_into_buffer() {
+            return Err(ApiError::TooLarge);
+        }
+        let body = request_body.read_all().await.map_err(|_| ApiError::Io)?;
+        serde_json_core::from_slice(body)
+            .map(|(value, _)| Self(value))
+            .map_err(ApiError::InvalidJson)
+    }
+}
+
+// API error: responds with a status code and {"error": "message"}
+#[derive(Debug)]
+pub enum ApiError {
+    InvalidJson(serde_json_core::de::Error),
+    TooLarge,
+    Io,
+    // `/events`: every subscriber slot is taken
+    TooManySubscribers,
+    // No credentials, or wrong ones. The `WWW-Authenticate` challenge: see auth.rs
+    Unauthorized(&'static str),
+}
+
+#[derive(serde::Serialize)]
+struct ErrorBody<'a> {
+    error: &'a str,
+}
+
+impl IntoResponse for ApiError {
+    async fn write_to<R: picoserve::io::Read, W: picoserve::response::ResponseWriter<Error = R::Error>>(
+        self,
+        connection: picoserve::response::Connection<'_, R>,
+        response_writer: W,
+    ) -> Result<picoserve::ResponseSent, W::Error> {
+        let mut message = heapless::String::<64>::new();
+        let mut challenge = None;
+        let status = match &self {
+            Self::InvalidJson(e) => {
+                // Truncated if too long: fine
+                write!(message, "Invalid JSON: {e}").ok();
+                StatusCode::BAD_REQUEST
+            }
+            Self::TooLarge => {
+                message.push_str("Request body is too large").ok();
+                StatusCode::PAYLOAD_TOO_LARGE
+            }
+            Self::Io => {
+                message.push_str("Failed to read the request").ok();
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            Self::TooManySubscribers => {
+                message.push_str("Too many subscribers, try again later").ok();
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            Self::Unauthorized(www_authenticate) => {
+                message.push_str("Unauthorized").ok();
+                challenge = Some(("WWW-Authenticate", *www_authenticate));
+                StatusCode::UNAUTHORIZED
+            }
+        };
+        let response = Json(ErrorBody { error: &message })
+            .into_response()
+            .with_status_code(status)
+            .with_headers(challenge);
+        response_writer.write_response(connection, response).await
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::Cell;
+    use std::rc::Rc;
+    use crate::{auth::{Auth, Credentials}, testing};
+
+    // Fake device: every test has its own
+    #[derive(Clone)]
+    struct FakeDevice {
+        led: Rc<Cell<bool>>,
+        events: &'static Hub,
+    }
+
+    impl FakeDevice {
+        fn new() -> Self {
+            Self { led: Rc::default(), events: Box::leak(Box::new(Hub::new())) }
+        }
+    }
+
+    impl Device for FakeDevice {
+        fn status(&self) -> Status {
+            Status {
+                uptime_ms: 12_345,
+                heap: Heap { used: 1000, free: 2000 },
+                rssi: Some(-60),
+                led: Led { is_on: self.led() },
+            }
+        }
+        fn led(&self) -> bool {
+            self.led.get()
+        }
+        fn set_led(&self, is_on: bool) {
+            self.led.set(is_on)
+        }
+        fn events(&self) -> &'static Hub {
+            self.events
+        }
+    }
+
+    // Send a request to the API, with credentials; get the status code and the body
+    fn request(device: &FakeDevice, method: &str, path: &str, body: &str) -> (u16, String) {
+        let (status, _, body) = request_with_headers(device, method, path, "Authorization: Bearer secret\r\n", body);
+        (status, body)
+    }
+
+    // Send a request with custom headers; get the status code, the headers, and the body
+    fn request_with_headers(device: &FakeDevice, method: &str, path: &str, headers: &str, body: &str) -> (u16, String, String) {
+        let app = Router::new()
+            .route("/events", routing::get(crate::events::get_events::<FakeDevice>))
+            .nest("/api", router::<FakeDevice>())
+            .layer(Auth::new(Credentials::Bearer("secret")).with_public(&["/api/status"]));
+        let request = format!(
+            "{method} {path} HTTP/1.1\r\nConnection: close\r\n{headers}Content-Length: {}\r\n\r\n{body}",
+            body.len(),
+        );
+        let response = testing::serve(&app, device, &request);
+        (response.status, response.head.clone(), response.text())
+    }
+
+    #[test]
+    fn test_status() {
+        let device = FakeDevice::new();
+        let (status, body) = request(&device, "GET", "/api/status", "");
+        assert_eq!(status, 200);
+        assert_eq!(body, r#"{"uptime_ms":12345,"heap":{"used":1000,"free":2000},"rssi":-60,"led":{"is_on":false}}"#);
+    }
+
+    #[test]
+    fn test_led() {
+        let device = FakeDevice::new();
+        assert_eq!(request(&device, "GET", "/api/led", ""), (200, r#"{"is_on":false}"#.into()));
+        assert_eq!(request(&device, "PUT", "/api/led", r#"{"is_on": true}"#), (200, r#"{"is_on":true}"#.into()));
+        assert_eq!(request(&device, "GET", "/api/led", ""), (200, r#"{"is_on":true}"#.into()));
+
+        // Wrong method
+        assert_eq!(request(&device, "POST", "/api/led", r#"{"is_on": true}"#).0, 405);
+    }
+
+    #[test]
+    fn test_bad_request() {
+        let device = FakeDevice::new();
+        for body in ["", "{", r#"{"is_on": 1}"#, r#"{"on": true}"#] {
+            let (status, body) = request(&device, "PUT", "/api/led", body);
+            assert_eq!(status, 400, "{body}");
+            assert!(body.starts_with(r#"{"error":"Invalid JSON: "#), "{body}");
+        }
+        // Unchanged
+        assert!(!device.led());
+
+        let (status, body) = request(&device, "PUT", "/api/led", &" ".repeat(4096));
+        assert_eq!((status, body.as_str()), (413, r#"{"error":"Request body is too large"}"#));
+    }
+
+    #[test]
+    fn test_openapi() {
+        let device = FakeDevice::new();
+        let (status, body) = request(&device, "GET", "/api/openapi.json", "");
+        assert_eq!(status, 200);
+        assert_eq!(body, OPENAPI);
+        // Every route is documented
+        for path in ["\"/api/status\"", "\"/api/led\"", "\"/api/openapi.json\"", "\"/events\""] {
+            assert!(OPENAPI.contains(path), "{path} is not documented");
+        }
+        assert_eq!(request(&device, "GET", "/api/nope", "").0, 404);
+    }
+
+    #[test]
+    fn test_events_busy() {
+        let device = FakeDevice::new();
+        // Every slot is taken: come back later
+        let _subscriptions: Vec<_> = (0..crate::events::MAX_SUBSCRIBERS)
+            .map(|_| device.events().subscribe().unwrap())
+            .collect();
+        let (status, body) = request(&device, "GET", "/events", "");
+        assert_eq!((status, body.as_str()), (503, r#"{"error":"Too many subscribers, try again later"}"#));
+    }
+
+    #[test]
+    fn test_auth() {
+        let device = FakeDevice::new();
+
+        // No credentials, or wrong ones: 401, and what we expect
+        for headers in ["", "Authorization: Bearer nope\r\n", "Authorization: Basic YWRtaW46c2VjcmV0\r\n"] {
+            let (status, head, body) = request_with_headers(&device, "PUT", "/api/led", headers, r#"{"is_on": true}"#);
+            assert_eq!((status, body.as_str()), (401, r#"{"error":"Unauthorized"}"#));
+            assert!(head.contains("WWW-Authenticate: Bearer realm=\"esp32\""), "{head}");
+        }
+        assert!(!device.led());
+        assert_eq!(request_with_headers(&device, "GET", "/api/led", "", "").0, 401);
+
+        // Public, read-only
+        assert_eq!(request_with_headers(&device, "GET", "/api/status", "", "").0, 200);
+    }
+}
@@ -0,0 +1,105 @@
+// Static web assets: the files in assets/, gzipped and embedded at build time. See build.rs
+//
+//   GET /                        index.html
+//   GET /static/index.html       same
+//   GET /static/app.0123abcd.js  assets/app.js: the hash changes whenever the file does
+//
+// Every response is gzipped: `Content-Encoding: gzip`. All browsers support it; for curl, use `--compressed`.
+//
+// Caching: every file has an `ETag`: the hash of its contents.
+// The browser sends it back in `If-None-Match`; if the file hasn't changed, it gets an empty `304 Not Modified`.
+// * HTML: cached, but checked every time. A new firmware shows up right away.
+// * Everything else: has a hash in its name, so it's cached forever. A new version has a different name.
+//
+// picoserve's `File` does the ETag and 304 part: we only add the headers.
+
+use picoserve::response::fs::{Directory, File};
+
+// HTML: always ask whether it has changed
+const REVALIDATE: &[(&str, &str)] = &[("Content-Encoding", "gzip"), ("Cache-Control", "no-cache")];
+
+// Hashed names: never changes
+const IMMUTABLE: &[(&str, &str)] = &[("Content-Encoding", "gzip"), ("Cache-Control", "public, max-age=31536000, immutable")];
+
+// `INDEX`: index.html; `STATIC`: every file, mount under "/static"
+include!(concat!(env!("OUT_DIR"), "/assets/assets.rs"));
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use picoserve::routing::{self, Router};
+    use crate::testing;
+
+    fn get(path: &str, headers: &str) -> testing::Response {
+        let app = Router::new()
+            .route("/", routing::get_service(INDEX))
+            .nest_service("/static", STATIC);
+        testing::serve(&app, &(), &format!("GET {path} HTTP/1.1\r\nConnection: close\r\n{headers}\r\n"))
+    }
+
+    fn gunzip(data: &[u8]) -> String {
+        let mut text = String::new();
+        flate2::read::GzDecoder::new(data).read_to_string(&mut text).unwrap();
+        text
+    }
+
+    // "app.js" -> "/static/app.0123abcd.js"
+    fn hashed(name: &str) -> String {
+        let (stem, ext) = name.rsplit_once('.').unwrap();
+        let (hashed, _) = STATIC.files.iter()
+            .find(|(hashed, _)| hashed.starts_with(&format!("{stem}.")) && hashed.ends_with(&format!(".{ext}")))
+            .unwrap();
+        format!("/static/{hashed}")
+    }
+
+    #[test]
+    fn test_index() {
+        let response = get("/", "");
+        assert_eq!(response.status, 200);
+        assert_eq!(response.header("Content-Type"), Some("text/html; charset=utf-8"));
+        assert_eq!(response.header("Content-Encoding"), Some("gzip"));
+        assert_eq!(response.header("Cache-Control"), Some("no-cache"));
+
+        // Links point to the hashed names
+        let html = gunzip(&response.body);
+        assert!(html.contains(&format!(r#"<script src="{}">"#, hashed("app.js"))), "{html}");
+        assert!(html.contains(&format!(r#"href="{}""#, hashed("style.css"))), "{html}");
+        assert!(!html.contains("/static/app.js"));
+
+        assert_eq!(gunzip(&get("/static/index.html", "").body), html);
+    }
+
+    #[test]
+    fn test_hashed() {
+        let path = hashed("app.js");
+        assert_eq!(path.len(), "/static/app.0123abcd.js".len());
+
+        let response = get(&path, "");
+        assert_eq!(response.status, 200);
+        assert_eq!(response.header("Content-Type"), Some("application/javascript; charset=utf-8"));
+        assert_eq!(response.header("Cache-Control"), Some("public, max-age=31536000, immutable"));
+        assert_eq!(gunzip(&response.body), include_str!("../assets/app.js"));
+        // Smaller
+        assert!(response.body.len() < include_str!("../assets/app.js").len());
+
+        // The original name is not served
+        assert_eq!(get("/static/app.js", "").status, 404);
+    }
+
+    #[test]
+    fn test_not_modified() {
+        let etag = get("/", "").header("ETag").unwrap().to_string();
+
+        // Same ETag: nothing to send
+        let response = get("/", &format!("If-None-Match: {etag}\r\n"));
+        assert_eq!(response.status, 304);
+        assert!(response.body.is_empty());
+
+        // Another version: send it
+        let response = get("/", "If-None-Match: \"0123\"\r\n");
+        assert_eq!(response.status, 200);
+        assert!(!response.body.is_empty());
+    }
+}
@@ -0,0 +1,255 @@
+// Authentication: a picoserve middleware ("layer") that guards the routes.
+//
+// Credentials are set at compile time. One of:
+//   $ API_TOKEN=secret cargo run                     ->  Authorization: Bearer secret
+//   $ API_USER=admin API_PASSWORD=secret cargo run   ->  Authorization: Basic YWRtaW46c2VjcmV0
+// Try it:
+//   $ curl -X PUT http://192.168.2.150/api/led -d '{"is_on":true}' -H 'Authorization: Bearer secret'
+//   $ curl -X PUT http://192.168.2.150/api/led -d '{"is_on":true}' -u admin:secret
+//
+// No credentials: 401 Unauthorized, with a `WWW-Authenticate` header that tells the client what to send.
+// With basic auth, the browser shows a login dialog and remembers the password.
+//
+// Some routes can stay open for reading: GET and HEAD requests to the `public` paths go through.
+// Whatever changes things, always needs credentials.
+//
+// NOTE: this is plain HTTP: anyone who can sniff the Wi-Fi sees the credentials.
+// It keeps your flatmates away from the LED, not a determined attacker.
+
+use picoserve::{
+    io::Read,
+    request::RequestParts,
+    response::{IntoResponse, ResponseWriter},
+    routing::{Layer, Next},
+    ResponseSent,
+};
+
+use crate::api::ApiError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Credentials {
+    // Authorization: Bearer <token>
+    Bearer(&'static str),
+    // Authorization: Basic base64(<username>:<password>)
+    Basic { username: &'static str, password: &'static str },
+}
+
+impl Credentials {
+    // Pick credentials from compile-time env variables: pass `option_env!()`s.
+    // Neither is set: fails the build. Use it in a `const`.
+    pub const fn from_env(token: Option<&'static str>, username: Option<&'static str>, password: Option<&'static str>) -> Self {
+        match (token, username, password) {
+            (Some(token), _, _) => Self::Bearer(token),
+            (None, Some(username), Some(password)) => Self::Basic { username, password },
+            _ => panic!("Set API_TOKEN, or API_USER and API_PASSWORD"),
+        }
+    }
+
+    // The `WWW-Authenticate` header: what we expect
+    pub fn challenge(&self) -> &'static str {
+        match self {
+            Self::Bearer(_) => r#"Bearer realm="esp32""#,
+            Self::Basic { .. } => r#"Basic realm="esp32", charset="UTF-8""#,
+        }
+    }
+
+    // Check the `Authorization` header
+    pub fn verify(&self, authorization: &[u8]) -> bool {
+        match self {
+            Self::Bearer(token) => match strip_scheme(authorization, b"Bearer ") {
+                Some(given) => constant_time_eq(given, token.as_bytes()),
+                None => false,
+            },
+            Self::Basic { username, password } => {
+                let Some(encoded) = strip_scheme(authorization, b"Basic ") else { return false };
+                let Some(decoded) = base64_decode::<128>(encoded) else { return false };
+                let Some(colon) = decoded.iter().position(|&b| b == b':') else { return false };
+                let (given_username, given_password) = (&decoded[..colon], &decoded[colon + 1..]);
+                // `&`, not `&&`: check both, always. Otherwise the timing tells whether the username is right.
+                constant_time_eq(given_username, username.as_bytes())
+                    & constant_time_eq(given_password, password.as_bytes())
+            }
+        }
+    }
+}
+
+// The middleware: `router.layer(Auth::new(credentials).with_public(&["/"]))`
+pub struct Auth {
+    credentials: Credentials,
+    // Paths that anyone can GET
+    public: &'static [&'static str],
+}
+
+impl Auth {
+    pub const fn new(credentials: Credentials) -> Self {
+        Self { credentials, public: &[] }
+    }
+
+    // These paths are read-only for anyone.
+    // Exact match; "/static/*" matches everything under "/static/".
+    pub const fn with_public(mut self, paths: &'static [&'static str]) -> Self {
+        self.public = paths;
+        self
+    }
+
+    // Let the request through?
+    pub fn allows(&self, method: &str, path: &str, authorization: Option<&[u8]>) -> bool {
+        let is_read_only = matches!(method, "GET" | "HEAD");
+        let is_public = self.public.iter().any(|&public| match public.strip_suffix('*') {
+            Some(prefix) => path.starts_with(prefix),
+            None => path == public,
+        });
+        if is_read_only && is_public {
+            return true;
+        }
+        authorization.is_some_and(|authorization| self.credentials.verify(authorization))
+    }
+}
+
+impl<State, PathParameters> Layer<State, PathParameters> for Auth {
+    type NextState = State;
+    type NextPathParameters = PathParameters;
+
+    async fn call_layer<'a, R: Read + 'a, NextLayer: Next<'a, R, State, PathParameters>, W: ResponseWriter<Error = R::Error>>(
+        &self,
+        next: NextLayer,
+        state: &State,
+        path_parameters: PathParameters,
+        request_parts: RequestParts<'_>,
+        response_writer: W,
+    ) -> Result<ResponseSent, W::Error> {
+        let authorization = request_parts.headers().get("Authorization").map(|value| value.as_raw());
+        if self.allows(request_parts.method(), request_parts.path().encoded(), authorization) {
+            next.run(state, path_parameters, response_writer).await
+        } else {
+            // Skip the body, and respond
+            let connection = next.into_connection().await?;
+            ApiError::Unauthorized(self.credentials.challenge())
+                .write_to(connection, response_writer)
+                .await
+        }
+    }
+}
+
+// "Bearer xxx" -> "xxx". The scheme is case-insensitive.
+fn strip_scheme<'a>(header: &'a [u8], scheme: &[u8]) -> Option<&'a [u8]> {
+    let (given, rest) = header.split_at_checked(scheme.len())?;
+    given.eq_ignore_ascii_case(scheme).then(|| rest.trim_ascii())
+}
+
+// Compare secrets in constant time.
+// A regular `==` returns at the first mismatch: by measuring how long it takes,
+// one can guess the secret byte by byte. This one looks at every byte, always.
+// The length does leak; the contents don't.
+pub fn constant_time_eq(given: &[u8], expected: &[u8]) -> bool {
+    let mut diff = (given.len() != expected.len()) as u8;
+    for (i, &a) in given.iter().enumerate() {
+        let b = expected.get(i).copied().unwrap_or(0);
+        diff |= a ^ b;
+    }
+    // Don't let the compiler get smart and add an early exit
+    core::hint::black_box(diff) == 0
+}
+
+// Decode base64 into a fixed buffer. `None`: invalid, or too long.
+fn base64_decode<const N: usize>(input: &[u8]) -> Option<heapless::Vec<u8, N>> {
+    fn value(c: u8) -> Option<u32> {
+        Some(match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        } as u32)
+    }
+
+    let input = input.strip_suffix(b"==").or_else(|| input.strip_suffix(b"=")).unwrap_or(input);
+    let mut output = heapless::Vec::new();
+    // 4 chars -> 24 bits -> 3 bytes. The last chunk may be shorter.
+    for chunk in input.chunks(4) {
+        if chunk.len() == 1 {
+            return None;
+        }
+        let mut bits = 0;
+        for &c in chunk {
+            bits = (bits << 6) | value(c)?;
+        }
+        bits <<= 6 * (4 - chunk.len());
+        for &byte in &bits.to_be_bytes()[1..chunk.len()] {
+            output.push(byte).ok()?;
+        }
+    }
+    Some(output)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: Credentials = Credentials::Bearer("secret");
+    const BASIC: Credentials = Credentials::Basic { username: "admin", password: "secret" };
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secreT", b"secret"));
+        assert!(!constant_time_eq(b"secret!", b"secret"));
+        assert!(!constant_time_eq(b"secre", b"secret"));
+        assert!(!constant_time_eq(b"", b"secret"));
+        assert!(constant_time_eq(b"", b""));
+    }
+
+    #[test]
+    fn test_base64() {
+        let decode = |s: &str| base64_decode::<32>(s.as_bytes()).map(|v| String::from_utf8(v.to_vec()).unwrap());
+        assert_eq!(decode("YWRtaW46c2VjcmV0").as_deref(), Some("admin:secret"));
+        assert_eq!(decode("YQ==").as_deref(), Some("a"));
+        assert_eq!(decode("YWI=").as_deref(), Some("ab"));
+        assert_eq!(decode("").as_deref(), Some(""));
+        assert_eq!(decode("YWJj!"), None);
+        assert_eq!(decode("Y"), None);
+        // Too long for the buffer
+        assert_eq!(decode(&"YWJj".repeat(11)), None);
+    }
+
+    #[test]
+    fn test_verify() {
+        assert!(TOKEN.verify(b"Bearer secret"));
+        assert!(TOKEN.verify(b"bearer secret"));
+        assert!(!TOKEN.verify(b"Bearer nope"));
+        assert!(!TOKEN.verify(b"Basic secret"));
+        assert!(!TOKEN.verify(b"secret"));
+
+        assert!(BASIC.verify(b"Basic YWRtaW46c2VjcmV0")); // admin:secret
+        assert!(!BASIC.verify(b"Basic YWRtaW46bm9wZQ==")); // admin:nope
+        assert!(!BASIC.verify(b"Basic cm9vdDpzZWNyZXQ=")); // root:secret
+        assert!(!BASIC.verify(b"Basic YWRtaW4=")); // admin
+        assert!(!BASIC.verify(b"Basic !!!"));
+        assert!(!BASIC.verify(b"Bearer secret"));
+    }
+
+    #[test]
+    fn test_from_env() {
+        assert_eq!(Credentials::from_env(Some("secret"), None, None), TOKEN);
+        assert_eq!(Credentials::from_env(None, Some("admin"), Some("secret")), BASIC);
+    }
+
+    #[test]
+    fn test_allows() {
+        let auth = Auth::new(TOKEN).with_public(&["/", "/api/led", "/static/*"]);
+        // Public: read-only
+        assert!(auth.allows("GET", "/api/led", None));
+        assert!(auth.allows("HEAD", "/", None));
+        assert!(auth.allows("GET", "/static/app.js", None));
+        assert!(!auth.allows("PUT", "/api/led", None));
+        assert!(auth.allows("PUT", "/api/led", Some(b"Bearer secret")));
+        // Not public
+        assert!(!auth.allows("GET", "/api/status", None));
+        assert!(!auth.allows("GET", "/api/led/", None));
+        assert!(!auth.allows("GET", "/static", None));
+        assert!(!auth.allows("GET", "/api/status", Some(b"Bearer nope")));
+        assert!(auth.allows("GET", "/api/status", Some(b"Bearer secret")));
+    }
+}
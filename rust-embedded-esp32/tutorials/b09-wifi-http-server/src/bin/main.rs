@@ -12,6 +12,7 @@ use esp_hal::{
     timer::timg::TimerGroup,
     interrupt::software::SoftwareInterruptControl,
     gpio,
+    tsens::{self, TemperatureSensor},
 };
 
 // $ cargo add embassy-executor --features task-arena-size-65536
@@ -20,7 +21,7 @@ use embassy_time::{Duration, Timer};
 
 // Our library
 use b08_wifi_http_client::wifi;
-use b09_wifi_http_server::{webserver, led};
+use b09_wifi_http_server::{webserver, led, sensors, board::AppState, events::{Event, Hub, Uptime}};
 
 #[esp_rtos::main]
 async fn main(spawner: Spawner) -> ! {
@@ -35,22 +36,37 @@ async fn main(spawner: Spawner) -> ! {
     esp_rtos::start(timg0.timer0, sw_interrupt.software_interrupt0);
 
     // Init WiFi & network stack
-    let stack = wifi::start_wifi(&spawner, peripherals.WIFI).await;
+    // No Wi-Fi settings yet? Starts the setup AP. GPIO9: the BOOT button, hold it to start over.
+    let stack = wifi::start_wifi(&spawner, peripherals.WIFI, peripherals.FLASH, peripherals.GPIO9).await.unwrap();
+
+    // App state: channels between the web server and the tasks
+    let state = picoserve::make_static!(AppState, AppState {
+        led: picoserve::make_static!(led::LedControl, led::LedControl::new_with(false)),
+        events: picoserve::make_static!(Hub, Hub::new()),
+    });
 
     // Spawn webserver tasks
     let web_app = webserver::WebApp::default();
     for id in 0..webserver::WEB_TASK_POOL_SIZE {
-        spawner.must_spawn(webserver::web_task(id, stack, web_app.router, web_app.config));
+        spawner.must_spawn(webserver::web_task(id, stack, web_app.router, web_app.config, state));
     }
     defmt::info!("Web server started!");
 
     // Spawm LED tasks
     spawner.must_spawn(led::led_task(
-        gpio::Output::new(peripherals.GPIO8, gpio::Level::Low, gpio::OutputConfig::default()),
+        gpio::Output::new(peripherals.GPIO8, gpio::Level::High, gpio::OutputConfig::default()),
+        state.led,
+        state.events,
     ));
 
-    // Sleep
+    // Spawn sensor tasks
+    let temperature_sensor = TemperatureSensor::new(peripherals.TSENS, tsens::Config::default()).unwrap();
+    spawner.must_spawn(sensors::sensor_task(temperature_sensor, state.events));
+
+    // Publish the uptime
     loop {
+        let secs = embassy_time::Instant::now().as_secs();
+        state.events.publish(Event::Uptime(Uptime { secs }));
         Timer::after(Duration::from_secs(1)).await;
     }
 }
@@ -0,0 +1,37 @@
+use b08_wifi_http_client::wifi;
+use crate::{api::{self, Device}, events::Hub, led::LedControl};
+
+// App state: how handlers talk to the tasks. No globals: main() creates the parts, and hands them out.
+// Every actuator gets a channel: the handler sends, the task that owns the hardware receives.
+// Adding a device? Add a field here, and pass the other end to its task.
+#[derive(Clone, Copy)]
+pub struct AppState {
+    // LED: on/off
+    pub led: &'static LedControl,
+    // Tasks publish their changes here; `/events` subscribes
+    pub events: &'static Hub,
+}
+
+impl Device for AppState {
+    fn status(&self) -> api::Status {
+        api::Status {
+            uptime_ms: embassy_time::Instant::now().as_millis(),
+            heap: api::Heap { used: esp_alloc::HEAP.used(), free: esp_alloc::HEAP.free() },
+            rssi: wifi::rssi(),
+            led: api::Led { is_on: self.led() },
+        }
+    }
+
+    // The latest command. The LED task applies it right away.
+    fn led(&self) -> bool {
+        self.led.try_get().unwrap_or_default()
+    }
+
+    fn set_led(&self, is_on: bool) {
+        self.led.sender().send(is_on);
+    }
+
+    fn events(&self) -> &'static Hub {
+        self.events
+    }
+}
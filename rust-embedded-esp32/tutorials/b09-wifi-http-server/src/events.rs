@@ -0,0 +1,308 @@
+// Events: publish/subscribe hub + Server-Sent Events.
+//
+// Tasks publish what they observe: the LED task publishes the LED state, the sensor task the temperature,
+// main() the uptime. The hub is a part of the app state: see `AppState`. Web clients subscribe to `GET /events` and get every change pushed to them:
+//
+//   event: led
+//   data: {"is_on":true}
+//
+//   event: temperature
+//   data: {"celsius":23.5}
+//
+// In the browser:
+//   new EventSource('/events').addEventListener('led', e => console.log(JSON.parse(e.data)));
+//
+// The hub is an embassy-sync `PubSubChannel`: every subscriber gets its own copy of every message.
+// Publishing never blocks: if a subscriber is too slow, it loses the oldest messages.
+// The hub also remembers the latest value of everything: a new subscriber gets those first,
+// so the page shows the whole picture right away, without waiting for the next change.
+// A subscriber that lost messages starts over the same way: the latest values, then the changes.
+
+use core::cell::RefCell;
+use embassy_sync::{
+    blocking_mutex::{raw::CriticalSectionRawMutex, Mutex},
+    pubsub::{PubSubChannel, Subscriber, WaitResult},
+};
+use picoserve::{
+    extract::State,
+    io::Write,
+    response::{sse::{EventSource, EventStream, EventWriter}, Json},
+};
+
+use crate::api::{ApiError, Device, Led};
+
+// How many `/events` clients at a time.
+// Every one of them keeps an http worker busy: leave some workers for the regular requests.
+pub const MAX_SUBSCRIBERS: usize = 2;
+
+// Messages queued per subscriber
+const CAPACITY: usize = 8;
+
+#[derive(serde::Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Temperature {
+    pub celsius: f32,
+}
+
+#[derive(serde::Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Uptime {
+    pub secs: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    Led(Led),
+    Temperature(Temperature),
+    Uptime(Uptime),
+}
+
+// How many kinds of events there are: see `Event::kind()`
+const KINDS: usize = 3;
+
+impl Event {
+    // SSE event name
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Led(_) => "led",
+            Self::Temperature(_) => "temperature",
+            Self::Uptime(_) => "uptime",
+        }
+    }
+
+    // Index: to remember the latest event of every kind
+    fn kind(&self) -> usize {
+        match self {
+            Self::Led(_) => 0,
+            Self::Temperature(_) => 1,
+            Self::Uptime(_) => 2,
+        }
+    }
+
+    // Send it as an SSE event, with JSON data
+    async fn write_to<W: Write>(self, writer: &mut EventWriter<W>) -> Result<(), W::Error> {
+        match self {
+            Self::Led(v) => writer.write_event(self.name(), Json(v)).await,
+            Self::Temperature(v) => writer.write_event(self.name(), Json(v)).await,
+            Self::Uptime(v) => writer.write_event(self.name(), Json(v)).await,
+        }
+    }
+}
+
+type Channel = PubSubChannel<CriticalSectionRawMutex, Event, CAPACITY, MAX_SUBSCRIBERS, 0>;
+
+pub struct Hub {
+    channel: Channel,
+    // The latest event of every kind
+    latest: Mutex<CriticalSectionRawMutex, RefCell<[Option<Event>; KINDS]>>,
+}
+
+impl Default for Hub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hub {
+    // const: can be a `static`
+    pub const fn new() -> Self {
+        Self {
+            channel: PubSubChannel::new(),
+            latest: Mutex::new(RefCell::new([None; KINDS])),
+        }
+    }
+
+    // Publish an event, if it's a change. Returns `true` if published.
+    // Doesn't block: can be called from anywhere.
+    pub fn publish(&self, event: Event) -> bool {
+        let changed = self.latest.lock(|latest| {
+            let latest = &mut latest.borrow_mut()[event.kind()];
+            let changed = *latest != Some(event);
+            *latest = Some(event);
+            changed
+        });
+        if changed {
+            // Immediate publisher: doesn't take a publisher slot, never waits
+            self.channel.immediate_publisher().publish_immediate(event);
+        }
+        changed
+    }
+
+    // The latest events, one of each kind
+    pub fn latest(&self) -> [Option<Event>; KINDS] {
+        self.latest.lock(|latest| *latest.borrow())
+    }
+
+    // Subscribe to events. `None`: too many subscribers already.
+    pub fn subscribe(&self) -> Option<Subscription<'_>> {
+        // Subscribe first, then take the snapshot: a change in between arrives twice, rather than never
+        let subscriber = self.channel.subscriber().ok()?;
+        Some(Subscription { hub: self, subscriber, latest: self.latest(), next: 0 })
+    }
+}
+
+pub struct Subscription<'a> {
+    hub: &'a Hub,
+    subscriber: Subscriber<'a, CriticalSectionRawMutex, Event, CAPACITY, MAX_SUBSCRIBERS, 0>,
+    // The latest events, as of subscription (or of the last `resync()`): these go first
+    latest: [Option<Event>; KINDS],
+    next: usize,
+}
+
+impl Subscription<'_> {
+    // Wait for the next event
+    pub async fn next(&mut self) -> Event {
+        loop {
+            if let Some(event) = self.next_latest() {
+                return event;
+            }
+            match self.subscriber.next_message().await {
+                WaitResult::Message(event) => return event,
+                WaitResult::Lagged(_) => self.resync(),
+            }
+        }
+    }
+
+    // The next event, if there's one right now
+    pub fn try_next(&mut self) -> Option<Event> {
+        loop {
+            if let Some(event) = self.next_latest() {
+                return Some(event);
+            }
+            match self.subscriber.try_next_message()? {
+                WaitResult::Message(event) => return Some(event),
+                WaitResult::Lagged(_) => self.resync(),
+            }
+        }
+    }
+
+    // Too slow: missed some. Only changes are published, so a missed LED change may never come again.
+    // Start over: drop what's queued, and send the latest of every kind.
+    // Like `Hub::subscribe()`: a change in between arrives twice, rather than never.
+    fn resync(&mut self) {
+        while self.subscriber.try_next_message().is_some() {}
+        self.latest = self.hub.latest();
+        self.next = 0;
+    }
+
+    // The snapshot taken at subscription, one by one
+    fn next_latest(&mut self) -> Option<Event> {
+        while let Some(slot) = self.latest.get(self.next) {
+            self.next += 1;
+            if slot.is_some() {
+                return *slot;
+            }
+        }
+        None
+    }
+}
+
+// SSE: send events until the client disconnects
+impl EventSource for Subscription<'_> {
+    async fn write_events<W: Write>(mut self, mut writer: EventWriter<W>) -> Result<(), W::Error> {
+        loop {
+            self.next().await.write_to(&mut writer).await?;
+        }
+    }
+}
+
+// GET /events
+pub async fn get_events<D: Device>(State(device): State<D>) -> Result<EventStream<Subscription<'static>>, ApiError> {
+    device.events().subscribe().map(EventStream).ok_or(ApiError::TooManySubscribers)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ON: Event = Event::Led(Led { is_on: true });
+    const OFF: Event = Event::Led(Led { is_on: false });
+
+    fn events(subscription: &mut Subscription) -> Vec<Event> {
+        core::iter::from_fn(|| subscription.try_next()).collect()
+    }
+
+    #[test]
+    fn test_publish_changes() {
+        let hub = Hub::new();
+        let mut subscription = hub.subscribe().unwrap();
+
+        assert!(hub.publish(ON));
+        // Not a change: not published
+        assert!(!hub.publish(ON));
+        assert!(hub.publish(Event::Uptime(Uptime { secs: 1 })));
+        assert!(hub.publish(OFF));
+
+        assert_eq!(events(&mut subscription), vec![ON, Event::Uptime(Uptime { secs: 1 }), OFF]);
+        assert_eq!(events(&mut subscription), vec![]);
+    }
+
+    #[test]
+    fn test_latest_first() {
+        let hub = Hub::new();
+        hub.publish(ON);
+        hub.publish(Event::Temperature(Temperature { celsius: 20.0 }));
+        hub.publish(OFF);
+
+        // A new subscriber gets the latest of every kind, then the changes
+        let mut subscription = hub.subscribe().unwrap();
+        hub.publish(Event::Temperature(Temperature { celsius: 21.0 }));
+        assert_eq!(events(&mut subscription), vec![
+            OFF,
+            Event::Temperature(Temperature { celsius: 20.0 }),
+            Event::Temperature(Temperature { celsius: 21.0 }),
+        ]);
+    }
+
+    #[test]
+    fn test_slow_subscriber() {
+        let hub = Hub::new();
+        let mut subscription = hub.subscribe().unwrap();
+
+        // Publishing never blocks: the slow subscriber loses events, and gets the latest value instead
+        for secs in 0..20 {
+            hub.publish(Event::Uptime(Uptime { secs }));
+        }
+        assert_eq!(events(&mut subscription), vec![Event::Uptime(Uptime { secs: 19 })]);
+
+        // Async: same thing
+        hub.publish(ON);
+        assert_eq!(embassy_futures::block_on(subscription.next()), ON);
+    }
+
+    #[test]
+    fn test_lagged_keeps_every_kind() {
+        let hub = Hub::new();
+        let mut subscription = hub.subscribe().unwrap();
+
+        // The LED and the temperature change once, then the uptime floods the queue:
+        // their changes are lost, and won't be published again
+        hub.publish(ON);
+        hub.publish(Event::Temperature(Temperature { celsius: 20.0 }));
+        for secs in 0..CAPACITY as u64 * 2 {
+            hub.publish(Event::Uptime(Uptime { secs }));
+        }
+
+        // Still, the client ends up with the final state of everything
+        let mut state = [None; KINDS];
+        for event in events(&mut subscription) {
+            state[event.kind()] = Some(event);
+        }
+        assert_eq!(state, hub.latest());
+        assert_eq!(state[ON.kind()], Some(ON));
+
+        // Then, the changes as usual
+        hub.publish(OFF);
+        assert_eq!(events(&mut subscription), vec![OFF]);
+    }
+
+    #[test]
+    fn test_max_subscribers() {
+        let hub = Hub::new();
+        let subscriptions: Vec<_> = (0..MAX_SUBSCRIBERS).map(|_| hub.subscribe().unwrap()).collect();
+        assert!(hub.subscribe().is_none());
+
+        // Unsubscribe: the slot is free again
+        drop(subscriptions);
+        assert!(hub.subscribe().is_some());
+    }
+}
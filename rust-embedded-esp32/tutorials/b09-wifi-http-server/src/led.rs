@@ -1,32 +1,26 @@
-use embassy_time::{Duration, Timer};
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, watch::Watch};
 use esp_hal::gpio::Output;
 
-use core::sync::atomic::{AtomicBool, Ordering};
+use crate::{api::Led, events::{Event, Hub}};
 
-// Primitive shared-memory communication:
-// 1. webserver will set the value
-// 2. LED task will read it
-pub static LED_STATE: AtomicBool = AtomicBool::new(false);
+// Commands for the LED: on/off.
+// A `Watch` holds the latest value, and wakes up the receiver when it changes: no polling.
+// One receiver: the LED task.
+pub type LedControl = Watch<CriticalSectionRawMutex, bool, 1>;
 
-// Task: toggle LED, controlled by static `LED_STATE`
+// Task: turn the LED on and off, as commanded by `control`. Publish the changes.
 #[embassy_executor::task]
-pub async fn led_task(mut led: Output<'static>) {
-    // NOTE: reading a GPIO state is as easy as reading a mem region.
-    // Therefore we don't have to keep this bool here: just read current state and compare.
-    let mut current_led_state: bool = led.is_set_low(); // the LED is inverted
+pub async fn led_task(mut led: Output<'static>, control: &'static LedControl, events: &'static Hub) {
+    let mut receiver = control.receiver().expect("LED control has one receiver only");
     loop {
-        // Read expected LED state
-        let set_led_state = LED_STATE.load(Ordering::Relaxed);
+        // The LED is inverted
+        let is_on = led.is_set_low();
+        events.publish(Event::Led(Led { is_on }));
 
-        // Set LED. Only if changed.
-        match (current_led_state, set_led_state) {
-            (true, false) => led.set_high(),
-            (false, true) => led.set_low(),
-            _ => ()
+        // Sleep until there's a new command
+        match receiver.changed().await {
+            true => led.set_low(),
+            false => led.set_high(),
         }
-        current_led_state = set_led_state;
-
-        // Sleep
-        Timer::after(Duration::from_millis(50)).await;
     }
 }
@@ -1,5 +1,21 @@
-#![no_std]
-#![feature(impl_trait_in_assoc_type)]
+#![cfg_attr(not(test), no_std)]
+#![cfg_attr(target_arch = "riscv32", feature(impl_trait_in_assoc_type))]
 
+// Pure logic: runs on the host too
+pub mod api;
+pub mod assets;
+pub mod auth;
+pub mod events;
+
+#[cfg(test)]
+mod testing;
+
+// ESP-only
+#[cfg(target_arch = "riscv32")]
 pub mod webserver;
+#[cfg(target_arch = "riscv32")]
 pub mod led;
+#[cfg(target_arch = "riscv32")]
+pub mod board;
+#[cfg(target_arch = "riscv32")]
+pub mod sensors;
@@ -0,0 +1,18 @@
+use embassy_time::{Duration, Timer};
+use esp_hal::tsens::TemperatureSensor;
+
+use crate::events::{Event, Hub, Temperature};
+
+// Task: read the chip's internal temperature sensor, publish the changes.
+// It measures the die, not the room: expect 10-20°C above the ambient temperature.
+#[embassy_executor::task]
+pub async fn sensor_task(sensor: TemperatureSensor<'static>, events: &'static Hub) {
+    loop {
+        // The reading jitters: round it to 0.5°C, or every reading is a "change"
+        let celsius = sensor.get_temperature().to_celsius();
+        let celsius = libm::roundf(celsius * 2.0) / 2.0;
+        events.publish(Event::Temperature(Temperature { celsius }));
+
+        Timer::after(Duration::from_secs(2)).await;
+    }
+}
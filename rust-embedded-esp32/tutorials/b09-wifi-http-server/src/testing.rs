@@ -0,0 +1,89 @@
+// Test helpers: run a picoserve app in memory. No network, no timers: runs on the host.
+
+use core::convert::Infallible;
+use picoserve::routing::{PathRouter, Router};
+
+pub struct Response {
+    pub status: u16,
+    // Status line and headers
+    pub head: String,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.head.lines()
+            .filter_map(|line| line.split_once(": "))
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8(self.body.clone()).unwrap()
+    }
+}
+
+// Send a raw request; get the response
+pub fn serve<S, P: PathRouter<S>>(app: &Router<P, S>, state: &S, request: &str) -> Response {
+    let config = picoserve::Config::new(picoserve::Timeouts {
+        start_read_request: None, read_request: None, write: None, persistent_start_read_request: None,
+    });
+
+    let mut response = vec![];
+    let socket = TestSocket { request: request.as_bytes(), response: &mut response };
+    let mut buffer = [0; 2048];
+    embassy_futures::block_on(picoserve::serve_with_state(app, NoTimer, &config, &mut buffer, socket, state))
+        .unwrap();
+
+    // "HTTP/1.1 200 OK\r\n<headers>\r\n\r\n<body>"
+    let split = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+    let head = String::from_utf8(response[..split].to_vec()).unwrap();
+    let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+    Response { status, head, body: response[split + 4..].to_vec() }
+}
+
+// In-memory connection: reads the request, collects the response
+struct TestSocket<'r> {
+    request: &'r [u8],
+    response: &'r mut Vec<u8>,
+}
+
+struct Sink<'a>(&'a mut Vec<u8>);
+
+impl picoserve::io::ErrorType for Sink<'_> {
+    type Error = Infallible;
+}
+impl picoserve::io::Write for Sink<'_> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Infallible> {
+        self.0.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+}
+
+impl<'r> picoserve::io::Socket for TestSocket<'r> {
+    type Error = Infallible;
+    type ReadHalf<'a> = &'a mut &'r [u8] where Self: 'a;
+    type WriteHalf<'a> = Sink<'a> where Self: 'a;
+
+    fn split(&mut self) -> (Self::ReadHalf<'_>, Self::WriteHalf<'_>) {
+        (&mut self.request, Sink(self.response))
+    }
+
+    async fn shutdown<T: picoserve::Timer>(
+        self, _timeouts: &picoserve::Timeouts<T::Duration>, _timer: &mut T,
+    ) -> Result<(), picoserve::Error<Infallible>> {
+        Ok(())
+    }
+}
+
+// No timeouts: everything is in memory anyway
+struct NoTimer;
+
+impl picoserve::Timer for NoTimer {
+    type Duration = ();
+    type TimeoutError = Infallible;
+
+    async fn run_with_timeout<F: Future>(&mut self, _: (), future: F) -> Result<F::Output, Infallible> {
+        Ok(future.await)
+    }
+}
@@ -1,71 +1,56 @@
-use core::sync::atomic::Ordering;
-
 use embassy_net::Stack;
 use embassy_time::Duration;
 
 // Picoserve: async http server for bare-metal environments.
 // $ cargo add picoserve --features embassy
 use picoserve::{
-    AppBuilder, AppRouter, response::{File, IntoResponse}, routing::{self, Router}
+    AppWithStateBuilder, AppRouter, routing::{self, Router}
 };
 
-// Serde: Serialize/Deserialize data structures
-// $ cargo add serde --no-default-features --features derive
-use serde;
+use crate::{api, assets, auth::{Auth, Credentials}, board::AppState, events};
 
 // How many embassy tasks to spawm as http server workers?
-pub const WEB_TASK_POOL_SIZE: usize = 2;
+// Every `/events` client keeps one busy: have more than `events::MAX_SUBSCRIBERS`
+pub const WEB_TASK_POOL_SIZE: usize = 4;
+
+
+// Credentials for the control endpoints: set at compile time. See auth.rs
+const CREDENTIALS: Credentials = Credentials::from_env(
+    option_env!("API_TOKEN"),
+    option_env!("API_USER"),
+    option_env!("API_PASSWORD"),
+);
 
+// Anyone can read these; changing things needs credentials
+const PUBLIC: &[&str] = &["/", "/static/*", "/events", "/api/status", "/api/led", "/api/openapi.json"];
 
 pub struct Application;
 
-// Implement the `AppBuilder` trait for application: this creates a router.
-// If you need a router with state, impl `AppWithStateBuilder`
-impl AppBuilder for Application {
-    type PathRouter = impl routing::PathRouter;
+// Implement the `AppWithStateBuilder` trait for application: this creates a router with state.
+// Handlers get the state with the `State` extractor. No state? impl `AppBuilder`.
+impl AppWithStateBuilder for Application {
+    type State = AppState;
+    type PathRouter = impl routing::PathRouter<AppState>;
 
-    fn build_app(self) -> Router<Self::PathRouter> {
-        // Serve a static file.
-        // Its contents are embedded.
+    fn build_app(self) -> Router<Self::PathRouter, AppState> {
+        // Serve static files: gzipped and embedded at build time. See assets.rs
         Router::new()
-            .route(
-                "/",
-                routing::get_service(File::html(include_str!("index.html"))),
-            )
-            .route(
-                "/set-led",
-                routing::post(api_set_led),
-            )
+            .route("/", routing::get_service(assets::INDEX))
+            .nest_service("/static", assets::STATIC)
+            // REST API: see api.rs
+            .nest("/api", api::router::<AppState>())
+            // Server-Sent Events: see events.rs
+            .route("/events", routing::get(events::get_events::<AppState>))
+            // Check credentials: for every route above
+            .layer(Auth::new(CREDENTIALS).with_public(PUBLIC))
     }
 }
 
 
-// Request JSON
-#[derive(serde::Deserialize)]
-struct LedRequest {
-    is_on: bool,
-}
-
-// Response JSON
-#[derive(serde::Serialize)]
-struct LedResponse {
-    success: bool,
-}
-
-// API: set led
-async fn api_set_led(input: picoserve::extract::Json<LedRequest, 0>) -> impl IntoResponse {
-    // JSON value: put into LED_SATE
-    crate::led::LED_STATE.store(input.0.is_on, Ordering::Relaxed);
-
-    // Respond
-    picoserve::response::Json(LedResponse { success: true })
-}
-
-
 
 // Web app: holds config and an instance of picoserve router
 pub struct WebApp {
-    pub router: &'static Router<<Application as AppBuilder>::PathRouter>,
+    pub router: &'static AppRouter<Application>,
     pub config: &'static picoserve::Config<Duration>,
 }
 
@@ -95,12 +80,13 @@ pub async fn web_task(
     stack: Stack<'static>,
     router: &'static AppRouter<Application>,
     config: &'static picoserve::Config<Duration>,
+    state: &'static AppState,
 ) -> ! {
     let mut tcp_rx_buffer = [0; 1024];
     let mut tcp_tx_buffer = [0; 1024];
     let mut http_buffer = [0; 2048];
 
-    picoserve::listen_and_serve(
+    picoserve::listen_and_serve_with_state(
         id,
         router,
         config,
@@ -108,6 +94,7 @@ pub async fn web_task(
         &mut tcp_rx_buffer,
         &mut tcp_tx_buffer,
         &mut http_buffer,
+        state,
     )
     .await
 }
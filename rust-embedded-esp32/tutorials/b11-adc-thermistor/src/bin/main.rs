@@ -8,13 +8,17 @@ use defmt;
 // Non-blocking
 use nb;
 
-// Math for no_std
-use libm;
+// Thermistor math: ../../libs/thermistor
+use thermistor::{BParameter, Divider, Thermistor, Topology};
+
+// ADC counts -> millivolts: ../../libs/adc-cal
+use adc_cal::{Calibration, esp::efuse_line};
 
 use esp_hal::{
     clock::CpuClock,
     delay::Delay,
     analog::adc,
+    peripherals::ADC1,
     main,
 };
 
@@ -25,23 +29,37 @@ fn main() -> ! {
         .with_cpu_clock(CpuClock::max()));
 
     // ADC Input
-    // Attenuation: -11dB. Gives us a safe range of 0..3.9V = 1100 * 10 ^ (11/20)
+    // Attenuation: -11dB. Gives us the widest range: the datasheet recommends 0..2.5V
+    // `AdcCalBasic`: the ADC removes its own offset (eFuse), so that 0V reads as 0.
     let ldr_pin = peripherals.GPIO4;
     let mut adc_config = adc::AdcConfig::new();
-    let mut pin = adc_config.enable_pin(ldr_pin, adc::Attenuation::_11dB);
+    let mut pin = adc_config.enable_pin_with_cal::<_, adc::AdcCalBasic<ADC1>>(ldr_pin, ATTENUATION);
     let mut adc1 = adc::Adc::new(peripherals.ADC1, adc_config);
 
+    // Counts -> millivolts: use the factory calibration
+    let attenuation = ATTENUATION.into();
+    let calibration = Calibration::nominal()
+        .with(attenuation, efuse_line(ATTENUATION));
+
     let delay = Delay::new();
     loop {
-        let adc_value: u16 = match nb::block!(adc1.read_oneshot(&mut pin)) {
-            Ok(result) => result,
+        // Oversample: 16 readings, averaged
+        let samples = match adc_cal::oversample(16, || nb::block!(adc1.read_oneshot(&mut pin))) {
+            Ok(samples) => samples,
             Err(err) => {
                 defmt::error!("err={}", err);
                 continue;
             }
         };
-        let temperature = adc_value_to_temperature(adc_value, 11);
-        defmt::info!("Temperature: {}", temperature);
+        let reading = calibration.read(attenuation, &samples);
+        if reading.out_of_range {
+            defmt::warn!("ADC out of range: {} mV", reading.mv);
+        }
+
+        match THERMISTOR.temperature_c(reading.volts() as f64) {
+            Ok(temperature) => defmt::info!("Temperature: {} (V={}±{}mV)", temperature, reading.mv, reading.uncertainty_mv),
+            Err(err) => defmt::error!("Thermistor: {}", defmt::Debug2Format(&err)),
+        }
 
         delay.delay_millis(100);
     }
@@ -51,37 +69,16 @@ fn main() -> ! {
 
 // Math section
 
-// Our voltage divider circuit
-const ADC_VREF: f64 = 2.5; // ESP32 ADC Reference Voltage at the given attenuation (see docs)
-const VDV_VDD: f64 = 3.3;   // Input voltage on the divider
-const VDV_R1: f64 = 10_000.0;  // Second resistor in the voltage divider
+const ATTENUATION: adc::Attenuation = adc::Attenuation::_11dB;
 
-// ADC resolution: 12 bits
-// I.e. it maps voltages into 0..4095
-const ADC_MAX: i32 = 2 << 12;  // 4095
-
-// The typical B value for the NTC 103 thermistor is 3950.
-// The reference temperature is usually 25°C and 10kΩ
-const TRM_B_VALUE: f64 = 3950.0;
-const TRM_REF_TEMP: f64 = 25.0; // Reference temperature 25°C
-const TRM_REF_RES: f64 = 10_000.0; // Thermistor resistance at the Reference Temperature(25°C)
-
-
-// Calc: measured resistance for RT:
+// Our voltage divider circuit:
 //                         ┌──────────────── GPIO4
 //   V_DD ──────[ R_1 ]────┴───[ R_T ]────── GND
 //
-//   $V_{measured} = V_{ref} * {adc\_value} / {ADC\_MAX}$
-//   $R_T = R_1 / (V_{DD}/V_{measured} - 1)$
-fn adc_value_to_temperature(adc_value: u16, att_db: u8) -> f64 {
-    let V_measured: f64 = ADC_VREF * adc_value as f64 / ADC_MAX as f64;
-    let R_thermistor: f64 = VDV_R1 / (VDV_VDD / V_measured - 1.0);
-    // defmt::info!("adc_value={}", adc_value);
-    // defmt::info!("V_measured={}", V_measured);
-    // defmt::info!("R_thermistor={}", R_thermistor);
-
-    // $1/T = 1/T_0 + (1/B) * ln(R/R_0)$
-    const K: f64 = 273.15; // Kelvin
-    let inv_t = 1.0/(TRM_REF_TEMP + K) + (1.0/TRM_B_VALUE) * libm::log(R_thermistor / TRM_REF_RES);
-    return 1.0/inv_t - K;
-}
\ No newline at end of file
+// The typical B value for the NTC 103 thermistor is 3950.
+// The reference temperature is usually 25°C and 10kΩ.
+// Have the datasheet table? Use `SteinhartHart::fit()` with 3 points from it: it's way more accurate.
+const THERMISTOR: Thermistor<BParameter> = Thermistor {
+    divider: Divider { topology: Topology::NtcLow, vdd: 3.3, r_fixed: 10_000.0 },
+    model: BParameter::NTC_103_B3950,
+};